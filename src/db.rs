@@ -1,171 +1,1319 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
 
 use chrono::NaiveDate;
-use quick_xml::{events::Event, Reader};
+use quick_xml::{events::attributes::Attribute, events::Event, Reader};
+use serde::{Deserialize, Serialize};
 use whr::WhrBuilder;
 
-#[derive(Debug, Clone)]
+/// Color of the stones played by a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    Black,
+    White,
+}
+impl Color {
+    /// Color of the player playing the move at index `ply` of a game.
+    pub fn of_ply(ply: usize) -> Self {
+        if ply.is_multiple_of(2) {
+            Self::Black
+        } else {
+            Self::White
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
     pub black_elo: u64,
     pub white_elo: u64,
     pub moves: Vec<(u8, u8)>,
+    /// Color of the winner, or `None` for a draw.
+    pub winner: Option<Color>,
+    /// Ids of the players in the database, or 0 when the database has none.
+    pub black_id: u64,
+    pub white_id: u64,
+    /// Names of the players, when the database has them.
+    pub black_name: Option<String>,
+    pub white_name: Option<String>,
+}
+impl Game {
+    /// Elo of the player with the given color.
+    pub fn elo(&self, color: Color) -> u64 {
+        match color {
+            Color::Black => self.black_elo,
+            Color::White => self.white_elo,
+        }
+    }
+
+    /// Id of the player with the given color.
+    pub fn player_id(&self, color: Color) -> u64 {
+        match color {
+            Color::Black => self.black_id,
+            Color::White => self.white_id,
+        }
+    }
+}
+
+/// Reasons a database cannot be loaded.
+///
+/// Games are identified by their index among the games of the database, in the order they are
+/// written, along with their id in XML databases.
+#[derive(Debug)]
+pub enum DbError {
+    Io(std::io::Error),
+    /// The XML of the database is malformed.
+    Xml(quick_xml::Error),
+    /// The end date of a tournament is not a `YYYY-MM-DD` date, or is before 1900.
+    InvalidDate {
+        tournament: usize,
+        date: String,
+    },
+    /// A game belongs to a tournament without an end date, so its players cannot be rated.
+    MissingDate {
+        game_index: usize,
+        game_id: Option<usize>,
+    },
+    /// An attribute of a tournament or a game has an unexpected value.
+    InvalidAttribute {
+        name: String,
+        value: String,
+    },
+    /// A move of a game could not be parsed.
+    InvalidMove {
+        game_index: usize,
+        game_id: Option<usize>,
+        token: String,
+    },
+    /// A point of the board is played twice in a game.
+    DuplicateMove {
+        game_index: usize,
+        game_id: Option<usize>,
+        point: (u8, u8),
+    },
+    /// The database was compiled by another version of this tool.
+    CompiledVersion(u32),
+    /// The compiled database is corrupted.
+    Compiled(bincode::Error),
+    /// A line of an exported database is not a game.
+    Exported {
+        line: usize,
+        error: serde_json::Error,
+    },
+    InvalidRatings(String),
+}
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read database: {e}"),
+            Self::Xml(e) => write!(f, "malformed XML: {e}"),
+            Self::InvalidDate { tournament, date } => {
+                write!(
+                    f,
+                    "tournament {tournament}: invalid end date `{date}`, expected a `YYYY-MM-DD` date from {RATING_EPOCH}"
+                )
+            }
+            Self::MissingDate {
+                game_index,
+                game_id,
+            } => write!(
+                f,
+                "{}: its tournament has no end date",
+                game_name(*game_index, *game_id)
+            ),
+            Self::InvalidAttribute { name, value } => {
+                write!(f, "invalid value `{value}` of attribute `{name}`")
+            }
+            Self::InvalidMove {
+                game_index,
+                game_id,
+                token,
+            } => write!(
+                f,
+                "{}: invalid move `{token}`",
+                game_name(*game_index, *game_id)
+            ),
+            Self::DuplicateMove {
+                game_index,
+                game_id,
+                point,
+            } => write!(
+                f,
+                "{}: {point:?} is played twice",
+                game_name(*game_index, *game_id)
+            ),
+            Self::CompiledVersion(version) => write!(
+                f,
+                "compiled database has version {version}, but only version {COMPILED_VERSION} is supported: compile it again"
+            ),
+            Self::Compiled(e) => write!(f, "could not read compiled database: {e}"),
+            Self::Exported { line, error } => write!(f, "line {line}: invalid game: {error}"),
+            Self::InvalidRatings(s) => write!(f, "invalid ratings: {s}"),
+        }
+    }
+}
+impl std::error::Error for DbError {}
+
+/// Names a game in errors, by its index and its id if it has one.
+fn game_name(game_index: usize, game_id: Option<usize>) -> String {
+    match game_id {
+        Some(id) => format!("game {game_index} (id {id})"),
+        None => format!("game {game_index}"),
+    }
+}
+
+/// Parses the moves of a game from `tokens`, whatever format it was read from: `parse` reads
+/// the point of a token, which must be on a board of this width and height, and moves are
+/// checked with [`validate_moves`].
+fn parse_moves<'a>(
+    (game_index, game_id): (usize, Option<usize>),
+    tokens: impl Iterator<Item = &'a str>,
+    (width, height): (u8, u8),
+    parse: impl Fn(&str) -> Option<(u8, u8)>,
+) -> Result<Vec<(u8, u8)>, DbError> {
+    let moves = tokens
+        .map(|token| {
+            parse(token)
+                .filter(|(x, y)| *x < width && *y < height)
+                .ok_or_else(|| DbError::InvalidMove {
+                    game_index,
+                    game_id,
+                    token: token.trim().to_string(),
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    validate_moves((game_index, game_id), &moves)?;
+    Ok(moves)
+}
+
+/// Checks the moves of a game, whatever format it was read from.
+fn validate_moves(
+    (game_index, game_id): (usize, Option<usize>),
+    moves: &[(u8, u8)],
+) -> Result<(), DbError> {
+    let mut played = std::collections::HashSet::with_capacity(moves.len());
+    for point in moves {
+        if !played.insert(point) {
+            return Err(DbError::DuplicateMove {
+                game_index,
+                game_id,
+                point: *point,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// How drawn games are handled by filters depending on the result of games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawPolicy {
+    /// Both players of a drawn game are considered to be on the selected side.
+    #[default]
+    IncludeBoth,
+    /// Drawn games are excluded by result-aware filters.
+    Exclude,
+    /// Drawn games are counted as a win for this color.
+    CountAs(Color),
+}
+impl std::str::FromStr for DrawPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "include-both" => Ok(Self::IncludeBoth),
+            "exclude" => Ok(Self::Exclude),
+            "count-as-black" => Ok(Self::CountAs(Color::Black)),
+            "count-as-white" => Ok(Self::CountAs(Color::White)),
+            _ => Err(format!(
+                "unknown draw policy `{s}`, expected include-both, exclude, count-as-black or count-as-white"
+            )),
+        }
+    }
+}
+
+/// Selects players of a game depending on its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SideFilter {
+    #[default]
+    All,
+    Winner,
+    Loser,
+}
+impl SideFilter {
+    /// Whether the player with the given color is selected, handling draws with `draws`.
+    ///
+    /// This is the single place where the result of a game is interpreted.
+    pub fn includes(self, game: &Game, color: Color, draws: DrawPolicy) -> bool {
+        let winner = match (game.winner, draws) {
+            (Some(winner), _) | (None, DrawPolicy::CountAs(winner)) => winner,
+            (None, DrawPolicy::IncludeBoth) => return true,
+            (None, DrawPolicy::Exclude) => return self == Self::All,
+        };
+        match self {
+            Self::All => true,
+            Self::Winner => color == winner,
+            Self::Loser => color != winner,
+        }
+    }
+}
+impl std::str::FromStr for SideFilter {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(Self::All),
+            "winner" => Ok(Self::Winner),
+            "loser" => Ok(Self::Loser),
+            _ => Err(format!("unknown side `{s}`, expected all, winner or loser")),
+        }
+    }
+}
+
+/// Selects players of a game by the color of their stones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorFilter {
+    #[default]
+    Both,
+    Black,
+    White,
+}
+impl ColorFilter {
+    /// Whether the player with the given color is selected.
+    pub fn includes(self, color: Color) -> bool {
+        match self {
+            Self::Both => true,
+            Self::Black => color == Color::Black,
+            Self::White => color == Color::White,
+        }
+    }
+}
+impl std::str::FromStr for ColorFilter {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "both" => Ok(Self::Both),
+            "black" => Ok(Self::Black),
+            "white" => Ok(Self::White),
+            _ => Err(format!(
+                "unknown color `{s}`, expected both, black or white"
+            )),
+        }
+    }
+}
+
+/// How the games used are picked when only some of them are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sampling {
+    /// The first games of the database.
+    #[default]
+    Head,
+    /// A uniformly random subset of the games, kept in database order.
+    Random,
+    /// Random games, as many in each rating bracket (by the average rating of their players)
+    /// as possible.
+    Stratified,
+}
+impl std::str::FromStr for Sampling {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "head" => Ok(Self::Head),
+            "random" => Ok(Self::Random),
+            "stratified" => Ok(Self::Stratified),
+            _ => Err(format!(
+                "unknown sampling `{s}`, expected head, random or stratified"
+            )),
+        }
+    }
+}
+
+/// Magic bytes starting a compiled database.
+const COMPILED_MAGIC: &[u8; 4] = b"RMMG";
+/// Version of the compiled database format, to increment whenever [`Game`] changes.
+const COMPILED_VERSION: u32 = 2;
+
+/// Writes games, with their ratings, to a compact binary file that [`load_database`] loads
+/// without parsing XML or computing ratings again.
+pub fn compile_database<P: AsRef<Path>>(games: &[Game], path: P) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(COMPILED_MAGIC)?;
+    writer.write_all(&COMPILED_VERSION.to_le_bytes())?;
+    bincode::serialize_into(&mut writer, games)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    writer.flush()
+}
+
+/// Whether the file was written by [`compile_database`].
+pub fn is_compiled_database<P: AsRef<Path>>(path: P) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == COMPILED_MAGIC)
+}
+
+/// Loads games from a file written by [`compile_database`], or returns `None` if the file is
+/// not a compiled database.
+fn load_compiled_database(path: &Path) -> Option<Result<Vec<Game>, DbError>> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..4] != COMPILED_MAGIC {
+        return None;
+    }
+    let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if version != COMPILED_VERSION {
+        return Some(Err(DbError::CompiledVersion(version)));
+    }
+    Some(bincode::deserialize_from(reader).map_err(DbError::Compiled))
+}
+
+/// Writes games, with their ratings, to a JSON lines file holding a [`Game`] object per line,
+/// that [`load_database`] loads without parsing XML or computing ratings again.
+///
+/// Unlike a compiled database, it can be read and written by other tools, and does not depend
+/// on the version of this tool as long as the fields of games are not changed.
+pub fn export_database<P: AsRef<Path>>(games: &[Game], path: P) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for game in games {
+        serde_json::to_writer(&mut writer, game)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+/// Whether the file is an exported database, by its `.jsonl` extension.
+pub fn is_exported_database<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("jsonl"))
+}
+
+/// Loads games from a file written by [`export_database`]. Blank lines are skipped.
+pub fn load_exported_database<P: AsRef<Path>>(path: P) -> Result<Vec<Game>, DbError> {
+    let reader = BufReader::new(File::open(path).map_err(DbError::Io)?);
+    let mut games = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(DbError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let game: Game = serde_json::from_str(&line)
+            .map_err(|error| DbError::Exported { line: i + 1, error })?;
+        validate_moves((games.len(), None), &game.moves)?;
+        games.push(game);
+    }
+    Ok(games)
+}
+
+/// Ratings given to the players of Piskvork games, which are not rated.
+#[derive(Debug, Clone)]
+pub struct PsqConfig {
+    /// Rating of players whose games are not in `ratings`.
+    pub default_elo: u64,
+    /// CSV file of `file,black_elo,white_elo` rows, giving the ratings of the players of each
+    /// game by file name.
+    pub ratings: Option<PathBuf>,
+}
+impl Default for PsqConfig {
+    fn default() -> Self {
+        Self {
+            default_elo: 1900,
+            ratings: None,
+        }
+    }
+}
+
+/// Whether the path is a Piskvork game.
+fn is_psq_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("psq"))
+}
+
+/// Piskvork games of a directory, sorted by file name.
+fn psq_files(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|f| is_psq_file(f));
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the path is a Piskvork game, or a directory holding some.
+pub fn is_psq_database<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if path.is_dir() {
+        psq_files(path).is_ok_and(|files| !files.is_empty())
+    } else {
+        is_psq_file(path)
+    }
+}
+
+/// Width and height of the board of a Piskvork game, from a header like
+/// `Piskvork 20x20, 11:11, 0`.
+fn psq_board_size(header: &str) -> Option<(u8, u8)> {
+    let size = header.split_whitespace().nth(1)?.trim_end_matches(',');
+    let (width, height) = size.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Parses the moves of a Piskvork game: a header line, then one `x,y,time` line per move with
+/// 1-based coordinates, followed by lines that are not moves. Moves are checked to be on the
+/// board when the header gives its size.
+fn parse_psq(game_index: usize, content: &str) -> Result<Vec<(u8, u8)>, DbError> {
+    let size = content
+        .lines()
+        .next()
+        .and_then(psq_board_size)
+        .unwrap_or((u8::MAX, u8::MAX));
+    let is_move = |line: &&str| {
+        let fields = line.trim().split(',').collect::<Vec<_>>();
+        fields.len() == 3 && fields.iter().all(|f| f.trim().parse::<i64>().is_ok())
+    };
+    let point = |line: &str| {
+        let mut fields = line.trim().split(',');
+        let mut coordinate = || fields.next()?.trim().parse::<u8>().ok()?.checked_sub(1);
+        Some((coordinate()?, coordinate()?))
+    };
+    parse_moves(
+        (game_index, None),
+        content.lines().skip(1).take_while(is_move),
+        size,
+        point,
+    )
+}
+
+/// Loads a Piskvork game, or a directory of them sorted by file name.
+///
+/// Piskvork games have neither ratings nor results: players are rated from `config`, and games
+/// are considered drawn.
+pub fn load_psq_database<P: AsRef<Path>>(
+    path: P,
+    config: &PsqConfig,
+) -> Result<Vec<Game>, DbError> {
+    let path = path.as_ref();
+    let files = if path.is_dir() {
+        psq_files(path).map_err(DbError::Io)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut ratings = HashMap::new();
+    if let Some(ratings_path) = &config.ratings {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(ratings_path)
+            .map_err(|e| DbError::InvalidRatings(e.to_string()))?;
+        for row in reader.deserialize::<(String, u64, u64)>() {
+            let (file, black, white) = row.map_err(|e| DbError::InvalidRatings(e.to_string()))?;
+            ratings.insert(file, (black, white));
+        }
+    }
+
+    files
+        .iter()
+        .enumerate()
+        .map(|(game_index, file)| {
+            let content = std::fs::read_to_string(file).map_err(DbError::Io)?;
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            let (black_elo, white_elo) = ratings
+                .get(name.as_ref())
+                .copied()
+                .unwrap_or((config.default_elo, config.default_elo));
+            Ok(Game {
+                black_elo,
+                white_elo,
+                moves: parse_psq(game_index, &content)?,
+                winner: None,
+                black_id: 0,
+                white_id: 0,
+                black_name: None,
+                white_name: None,
+            })
+        })
+        .collect()
+}
+
+/// Hyperparameters of the Whole-History Rating computed for players of a database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WhrConfig {
+    /// Variance of the rating change of a player over a day, in squared Elo. Higher values let
+    /// ratings follow the progress of players more closely, but make them noisier.
+    pub w2: f64,
+    /// Number of draws against a virtual player introduced at each player's first game.
+    ///
+    /// These draws pull ratings toward the prior, stabilizing ratings of players with few
+    /// games but also pulling extreme players inward. With 0, ratings are raw WHR, which
+    /// widens the rating distribution: players who only won or only lost are then only held
+    /// back by their opponents' ratings, and their rating never converges. The computation is
+    /// then bounded by `max_iterations`.
+    pub virtual_games: u32,
+    /// Maximum number of iterations of the computation. When `None`, it iterates until ratings
+    /// converge, or [`UNSTABILIZED_MAX_ITERATIONS`] times without virtual games.
+    pub max_iterations: Option<u32>,
+    /// Added to every rating. WHR ratings are centered on 0, this only shifts them (and the
+    /// brackets they fall in) uniformly.
+    pub elo_offset: f64,
+}
+impl Default for WhrConfig {
+    fn default() -> Self {
+        Self {
+            w2: 19.3,
+            virtual_games: 2,
+            max_iterations: None,
+            elo_offset: 1900.,
+        }
+    }
+}
+
+/// Selects the games of a database to use.
+///
+/// Games can only be selected by rule, rating state, date and tournament when parsing an XML
+/// database:
+/// a compiled or exported database only holds the games selected when writing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbFilter {
+    /// Rules of the games to keep, as numbered by the database (1 for Renju).
+    pub rules: Vec<u8>,
+    /// Also keeps games that were not rated. They are then used to compute ratings as well.
+    pub include_unrated: bool,
+    /// Only keeps games where both players are rated in this range.
+    pub elo_range: Option<RangeInclusive<u64>>,
+    /// Only keeps games of tournaments that ended in this range.
+    pub date_range: Option<RangeInclusive<NaiveDate>>,
+    /// Only keeps games of these tournaments, by id in the database.
+    pub tournaments: Option<Vec<usize>>,
+}
+impl Default for DbFilter {
+    fn default() -> Self {
+        Self {
+            rules: vec![1],
+            include_unrated: false,
+            elo_range: None,
+            date_range: None,
+            tournaments: None,
+        }
+    }
+}
+
+/// Maximum number of iterations of the rating computation without virtual games, where the
+/// ratings of some players never converge.
+pub const UNSTABILIZED_MAX_ITERATIONS: u32 = 50;
+
+/// Strips the UTF-8 byte order mark and whitespace some tools add at the start of files.
+fn trim_start(content: &[u8]) -> &[u8] {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    let start = content
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(content.len());
+    &content[start..]
+}
+
+/// Value of an XML attribute, ignoring surrounding whitespace.
+fn attribute_value<'a>(attribute: &'a Attribute) -> Result<&'a str, DbError> {
+    std::str::from_utf8(&attribute.value)
+        .map(str::trim)
+        .map_err(|_| invalid_attribute(attribute))
 }
 
-/// Parses a database of games.
-pub fn load_database<P: AsRef<Path>>(data_path: P) -> Result<Vec<Game>, ()> {
-    let mut reader = Reader::from_file(data_path).unwrap();
+fn invalid_attribute(attribute: &Attribute) -> DbError {
+    DbError::InvalidAttribute {
+        name: String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+        value: String::from_utf8_lossy(&attribute.value).into_owned(),
+    }
+}
+
+/// Parses the value of an XML attribute.
+fn parse_attribute<T: std::str::FromStr>(attribute: &Attribute) -> Result<T, DbError> {
+    attribute_value(attribute)?
+        .parse()
+        .map_err(|_| invalid_attribute(attribute))
+}
+
+/// Origin of the time of games when computing ratings, before the first recorded tournaments.
+const RATING_EPOCH: NaiveDate = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap();
+
+/// Number of days since [`RATING_EPOCH`], negative for earlier dates.
+fn days_since_epoch(date: NaiveDate) -> i64 {
+    date.signed_duration_since(RATING_EPOCH).num_days()
+}
+
+/// Parses a `YYYY-MM-DD` date to a number of days.
+fn parse_date(tournament: usize, date: &str) -> Result<usize, DbError> {
+    let invalid_date = || DbError::InvalidDate {
+        tournament,
+        date: date.to_string(),
+    };
+    let mut parts = date.split('-').map(|p| p.parse().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid_date());
+    };
+    let date = NaiveDate::from_ymd_opt(year, month as u32, day as u32).ok_or_else(invalid_date)?;
+    usize::try_from(days_since_epoch(date)).map_err(|_| invalid_date())
+}
+
+/// Size of the board of the games of XML databases.
+pub const RIF_BOARD_SIZE: u8 = 15;
+
+/// Parses a move in the `h8` notation of the database.
+fn parse_move(token: &str) -> Option<(u8, u8)> {
+    let mut chars = token.chars();
+    let x = chars.next().filter(char::is_ascii_lowercase)? as u8 - b'a';
+    let y = chars.as_str().parse::<u8>().ok()?.checked_sub(1)?;
+    Some((x, y))
+}
+
+/// Number of games dropped by a [`DbFilter`], by reason.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DroppedGames {
+    rule: usize,
+    unrated: usize,
+    elo: usize,
+    date: usize,
+    tournament: usize,
+}
+impl DroppedGames {
+    /// Logs how many games were kept, and how many were dropped by reason if any.
+    fn log(&self, kept: usize) {
+        let total = self.rule + self.unrated + self.elo + self.date + self.tournament;
+        if total > 0 {
+            log::info!(
+                "Kept {kept} games, dropped {total}: {} of other rules, {} unrated, {} outside of the rating range, {} outside of the date range and {} of other tournaments",
+                self.rule,
+                self.unrated,
+                self.elo,
+                self.date,
+                self.tournament
+            )
+        } else {
+            log::info!("Kept {kept} games")
+        }
+    }
+}
+
+/// Parses a database of games, either in XML, compiled with [`compile_database`] or exported with
+/// [`export_database`], keeping the games selected by `filter`. The number of games dropped is
+/// logged.
+///
+/// Ratings of compiled and exported databases were computed when writing them, so `whr_config`
+/// is only used for XML databases.
+pub fn load_database<P: AsRef<Path>>(
+    data_path: P,
+    whr_config: &WhrConfig,
+    filter: &DbFilter,
+) -> Result<Vec<Game>, DbError> {
+    load_database_with_cache(data_path, whr_config, filter, None)
+}
+
+/// Magic number starting rating cache files.
+const RATING_CACHE_MAGIC: &[u8; 4] = b"RMMR";
+
+/// Version of the content of rating cache files, changed along with [`DroppedGames`].
+const RATING_CACHE_VERSION: u32 = 2;
+
+/// Like [`load_database`], keeping the games of XML databases and their ratings in the
+/// `rating_cache` file, so that they are neither parsed nor rated again by later runs.
+///
+/// The cache is keyed by a checksum of the database and of the settings its games were selected
+/// and rated with: it is computed again, and overwritten, when any of them changes.
+pub fn load_database_with_cache<P: AsRef<Path>>(
+    data_path: P,
+    whr_config: &WhrConfig,
+    filter: &DbFilter,
+    rating_cache: Option<&Path>,
+) -> Result<Vec<Game>, DbError> {
+    let data_path = data_path.as_ref();
+    let compiled = if is_exported_database(data_path) {
+        Some(load_exported_database(data_path))
+    } else {
+        load_compiled_database(data_path)
+    };
+    let (mut dropped, mut games) = match compiled {
+        Some(games) => (DroppedGames::default(), games?),
+        None => {
+            let content = std::fs::read(data_path).map_err(DbError::Io)?;
+            let key = rating_cache_key(&content, whr_config, filter);
+            match rating_cache.and_then(|path| load_rating_cache(path, key)) {
+                Some(cached) => cached,
+                None => {
+                    let mut dropped = DroppedGames::default();
+                    let games = load_xml_database(&content, whr_config, filter, &mut dropped)?;
+                    if let Some(path) = rating_cache {
+                        match save_rating_cache(path, key, &dropped, &games) {
+                            Ok(()) => log::info!("Saved ratings to {}", path.display()),
+                            Err(e) => {
+                                log::warn!("Could not save ratings to {}: {e}", path.display())
+                            }
+                        }
+                    }
+                    (dropped, games)
+                }
+            }
+        }
+    };
+    if let Some(range) = &filter.elo_range {
+        let count = games.len();
+        games.retain(|g| range.contains(&g.black_elo) && range.contains(&g.white_elo));
+        dropped.elo = count - games.len();
+    }
+    dropped.log(games.len());
+    Ok(games)
+}
+
+/// Key of the rating cache of an XML database: a checksum of its content, of the settings its
+/// games are selected and rated with, and of the versions of [`Game`] and of the cache.
+///
+/// The rating range of `filter` is left out, since it is applied to cached games.
+fn rating_cache_key(content: &[u8], whr_config: &WhrConfig, filter: &DbFilter) -> u32 {
+    let filter = DbFilter {
+        elo_range: None,
+        ..filter.clone()
+    };
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(content);
+    hasher.update(
+        format!("{COMPILED_VERSION} {RATING_CACHE_VERSION} {whr_config:?} {filter:?}").as_bytes(),
+    );
+    hasher.finalize()
+}
+
+/// Loads games saved by [`save_rating_cache`] with this key, along with the games dropped when
+/// loading them. Returns `None`, logging why, if the cache is missing, corrupted or stale.
+fn load_rating_cache(path: &Path, key: u32) -> Option<(DroppedGames, Vec<Game>)> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut header = [0; 8];
+    if reader.read_exact(&mut header).is_err() || &header[..4] != RATING_CACHE_MAGIC {
+        log::warn!("{} is not a rating cache, overwriting it", path.display());
+        return None;
+    }
+    if u32::from_le_bytes(header[4..].try_into().unwrap()) != key {
+        log::info!(
+            "The database or its settings changed since ratings were saved to {}, computing them again",
+            path.display()
+        );
+        return None;
+    }
+    match bincode::deserialize_from(reader) {
+        Ok(cached) => {
+            log::info!("Loaded ratings from {}", path.display());
+            Some(cached)
+        }
+        Err(e) => {
+            log::warn!("Could not read ratings from {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Writes games and the games dropped when loading them to a rating cache file.
+fn save_rating_cache(
+    path: &Path,
+    key: u32,
+    dropped: &DroppedGames,
+    games: &[Game],
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(RATING_CACHE_MAGIC)?;
+    writer.write_all(&key.to_le_bytes())?;
+    bincode::serialize_into(&mut writer, &(dropped, games))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    writer.flush()
+}
+
+/// Parses an XML database, dropping games by rule, rating state, date and tournament.
+fn load_xml_database(
+    content: &[u8],
+    whr_config: &WhrConfig,
+    filter: &DbFilter,
+    dropped: &mut DroppedGames,
+) -> Result<Vec<Game>, DbError> {
+    let mut reader = Reader::from_reader(trim_start(content));
+    reader.config_mut().trim_text(true);
     let mut buffer = vec![];
 
     let mut games = vec![];
     let mut tournament_timesteps = vec![];
+    let mut player_names = HashMap::new();
 
     let mut current_game_is_init = false;
-    let mut black = 0;
-    let mut white = 0;
+    // Index of the current game among the games of the database, and of the next one
+    let (mut game_index, mut next_game_index) = (0, 0);
+    let mut game_id = 0;
+    let mut black: u64 = 0;
+    let mut white: u64 = 0;
     let mut result = 0.5;
     let mut timestep = 0;
+    let mut tournament = 0;
     let mut moves = vec![];
-    'read: loop {
-        match reader.read_event_into(&mut buffer).unwrap() {
+    loop {
+        match reader.read_event_into(&mut buffer).map_err(DbError::Xml)? {
             Event::Eof => break,
-            Event::Empty(e) => {
-                if e.name().as_ref() == b"tournament" {
-                    let mut timestep = 0;
-                    let mut index: usize = 0;
-                    for attr in e.attributes().filter_map(|a| a.ok()) {
-                        match attr.key.as_ref() {
-                            b"id" => {
-                                index = std::str::from_utf8(attr.value.as_ref())
-                                    .unwrap()
-                                    .parse()
-                                    .unwrap()
-                            }
-                            b"start" => {}
-                            b"end" => {
-                                let date = std::str::from_utf8(&attr.value).unwrap();
-                                let mut parts = date.split('-');
-                                let year = parts.next().unwrap().parse().unwrap();
-                                let month = parts.next().unwrap().parse().unwrap();
-                                let day = parts.next().unwrap().parse().unwrap();
-                                timestep = NaiveDate::from_ymd_opt(year, month, day)
-                                    .map(|d| {
-                                        d.signed_duration_since(NaiveDate::default()).num_days()
-                                            as usize
-                                    })
-                                    .unwrap();
+            Event::Empty(e) if e.name().as_ref() == b"tournament" => {
+                let mut timestep = 0;
+                let mut index: usize = 0;
+                let mut end = None;
+                for attr in e.attributes().filter_map(|a| a.ok()) {
+                    match attr.key.as_ref() {
+                        b"id" => {
+                            index = parse_attribute(&attr)?;
+                            if index == 0 {
+                                return Err(invalid_attribute(&attr));
                             }
-                            _ => {}
                         }
+                        b"start" => {}
+                        b"end" => end = Some(attribute_value(&attr)?.to_string()),
+                        _ => {}
                     }
+                }
+                if let Some(end) = end {
+                    timestep = parse_date(index, &end)?;
+                }
+                if index > tournament_timesteps.len() {
                     tournament_timesteps.resize(index, 0);
-                    tournament_timesteps[index - 1] = timestep
+                }
+                tournament_timesteps[index - 1] = timestep
+            }
+            Event::Empty(e) if e.name().as_ref() == b"player" => {
+                let (mut id, mut name, mut surname) = (None, "", "");
+                let attributes = e.attributes().filter_map(|a| a.ok()).collect::<Vec<_>>();
+                for attr in &attributes {
+                    match attr.key.as_ref() {
+                        b"id" => id = Some(parse_attribute::<u64>(attr)?),
+                        b"name" => name = attribute_value(attr)?,
+                        b"surname" => surname = attribute_value(attr)?,
+                        _ => {}
+                    }
+                }
+                let full_name = format!("{name} {surname}").trim().to_string();
+                if let Some(id) = id.filter(|_| !full_name.is_empty()) {
+                    player_names.insert(id, full_name);
                 }
             }
             Event::End(e) => match e.name().as_ref() {
-                b"game" if current_game_is_init => games.push((
-                    black,
-                    white,
-                    if result == 1.0 {
-                        Some(black)
-                    } else if result == 0.5 {
-                        None
-                    } else if result == 0.0 {
-                        Some(white)
-                    } else {
-                        panic!()
-                    },
-                    timestep,
-                    moves.clone(),
-                )),
+                b"game" if current_game_is_init => {
+                    if timestep == 0 {
+                        return Err(DbError::MissingDate {
+                            game_index,
+                            game_id: Some(game_id),
+                        });
+                    }
+                    games.push((
+                        black,
+                        white,
+                        if result == 1.0 {
+                            Some(black)
+                        } else if result == 0.5 {
+                            None
+                        } else {
+                            Some(white)
+                        },
+                        timestep,
+                        tournament,
+                        moves.clone(),
+                    ))
+                }
                 _ => {}
             },
             Event::Start(e) => match e.name().as_ref() {
                 b"game" => {
+                    game_index = next_game_index;
+                    next_game_index += 1;
                     current_game_is_init = true;
+                    let mut rated = true;
+                    let mut rule = 1;
                     for attr in e.attributes().filter_map(|a| a.ok()) {
                         match attr.key.as_ref() {
                             b"tournament" => {
-                                let tournament = std::str::from_utf8(&attr.value)
-                                    .unwrap()
-                                    .parse::<usize>()
-                                    .unwrap();
-                                timestep = tournament_timesteps[tournament - 1];
-                            }
-                            b"rated" => {
-                                if std::str::from_utf8(&attr.value)
-                                    .unwrap()
-                                    .parse::<u8>()
-                                    .unwrap()
-                                    != 1
-                                {
-                                    current_game_is_init = false;
-                                    continue 'read;
-                                }
-                            }
-                            b"rule" => {
-                                if std::str::from_utf8(&attr.value)
-                                    .unwrap()
-                                    .parse::<u8>()
-                                    .unwrap()
-                                    != 1
-                                {
-                                    current_game_is_init = false;
-                                    continue 'read;
-                                }
-                            }
-                            b"black" => {
-                                black = std::str::from_utf8(&attr.value).unwrap().parse().unwrap()
-                            }
-                            b"white" => {
-                                white = std::str::from_utf8(&attr.value).unwrap().parse().unwrap()
+                                tournament = parse_attribute::<usize>(&attr)?;
+                                timestep = tournament
+                                    .checked_sub(1)
+                                    .and_then(|i| tournament_timesteps.get(i))
+                                    .copied()
+                                    .ok_or_else(|| invalid_attribute(&attr))?;
                             }
+                            b"rated" => rated = parse_attribute::<u8>(&attr)? == 1,
+                            b"rule" => rule = parse_attribute(&attr)?,
+                            b"id" => game_id = parse_attribute(&attr)?,
+                            b"black" => black = parse_attribute(&attr)?,
+                            b"white" => white = parse_attribute(&attr)?,
                             b"bresult" => {
-                                result = std::str::from_utf8(&attr.value)
-                                    .unwrap()
-                                    .parse::<f32>()
-                                    .unwrap();
+                                result = parse_attribute::<f32>(&attr)?;
+                                if ![0.0, 0.5, 1.0].contains(&result) {
+                                    return Err(invalid_attribute(&attr));
+                                }
                             }
                             _ => {}
                         }
                     }
+                    if !filter.rules.contains(&rule) {
+                        dropped.rule += 1;
+                        current_game_is_init = false;
+                    } else if !rated && !filter.include_unrated {
+                        dropped.unrated += 1;
+                        current_game_is_init = false;
+                    }
                 }
-                b"move" => match reader.read_event_into(&mut buffer).unwrap() {
-                    Event::Text(t) => {
-                        moves.clear();
-                        let str = t.unescape().unwrap();
-                        for m in str.split_whitespace() {
-                            let m = m.trim();
-                            if m.len() < 2 || m.len() > 3 {
-                                return Err(());
-                            };
-                            let x = m.chars().next().unwrap() as u8 - 'a' as u8;
-                            let y = &m[1..].parse::<u8>().unwrap() - 1;
-
-                            moves.push((x, y))
-                        }
+                b"move" if current_game_is_init => {
+                    if let Event::Text(t) =
+                        reader.read_event_into(&mut buffer).map_err(DbError::Xml)?
+                    {
+                        let str = t.unescape().map_err(DbError::Xml)?;
+                        moves = parse_moves(
+                            (game_index, Some(game_id)),
+                            str.split_whitespace(),
+                            (RIF_BOARD_SIZE, RIF_BOARD_SIZE),
+                            parse_move,
+                        )?;
                     }
-                    _ => {}
-                },
+                }
                 _ => {}
             },
             _ => (),
         }
     }
-    let whr = WhrBuilder::default()
-        .with_games(games.iter().map(|(b, w, r, t, _)| {
-            assert_ne!(*t, 0);
-            (*b, *w, *r, *t, None)
-        }))
-        .with_w2(19.3)
-        .with_virtual_games(2)
-        .build();
+    let mut whr = WhrBuilder::default()
+        .with_games(games.iter().map(|(b, w, r, t, ..)| (*b, *w, *r, *t, None)))
+        .with_w2(whr_config.w2)
+        .with_virtual_games(whr_config.virtual_games);
+    let max_iterations = whr_config
+        .max_iterations
+        .or((whr_config.virtual_games == 0).then_some(UNSTABILIZED_MAX_ITERATIONS));
+    if let Some(iterations) = max_iterations {
+        whr = whr.with_iterations(iterations)
+    }
+    let whr = whr.build();
+
+    if let Some(range) = &filter.date_range {
+        // Bounds before the epoch are kept as is, so that a range ending before every game
+        // selects none of them
+        let dates = days_since_epoch(*range.start())..=days_since_epoch(*range.end());
+        let count = games.len();
+        games.retain(|(.., time, _, _)| dates.contains(&(*time as i64)));
+        dropped.date = count - games.len();
+    }
+    if let Some(tournaments) = &filter.tournaments {
+        let count = games.len();
+        games.retain(|(.., tournament, _)| tournaments.contains(tournament));
+        dropped.tournament = count - games.len();
+    }
 
     Ok(games
         .into_iter()
-        .map(|(black, white, _, time, moves)| Game {
-            black_elo: (whr.rating(&black, time).unwrap().elo().round() + 1900f64) as u64,
-            white_elo: (whr.rating(&white, time).unwrap().elo().round() + 1900f64) as u64,
+        .map(|(black, white, winner, time, _, moves)| Game {
+            black_elo: (whr.rating(&black, time).unwrap().elo().round() + whr_config.elo_offset)
+                as u64,
+            white_elo: (whr.rating(&white, time).unwrap().elo().round() + whr_config.elo_offset)
+                as u64,
             moves,
+            winner: winner.map(|w| {
+                if w == black {
+                    Color::Black
+                } else {
+                    Color::White
+                }
+            }),
+            black_id: black,
+            white_id: white,
+            black_name: player_names.get(&black).cloned(),
+            white_name: player_names.get(&white).cloned(),
         })
         .collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{game, moves, temp_path, xml_database, XML_DATABASE};
+
+    /// Checks that games loaded twice from the same database are the same.
+    ///
+    /// The rating computation iterates over players in an order that varies between runs, so
+    /// ratings may differ by rounding.
+    fn assert_same_games(games: &[Game], expected: &[Game]) {
+        assert_eq!(games.len(), expected.len());
+        for (game, expected) in games.iter().zip(expected) {
+            assert_eq!(game.moves, expected.moves);
+            assert_eq!(game.winner, expected.winner);
+            assert_eq!(
+                (game.black_id, game.white_id),
+                (expected.black_id, expected.white_id)
+            );
+            assert_eq!(
+                (&game.black_name, &game.white_name),
+                (&expected.black_name, &expected.white_name)
+            );
+            assert!(game.black_elo.abs_diff(expected.black_elo) <= 1);
+            assert!(game.white_elo.abs_diff(expected.white_elo) <= 1);
+        }
+    }
+
+    /// Loads the fixture database with these settings, from a file of this name.
+    fn load(name: &str, whr_config: &WhrConfig, filter: &DbFilter) -> Vec<Game> {
+        load_database(xml_database(name), whr_config, filter).unwrap()
+    }
+
+    #[test]
+    fn drawn_games_follow_the_draw_policy() {
+        let draw = Game {
+            winner: None,
+            ..game(1800, 2000, moves(20))
+        };
+        let selected = |sides: SideFilter, draws| {
+            [Color::Black, Color::White].map(|color| sides.includes(&draw, color, draws))
+        };
+        for draws in [
+            DrawPolicy::IncludeBoth,
+            DrawPolicy::Exclude,
+            DrawPolicy::CountAs(Color::Black),
+        ] {
+            assert_eq!(selected(SideFilter::All, draws), [true, true]);
+        }
+        assert_eq!(
+            selected(SideFilter::Winner, DrawPolicy::IncludeBoth),
+            [true, true]
+        );
+        assert_eq!(
+            selected(SideFilter::Loser, DrawPolicy::IncludeBoth),
+            [true, true]
+        );
+        assert_eq!(
+            selected(SideFilter::Winner, DrawPolicy::Exclude),
+            [false, false]
+        );
+        assert_eq!(
+            selected(SideFilter::Loser, DrawPolicy::Exclude),
+            [false, false]
+        );
+        assert_eq!(
+            selected(SideFilter::Winner, DrawPolicy::CountAs(Color::White)),
+            [false, true]
+        );
+        assert_eq!(
+            selected(SideFilter::Loser, DrawPolicy::CountAs(Color::White)),
+            [true, false]
+        );
+    }
+
+    #[test]
+    fn compiled_database_round_trips() {
+        let games = load("compiled.xml", &WhrConfig::default(), &DbFilter::default());
+        assert_eq!(games.len(), 5);
+        let path = temp_path("compiled.bin");
+        compile_database(&games, &path).unwrap();
+        assert!(is_compiled_database(&path));
+        let compiled = load_database(&path, &WhrConfig::default(), &DbFilter::default()).unwrap();
+        assert_eq!(format!("{compiled:?}"), format!("{games:?}"));
+    }
+
+    #[test]
+    fn exported_database_round_trips() {
+        let games = load("exported.xml", &WhrConfig::default(), &DbFilter::default());
+        let path = temp_path("exported.jsonl");
+        export_database(&games, &path).unwrap();
+        assert!(is_exported_database(&path));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 5);
+        let exported = load_exported_database(&path).unwrap();
+        assert_eq!(format!("{exported:?}"), format!("{games:?}"));
+        // Matching reads the export with the loader of every other database.
+        let loaded = load_database(&path, &WhrConfig::default(), &DbFilter::default()).unwrap();
+        assert_eq!(format!("{loaded:?}"), format!("{games:?}"));
+    }
+
+    #[test]
+    fn zero_virtual_games_change_ratings() {
+        let elos = |games: Vec<Game>| {
+            games
+                .iter()
+                .flat_map(|g| [g.black_elo, g.white_elo])
+                .collect::<Vec<_>>()
+        };
+        let smoothed = elos(load(
+            "smoothed.xml",
+            &WhrConfig::default(),
+            &DbFilter::default(),
+        ));
+        let raw = elos(load(
+            "raw.xml",
+            &WhrConfig {
+                virtual_games: 0,
+                ..WhrConfig::default()
+            },
+            &DbFilter::default(),
+        ));
+        assert_eq!(raw.len(), smoothed.len());
+        assert_ne!(raw, smoothed);
+    }
+
+    #[test]
+    fn byte_order_mark_and_leading_whitespace_are_ignored() {
+        let clean = load("clean.xml", &WhrConfig::default(), &DbFilter::default());
+        let path = temp_path("bom.xml");
+        std::fs::write(&path, format!("\u{feff}\r\n  {XML_DATABASE}")).unwrap();
+        let games = load_database(&path, &WhrConfig::default(), &DbFilter::default()).unwrap();
+        assert_same_games(&games, &clean);
+    }
+
+    #[test]
+    fn piskvork_games_are_rated_and_checked() {
+        let directory = temp_path("psq");
+        std::fs::create_dir(&directory).unwrap();
+        assert!(!is_psq_database(&directory));
+        std::fs::write(directory.join("notes.txt"), "").unwrap();
+        assert!(!is_psq_database(&directory));
+
+        std::fs::write(
+            directory.join("a.psq"),
+            "Piskvork 15x15, 11:11, 0\n8,8,0\n9,9,0\n8,9,0\n-1\npbrain-yixin.exe\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("b.psq"), "Piskvork 15x15, 11:11, 0\n8,8,0\n").unwrap();
+        assert!(is_psq_database(&directory));
+        let games = load_psq_database(&directory, &PsqConfig::default()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].moves, vec![(7, 7), (8, 8), (7, 8)]);
+        assert_eq!((games[0].black_elo, games[0].white_elo), (1900, 1900));
+
+        for content in [
+            "Piskvork 15x15, 11:11, 0\n16,1,0\n",
+            "Piskvork 15x15, 11:11, 0\n1,1,0\n1,1,0\n",
+        ] {
+            std::fs::write(directory.join("b.psq"), content).unwrap();
+            assert!(load_psq_database(&directory, &PsqConfig::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn points_played_twice_are_refused() {
+        let path = temp_path("duplicate.xml");
+        std::fs::write(
+            &path,
+            XML_DATABASE.replace("h8 h9 h7 h6 i9 g7 f8 i8 f10 j7</move>", "h8 h9 h8</move>"),
+        )
+        .unwrap();
+        match load_database(&path, &WhrConfig::default(), &DbFilter::default()) {
+            Err(DbError::DuplicateMove {
+                game_index,
+                game_id,
+                point,
+            }) => {
+                assert_eq!((game_index, game_id, point), (2, Some(3), (7, 7)));
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn elo_offset_shifts_every_rating() {
+        let elos = |elo_offset| {
+            load(
+                &format!("offset_{elo_offset}.xml"),
+                &WhrConfig {
+                    elo_offset,
+                    ..WhrConfig::default()
+                },
+                &DbFilter::default(),
+            )
+            .iter()
+            .flat_map(|g| [g.black_elo as i64, g.white_elo as i64])
+            .collect::<Vec<_>>()
+        };
+        let (base, shifted) = (elos(1900.), elos(2400.));
+        assert_eq!(base.len(), shifted.len());
+        // Ratings are rounded, and computed again for each load
+        for (base, shifted) in base.iter().zip(&shifted) {
+            assert!((shifted - base - 500).abs() <= 1, "{base} {shifted}");
+        }
+    }
+
+    #[test]
+    fn cached_ratings_are_identical() {
+        let path = xml_database("cached.xml");
+        let cache = temp_path("cached.ratings");
+        let load = |path: &Path| {
+            load_database_with_cache(
+                path,
+                &WhrConfig::default(),
+                &DbFilter::default(),
+                Some(&cache),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|g| (g.black_elo, g.white_elo, g.moves))
+            .collect::<Vec<_>>()
+        };
+        let computed = load(&path);
+        let saved = std::fs::metadata(&cache).unwrap().modified().unwrap();
+        assert_eq!(load(&path), computed);
+        assert_eq!(
+            std::fs::metadata(&cache).unwrap().modified().unwrap(),
+            saved
+        );
+
+        // Ratings are computed again once the database changes
+        std::fs::write(&path, XML_DATABASE.replace(r#" rated="0""#, "")).unwrap();
+        assert_eq!(load(&path).len(), computed.len() + 1);
+        assert_ne!(
+            std::fs::metadata(&cache).unwrap().modified().unwrap(),
+            saved
+        );
+    }
+
+    #[test]
+    fn tournaments_before_1970_are_rated() {
+        let path = temp_path("before_1970.xml");
+        std::fs::write(
+            &path,
+            XML_DATABASE
+                .replace("2006-04-29", "1962-10-06")
+                .replace("2006-05-05", "1962-10-12"),
+        )
+        .unwrap();
+        let games = load_database(&path, &WhrConfig::default(), &DbFilter::default()).unwrap();
+        assert_eq!(games.len(), 5);
+
+        let select = |start: &str, end: &str| DbFilter {
+            date_range: Some(start.parse().unwrap()..=end.parse().unwrap()),
+            ..DbFilter::default()
+        };
+        let load = |filter| load_database(&path, &WhrConfig::default(), &filter);
+        assert_eq!(load(select("1962-01-01", "1962-12-31")).unwrap().len(), 3);
+        assert_eq!(load(select("1800-01-01", "1899-12-31")).unwrap().len(), 0);
+        assert_eq!(load(select("1800-01-01", "2100-01-01")).unwrap().len(), 5);
+
+        std::fs::write(&path, XML_DATABASE.replace("2006-05-05", "1899-12-31")).unwrap();
+        assert!(matches!(
+            load_database(&path, &WhrConfig::default(), &DbFilter::default()),
+            Err(DbError::InvalidDate { tournament: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn moves_outside_of_the_board_or_repeated_are_refused() {
+        let parse = |tokens: &str| {
+            parse_moves(
+                (0, Some(1)),
+                tokens.split_whitespace(),
+                (RIF_BOARD_SIZE, RIF_BOARD_SIZE),
+                parse_move,
+            )
+        };
+        assert_eq!(parse("a1 o15").unwrap(), vec![(0, 0), (14, 14)]);
+        for token in ["p8", "z16", "h16", "h0"] {
+            match parse(&format!("h8 {token}")) {
+                Err(DbError::InvalidMove { token: t, .. }) => assert_eq!(t, token),
+                other => panic!("{token}: {other:?}"),
+            }
+        }
+        assert!(matches!(
+            parse("h8 h9 h8"),
+            Err(DbError::DuplicateMove { point: (7, 7), .. })
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+//! # Event log
+//! Structured log of every matched position, written as one JSON object per line.
+//! Since it records the move played by the engine alongside the human move, a run can
+//! be re-scored offline without running the engine again.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::Color,
+    plot::{Brackets, ResultsFormat},
+};
+
+/// A position presented to the engine, and the moves played there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionRecord {
+    /// Index of the game in the database.
+    pub game: usize,
+    /// Index of the human move in the game.
+    pub ply: usize,
+    /// Color of the player to move.
+    pub color: Color,
+    /// Elo of the player to move.
+    pub elo: u64,
+    pub human_move: (u8, u8),
+    pub engine_move: (u8, u8),
+    /// Whether the engine played from its opening book, in which case the position is not
+    /// scored.
+    #[serde(default)]
+    pub book: bool,
+    /// Whether the position was counted as matched, which also accounts for the top-k moves
+    /// and symmetries of the board. Logs written before it was recorded do not have it.
+    #[serde(default)]
+    pub matched: Option<bool>,
+}
+impl PositionRecord {
+    pub fn is_match(&self) -> bool {
+        self.matched.unwrap_or(self.human_move == self.engine_move)
+    }
+}
+
+/// Append-only event log, shared by all workers.
+pub struct EventLog {
+    writer: Mutex<LineWriter<File>>,
+}
+impl EventLog {
+    /// Opens the log for appending.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    pub fn append(&self, record: &PositionRecord) -> std::io::Result<()> {
+        let line = serde_json::to_string(record)?;
+        writeln!(self.writer.lock().unwrap(), "{line}")
+    }
+}
+
+/// Reads all records of an event log.
+pub fn read_event_log<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<PositionRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?)
+    }
+    Ok(records)
+}
+
+/// Recomputes the `(elo, matches, total)` results of a run from its event log.
+pub fn rescore(records: &[PositionRecord]) -> Vec<(u64, u32, u32)> {
+    let mut matches = HashMap::<u64, (u32, u32)>::new();
+    for record in records.iter().filter(|r| !r.book) {
+        let (matched, total) = matches.entry(record.elo).or_default();
+        if record.is_match() {
+            *matched += 1
+        }
+        *total += 1
+    }
+    let mut matches = matches
+        .into_iter()
+        .map(|(elo, (matched, total))| (elo, matched, total))
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches
+}
+
+/// Property positions of an event log can be grouped by when analyzing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// Bracket of the rating of the player to move.
+    Rating,
+    /// Band of moves the position falls in, by its first move number.
+    Move,
+    /// Color of the player to move.
+    Color,
+}
+impl Aggregation {
+    /// Name of the column holding the groups of this aggregation.
+    pub fn column(self) -> &'static str {
+        match self {
+            Self::Rating => "elo",
+            Self::Move => "move",
+            Self::Color => "color",
+        }
+    }
+
+    /// Group of the record, ordered as they are displayed, or `None` if it falls outside of
+    /// every group.
+    fn group(
+        self,
+        record: &PositionRecord,
+        brackets: &Brackets,
+        move_bands: &[u32],
+    ) -> Option<u32> {
+        match self {
+            Self::Rating => brackets.index(record.elo).map(|i| brackets.start(i)),
+            Self::Move => move_bands
+                .iter()
+                .rev()
+                .find(|start| **start <= record.ply as u32 + 1)
+                .copied(),
+            Self::Color => Some(record.color as u32),
+        }
+    }
+
+    fn label(self, group: u32) -> serde_json::Value {
+        match (self, group) {
+            (Self::Color, 0) => "black".into(),
+            (Self::Color, _) => "white".into(),
+            _ => group.into(),
+        }
+    }
+}
+impl std::str::FromStr for Aggregation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rating" => Ok(Self::Rating),
+            "move" => Ok(Self::Move),
+            "color" => Ok(Self::Color),
+            _ => Err(format!(
+                "unknown aggregation `{s}`, expected rating, move or color"
+            )),
+        }
+    }
+}
+
+/// Matches and totals of the positions of an event log for each combination of groups of
+/// `aggregations`, as `(groups, matches, total)`, where `move_bands` are sorted. Without
+/// aggregations, every position falls in a single group. Book moves are not counted.
+pub fn analyze(
+    records: &[PositionRecord],
+    aggregations: &[Aggregation],
+    brackets: &Brackets,
+    move_bands: &[u32],
+) -> Vec<(Vec<u32>, u32, u32)> {
+    let mut groups = BTreeMap::<Vec<u32>, (u32, u32)>::new();
+    for record in records.iter().filter(|r| !r.book) {
+        let Some(key) = aggregations
+            .iter()
+            .map(|a| a.group(record, brackets, move_bands))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let (matched, total) = groups.entry(key).or_default();
+        if record.is_match() {
+            *matched += 1
+        }
+        *total += 1
+    }
+    groups
+        .into_iter()
+        .map(|(key, (matched, total))| (key, matched, total))
+        .collect()
+}
+
+/// Saves the results of [`analyze`], with a column for each aggregation followed by
+/// `matches`, `total` and `accuracy`.
+pub fn save_analysis<P: AsRef<Path>>(
+    path: P,
+    aggregations: &[Aggregation],
+    groups: &[(Vec<u32>, u32, u32)],
+    format: ResultsFormat,
+) -> std::io::Result<()> {
+    let accuracy = |matches: u32, total: u32| matches as f64 / total as f64;
+    match format {
+        ResultsFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            let mut header = aggregations.iter().map(|a| a.column()).collect::<Vec<_>>();
+            header.extend(["matches", "total", "accuracy"]);
+            writer.write_record(header)?;
+            for (key, matches, total) in groups {
+                let mut row = aggregations
+                    .iter()
+                    .zip(key)
+                    .map(|(a, group)| match a.label(*group) {
+                        serde_json::Value::String(label) => label,
+                        label => label.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                row.extend([
+                    matches.to_string(),
+                    total.to_string(),
+                    accuracy(*matches, *total).to_string(),
+                ]);
+                writer.write_record(row)?;
+            }
+            writer.flush()
+        }
+        ResultsFormat::Json => {
+            let rows = groups
+                .iter()
+                .map(|(key, matches, total)| {
+                    let mut row = aggregations
+                        .iter()
+                        .zip(key)
+                        .map(|(a, group)| (a.column().to_string(), a.label(*group)))
+                        .collect::<serde_json::Map<_, _>>();
+                    row.insert("matches".to_string(), (*matches).into());
+                    row.insert("total".to_string(), (*total).into());
+                    row.insert("accuracy".to_string(), accuracy(*matches, *total).into());
+                    row
+                })
+                .collect::<Vec<_>>();
+            let file = std::io::BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(file, &rows)?;
+            Ok(())
+        }
+    }
+}
@@ -1,55 +1,169 @@
 use std::{
     io::Result,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Style, Stylize},
     text::Span,
-    widgets::{block::Title, Axis, Block, Borders, Chart, Dataset, Gauge, Widget},
+    widgets::{block::Title, Axis, Block, Borders, Chart, Dataset, Gauge, Row, Table, Widget},
     DefaultTerminal,
 };
 
 use crate::{
-    move_matching::MoveMatching,
-    plot::{plot_results, save_results, Performance},
+    move_matching::{Metric, MoveMatching, WorkerState, WorkerStatus},
+    plot::ResultsFormat,
 };
 
+/// Time between two samples of the throughput.
+const THROUGHPUT_SAMPLE_PERIOD: Duration = Duration::from_secs(5);
+/// Weight of the last sample in the moving average of the throughput.
+const THROUGHPUT_SMOOTHING: f64 = 0.2;
+/// Time between two progress lines logged when running without the TUI.
+const PROGRESS_LOG_PERIOD: Duration = Duration::from_secs(30);
+/// Time between two checks of the state of a run without the TUI.
+const POLL_PERIOD: Duration = Duration::from_millis(100);
+
+/// When checkpoints are saved during a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointConfig {
+    /// Time between two checkpoints.
+    pub interval: Duration,
+    /// Also saves a checkpoint every time this many more positions are matched.
+    pub positions: Option<u64>,
+}
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(900),
+            positions: None,
+        }
+    }
+}
+
 pub struct Interface {
     experiment_name: String,
+    results_format: ResultsFormat,
     move_matching: Arc<MoveMatching>,
     exit_requested: bool,
+    /// Set when the run is interrupted by `SIGINT` or `Ctrl-C`.
+    interrupted: Arc<AtomicBool>,
+    checkpoints: CheckpointConfig,
+    selected_metric: usize,
+    /// Time and completed positions of the last throughput sample.
+    last_sample: (Instant, u64),
+    /// Exponential moving average of the positions matched per second, once sampled.
+    throughput: Option<f64>,
 }
 impl Interface {
-    pub fn new(experiment_name: String, move_matching: Arc<MoveMatching>) -> Self {
+    pub fn new(
+        experiment_name: String,
+        results_format: ResultsFormat,
+        move_matching: Arc<MoveMatching>,
+        interrupted: Arc<AtomicBool>,
+        checkpoints: CheckpointConfig,
+    ) -> Self {
         Self {
             experiment_name,
-            move_matching,
+            results_format,
             exit_requested: false,
+            interrupted,
+            checkpoints,
+            selected_metric: 0,
+            last_sample: (Instant::now(), move_matching.completed_positions()),
+            throughput: None,
+            move_matching,
         }
     }
 
     pub fn render_loop(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         let mut last_update = Instant::now();
-        let mut last_checkpoint = Instant::now();
-        while !self.exit_requested && !self.move_matching.is_completed() {
+        let mut last_checkpoint = (Instant::now(), self.move_matching.completed_positions());
+        while !self.exit_requested
+            && !self.move_matching.is_completed()
+            && !self.move_matching.is_stopped()
+        {
             if last_update.elapsed() > Duration::from_secs_f32(1. / 10.) {
                 terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
                 last_update = Instant::now()
             }
-            if last_checkpoint.elapsed() > Duration::from_secs(900) {
-                self.save_checkpoint();
-                last_checkpoint = Instant::now()
+            if self.last_sample.0.elapsed() > THROUGHPUT_SAMPLE_PERIOD {
+                self.sample_throughput()
             }
+            self.save_checkpoint_if_due(&mut last_checkpoint);
             self.handle_events()?;
+            if self.interrupted.load(Ordering::Relaxed) {
+                self.exit_requested = true
+            }
         }
+        self.move_matching.stop();
         Ok(())
     }
 
+    /// Runs without drawing anything, for runs without a terminal: progress is logged
+    /// periodically instead, and checkpoints are saved as with the TUI. Returns once every game
+    /// is matched, every worker stopped, or the run is interrupted.
+    ///
+    /// With the `progress-bar` feature, a progress bar is drawn on stderr instead of logging
+    /// progress when it is a terminal.
+    pub fn log_loop(mut self) {
+        #[cfg(feature = "progress-bar")]
+        let progress_bar = progress_bar(self.move_matching.total_positions());
+        #[cfg(feature = "progress-bar")]
+        let log_progress = progress_bar.is_hidden();
+        #[cfg(not(feature = "progress-bar"))]
+        let log_progress = true;
+        let mut last_log = Instant::now();
+        let mut last_checkpoint = (Instant::now(), self.move_matching.completed_positions());
+        while !self.move_matching.is_completed()
+            && !self.move_matching.is_stopped()
+            && !self.interrupted.load(Ordering::Relaxed)
+        {
+            std::thread::sleep(POLL_PERIOD);
+            if self.last_sample.0.elapsed() > THROUGHPUT_SAMPLE_PERIOD {
+                self.sample_throughput()
+            }
+            #[cfg(feature = "progress-bar")]
+            progress_bar.set_position(self.move_matching.processed_positions());
+            if log_progress && last_log.elapsed() > PROGRESS_LOG_PERIOD {
+                log::info!("{}", self.progress_label());
+                last_log = Instant::now()
+            }
+            self.save_checkpoint_if_due(&mut last_checkpoint);
+        }
+        #[cfg(feature = "progress-bar")]
+        if !log_progress {
+            progress_bar.println(self.progress_label());
+            progress_bar.finish_and_clear()
+        }
+        if log_progress {
+            log::info!("{}", self.progress_label());
+        }
+        self.move_matching.stop();
+    }
+
+    /// Saves a checkpoint when its interval elapsed or enough positions were matched since
+    /// `last_checkpoint`, the time and completed positions of the last one.
+    fn save_checkpoint_if_due(&mut self, last_checkpoint: &mut (Instant, u64)) {
+        let completed_positions = self.move_matching.completed_positions();
+        if last_checkpoint.0.elapsed() > self.checkpoints.interval
+            || self
+                .checkpoints
+                .positions
+                .is_some_and(|p| completed_positions - last_checkpoint.1 >= p)
+        {
+            self.save_checkpoint();
+            *last_checkpoint = (Instant::now(), completed_positions)
+        }
+    }
+
     fn handle_events(&mut self) -> Result<()> {
         let timeout = Duration::from_secs_f32(1. / 20.);
         if event::poll(timeout)? {
@@ -58,7 +172,24 @@ impl Interface {
                 if k.kind == KeyEventKind::Press {
                     match k.code {
                         KeyCode::Char('q') | KeyCode::Esc => self.exit_requested = true,
+                        // The terminal does not send `SIGINT` in raw mode.
+                        KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                            self.interrupted.store(true, Ordering::Relaxed)
+                        }
                         KeyCode::Char('s') | KeyCode::Enter => self.save_checkpoint(),
+                        KeyCode::Char('p') => self
+                            .move_matching
+                            .set_paused(!self.move_matching.is_paused()),
+                        KeyCode::Right => {
+                            self.selected_metric =
+                                (self.selected_metric + 1) % self.move_matching.metrics().len()
+                        }
+                        KeyCode::Left => {
+                            self.selected_metric = self
+                                .selected_metric
+                                .checked_sub(1)
+                                .unwrap_or(self.move_matching.metrics().len() - 1)
+                        }
                         _ => {}
                     }
                 }
@@ -67,88 +198,231 @@ impl Interface {
         Ok(())
     }
 
+    fn sample_throughput(&mut self) {
+        let (time, positions) = self.last_sample;
+        let completed_positions = self.move_matching.completed_positions();
+        let sample = (completed_positions - positions) as f64 / time.elapsed().as_secs_f64();
+        self.throughput = Some(match self.throughput {
+            Some(throughput) => {
+                THROUGHPUT_SMOOTHING * sample + (1. - THROUGHPUT_SMOOTHING) * throughput
+            }
+            None => sample,
+        });
+        self.last_sample = (Instant::now(), completed_positions)
+    }
+
+    /// Throughput and estimated remaining time, for the progress label.
+    fn eta_label(&self, processed_positions: u64, total_positions: u64) -> String {
+        if processed_positions >= total_positions || self.move_matching.is_completed() {
+            return "done".to_string();
+        }
+        match self.throughput {
+            Some(throughput) if throughput > 0. => {
+                let remaining =
+                    ((total_positions - processed_positions) as f64 / throughput) as u64;
+                format!(
+                    "{throughput:.1} positions/s, ETA {}h{:02}m{:02}s",
+                    remaining / 3600,
+                    remaining / 60 % 60,
+                    remaining % 60
+                )
+            }
+            _ => "estimating…".to_string(),
+        }
+    }
+
     fn save_checkpoint(&mut self) {
-        save_results(
-            format!("{}.csv", self.experiment_name),
-            Performance {
-                name: &self.experiment_name,
-                matches: self.move_matching.snapshot(),
-            },
+        crate::save_checkpoint(
+            &self.experiment_name,
+            self.results_format,
+            &self.move_matching,
         );
-        plot_results(
-            format!("{}.png", self.experiment_name),
-            std::iter::once(Performance {
-                name: &self.experiment_name,
-                matches: self.move_matching.snapshot(),
-            }),
+    }
+
+    /// Progress of the run, with its throughput and the positions excluded or failed.
+    fn progress_label(&self) -> String {
+        let processed_positions = self.move_matching.processed_positions();
+        let total_positions = self.move_matching.total_positions();
+        let mut label = format!(
+            "{processed_positions}/{total_positions} positions ({})",
+            self.eta_label(processed_positions, total_positions)
         );
+        let book_positions = self.move_matching.book_positions();
+        if book_positions > 0 {
+            label.push_str(&format!(" ({book_positions} book moves excluded)"))
+        }
+        let swapped_positions = self.move_matching.swapped_positions();
+        if swapped_positions > 0 {
+            label.push_str(&format!(" ({swapped_positions} swaps excluded)"))
+        }
+        let failed_positions = self.move_matching.failed_positions();
+        if failed_positions > 0 {
+            label.push_str(&format!(" ({failed_positions} positions failed"));
+            let failed_games = self.move_matching.failed_games();
+            if failed_games > 0 {
+                label.push_str(&format!(", {failed_games} games partially matched"))
+            }
+            label.push(')')
+        }
+        let truncated_games = self.move_matching.truncated_games();
+        if truncated_games > 0 {
+            label.push_str(&format!(
+                " ({truncated_games} games over their time budget)"
+            ))
+        }
+        label
     }
 
     fn draw_progress(&self, area: Rect, buffer: &mut Buffer) {
-        let completed_positions = self.move_matching.completed_positions();
+        let processed_positions = self.move_matching.processed_positions();
         let total_positions = self.move_matching.total_positions();
+        let label = self.progress_label();
+        let mut title = Title::from("Progress").alignment(Alignment::Left);
+        let stopped_worker = self
+            .move_matching
+            .worker_statuses()
+            .into_iter()
+            .enumerate()
+            .find_map(|(id, w)| Some((id, w.stop_reason?)));
+        if let Some((id, reason)) = stopped_worker {
+            title = Title::from(Span::styled(
+                format!("Error: worker {id} stopped, {reason}"),
+                Style::new().fg(Color::Red).bold(),
+            ));
+        } else if self.move_matching.is_paused() {
+            title = Title::from(Span::styled(
+                "PAUSED, press p to resume",
+                Style::new().fg(Color::Yellow).bold(),
+            ));
+        } else if let Some((m, fraction)) = self.move_matching.stuck_move() {
+            title = Title::from(Span::styled(
+                format!(
+                    "Warning: engine played {m:?} in {:.0}% of recent positions",
+                    fraction * 100.
+                ),
+                Style::new().fg(Color::Red).bold(),
+            ));
+        }
         Gauge::default()
             .block(
                 Block::new()
                     .borders(Borders::all())
-                    .title(Title::from("Progress").alignment(Alignment::Left))
+                    .title(title)
                     .fg(Color::White),
             )
             .gauge_style(Color::Green)
-            .ratio(completed_positions as f64 / total_positions as f64)
-            .label(Span::styled(
-                format!("{completed_positions}/{total_positions} positions"),
-                Style::new().fg(Color::White),
-            ))
+            .ratio(processed_positions as f64 / total_positions as f64)
+            .label(Span::styled(label, Style::new().fg(Color::White)))
             .render(area, buffer);
     }
 
-    fn draw_plot(&self, area: Rect, buffer: &mut Buffer) {
-        let mut brackets_performance = [(0, 0); 18];
-        for (elo, matches, total) in self.move_matching.snapshot() {
-            let bracket_index = (elo / 100) - 11;
-            brackets_performance[bracket_index as usize].0 += matches;
-            brackets_performance[bracket_index as usize].1 += total;
-        }
-        let mut plot_data = [(0., 0.); 18];
-        for (i, (matches, total)) in brackets_performance.into_iter().enumerate() {
-            let bracket = (i as u32 + 11) * 100;
-            let accuracy = if total == 0 {
-                0.
-            } else {
-                (matches as f64 / total as f64) * 100f64
+    fn draw_workers(&self, workers: &[WorkerStatus], area: Rect, buffer: &mut Buffer) {
+        let rows = workers.iter().enumerate().map(|(id, worker)| {
+            let (state, color) = match worker.state {
+                WorkerState::Idle => ("idle", Color::Gray),
+                WorkerState::Matching => ("healthy", Color::Green),
+                WorkerState::Errored => ("errored", Color::Red),
+                WorkerState::Stopped => ("stopped", Color::Red),
             };
-            plot_data[i] = (bracket as f64, accuracy)
-        }
+            Row::new([
+                id.to_string(),
+                state.to_string(),
+                worker
+                    .game_index
+                    .map_or("-".to_string(), |game| game.to_string()),
+                worker
+                    .last_move
+                    .map_or("-".to_string(), |(x, y)| format!("{x},{y}")),
+                worker.errors.to_string(),
+                worker.restarts.to_string(),
+            ])
+            .style(Style::new().fg(color))
+        });
+        Table::new(rows, [Constraint::Length(10); 6])
+            .header(Row::new(["Worker", "State", "Game", "Last move", "Errors", "Restarts"]).bold())
+            .block(
+                Block::bordered()
+                    .title(Title::from("Workers").alignment(Alignment::Left))
+                    .fg(Color::White),
+            )
+            .render(area, buffer);
+    }
 
-        let dataset = Dataset::default()
-            .name(self.experiment_name.as_str().italic())
-            .marker(ratatui::symbols::Marker::Braille)
-            .style(Style::default().fg(Color::Red))
-            .graph_type(ratatui::widgets::GraphType::Line)
-            .data(&plot_data);
+    fn draw_plot(&self, metric: &Metric, area: Rect, buffer: &mut Buffer) {
+        let y_max = metric
+            .series
+            .iter()
+            .flat_map(|(_, points)| points)
+            .map(|(_, y)| *y)
+            .fold(0f64, f64::max)
+            .max(1.);
+        let y_max = (y_max * 1.1).ceil();
+        let y_labels = (0..=8)
+            .map(|i| format!("{:.0}", y_max * i as f64 / 8.))
+            .collect::<Vec<_>>();
+        // Bounds fit the points, so that none of them is clipped, with a margin of 5% on both
+        // sides. Without points, those of brackets are used.
+        let (x_min, x_max) = metric
+            .series
+            .iter()
+            .flat_map(|(_, points)| points)
+            .fold(None, |bounds: Option<(f64, f64)>, (x, _)| match bounds {
+                Some((min, max)) => Some((min.min(*x), max.max(*x))),
+                None => Some((*x, *x)),
+            })
+            .map(|(min, max)| {
+                let margin = ((max - min) * 0.05).max(1.);
+                ((min - margin).floor(), (max + margin).ceil())
+            })
+            .unwrap_or_else(|| {
+                let brackets = self.move_matching.brackets();
+                (brackets.min as f64, brackets.max as f64)
+            });
+        let x_labels = (0..=8)
+            .map(|i| format!("{:.0}", x_min + (x_max - x_min) * i as f64 / 8.))
+            .collect::<Vec<_>>();
 
-        Chart::new(vec![dataset])
+        const PALETTE: [Color; 3] = [Color::Red, Color::Yellow, Color::Cyan];
+        let datasets = metric
+            .series
+            .iter()
+            .zip(PALETTE.iter().cycle())
+            .enumerate()
+            .map(|(i, ((series, points), color))| {
+                let name = if i == 0 {
+                    self.experiment_name.clone()
+                } else {
+                    format!("{} ({series})", self.experiment_name)
+                };
+                Dataset::default()
+                    .name(name.italic())
+                    .marker(ratatui::symbols::Marker::Braille)
+                    .style(Style::default().fg(*color))
+                    .graph_type(ratatui::widgets::GraphType::Line)
+                    .data(points)
+            })
+            .collect();
+
+        Chart::new(datasets)
             .block(
                 Block::bordered()
-                    .title(Title::from("Performance").alignment(Alignment::Left))
+                    .title(Title::from(metric.name.as_str()).alignment(Alignment::Left))
+                    .title(Title::from("<- / -> to switch metric").alignment(Alignment::Right))
                     .fg(Color::White),
             )
             .x_axis(
                 Axis::default()
-                    .title("Rating")
+                    .title(metric.x_label)
                     .style(Style::default().white())
-                    .bounds([1400., 3000.])
-                    .labels([
-                        "1400", "1600", "1800", "2000", "2200", "2400", "2600", "2800", "3000",
-                    ]),
+                    .bounds([x_min, x_max])
+                    .labels(x_labels),
             )
             .y_axis(
                 Axis::default()
-                    .title("Move matching %")
+                    .title(metric.y_label)
                     .style(Style::default().white())
-                    .bounds([0., 80.])
-                    .labels(["0", "10", "20", "30", "40", "50", "60", "70", "80"]),
+                    .bounds([0., y_max])
+                    .labels(y_labels),
             )
             .legend_position(Some(ratatui::widgets::LegendPosition::TopLeft))
             .render(area, buffer);
@@ -159,9 +433,30 @@ impl Widget for &Interface {
     where
         Self: Sized,
     {
-        let [progress, plot] =
-            Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(area);
+        let workers = self.move_matching.worker_statuses();
+        let [progress, plot, workers_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Fill(1),
+            Constraint::Length((workers.len() as u16 + 3).min(area.height / 3)),
+        ])
+        .areas(area);
         self.draw_progress(progress, buffer);
-        self.draw_plot(plot, buffer);
+        let metrics = self.move_matching.metrics();
+        let metric = &metrics[self.selected_metric.min(metrics.len() - 1)];
+        self.draw_plot(metric, plot, buffer);
+        self.draw_workers(&workers, workers_area, buffer);
     }
 }
+
+/// Progress bar of the positions matched, with their throughput and the time left.
+#[cfg(feature = "progress-bar")]
+fn progress_bar(total_positions: u64) -> indicatif::ProgressBar {
+    let progress_bar = indicatif::ProgressBar::new(total_positions);
+    progress_bar.set_style(
+        indicatif::ProgressStyle::with_template(
+            "{wide_bar} {pos}/{len} positions ({per_sec}, {eta} left)",
+        )
+        .unwrap(),
+    );
+    progress_bar
+}
@@ -1,93 +1,1271 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 
-use db::load_database;
-use interface::Interface;
-use move_matching::MoveMatching;
-use plot::{plot_rating_distribution, plot_results, save_results, Performance};
-use protocol::Engine;
+use signal_hook::consts::SIGINT;
+
+use db::{
+    is_compiled_database, is_exported_database, is_psq_database, load_database_with_cache,
+    load_psq_database, Color, DbFilter, Game, PsqConfig, Sampling, WhrConfig,
+};
+use event_log::EventLog;
+use interface::{CheckpointConfig, Interface};
+use move_matching::{
+    GameLog, GameObserver, GameResult, MatchingOptions, MatchingResults, MoveMatching, Schedule,
+};
+use plot::{
+    bracketize, load_completed_games, load_distance_results, load_metadata, load_move_results,
+    load_player_results, load_results, load_weighted_results, plot_move_results,
+    plot_rating_distribution, plot_results, plot_results_in_range, rating_axis_range,
+    rating_distribution, save_completed_games, save_distance_results, save_move_results,
+    save_player_results, save_rating_distribution, save_results, save_results_json,
+    save_weighted_results, Brackets, Performance, PlotOptions, PlotStyle, ResultsError,
+    ResultsFormat,
+};
+use protocol::{Command, Engine, EngineConfig, EngineError, Response, Rule};
+use stats::sample_indices;
 
 pub mod db;
+pub mod event_log;
 pub mod interface;
 pub mod move_matching;
 pub mod plot;
+pub mod profile;
 pub mod protocol;
+pub mod selftest;
+pub mod stats;
+#[cfg(test)]
+mod test_utils;
 
-pub fn move_matching_performance<P: AsRef<Path>>(
-    name: &str,
-    engine_command: &str,
-    database_path: P,
-    threads: u32,
-    games_count: Option<usize>,
-    move_time: u32,
+/// Number of times a worker tries to open its engine before giving up.
+const ENGINE_OPEN_ATTEMPTS: u32 = 3;
+/// Time between two attempts to open an engine.
+const ENGINE_OPEN_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Time workers are given to close their engines once matching is over, before exiting anyway.
+const WORKERS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time between two checks of whether a run without interface is over.
+const HEADLESS_POLL_PERIOD: Duration = Duration::from_millis(100);
+/// Time over which throughput is measured for each worker count when auto-tuning.
+const AUTO_TUNE_WINDOW: Duration = Duration::from_secs(60);
+/// Relative throughput gain required to keep adding workers when auto-tuning.
+const AUTO_TUNE_MIN_GAIN: f64 = 1.05;
+
+/// Settings of a move matching experiment.
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// Name of the experiment, used to name output files.
+    pub name: String,
+    pub engine: EngineConfig,
+    pub database_path: PathBuf,
+    /// Number of workers, each running its own engine.
+    pub threads: u32,
+    /// Number of games in a row a worker can fail to match before it stops, since its engine is
+    /// then probably misconfigured.
+    pub max_consecutive_errors: u32,
+    /// Only uses this many games from the database.
+    pub games_count: Option<usize>,
+    /// How the games are picked when only `games_count` of them are used.
+    pub sample: Sampling,
+    /// Seed of the random sampling of games.
+    pub seed: u64,
+    /// Starts with fewer workers and adds more as long as it increases throughput, up to
+    /// `threads`.
+    pub auto_tune: bool,
+    /// CSV file the results of each game are appended to as soon as the game completes.
+    pub game_log: Option<PathBuf>,
+    /// JSON lines file every position is recorded to, so that the run can be re-scored later.
+    pub event_log: Option<PathBuf>,
+    pub matching: MatchingOptions,
+    /// First move number of each band of moves results are also aggregated in.
+    pub move_bands: Vec<u32>,
+    pub whr: WhrConfig,
+    pub filter: DbFilter,
+    /// File the games of XML databases and their ratings are cached to, so that later runs
+    /// neither parse nor rate them again.
+    pub rating_cache: Option<PathBuf>,
+    /// Ratings of the players when the database holds Piskvork games.
+    pub psq: PsqConfig,
+    /// Minimizes run-to-run variance at the expense of throughput: games are matched in order
+    /// by a single worker, engines are configured to be deterministic, and their hash is
+    /// cleared before each position.
+    pub deterministic: bool,
+    /// Also plots results for each band of this many rating points, in separate files.
+    pub segment_plots: Option<u32>,
+    /// Also saves the number of players of each rating bracket, as plotted to the rating
+    /// distribution.
+    pub rating_distribution_csv: bool,
+    /// Format results are saved and resumed in.
+    pub results_format: ResultsFormat,
+    pub checkpoints: CheckpointConfig,
+    /// Only checks the database and the engine, without matching any game.
+    pub dry_run: bool,
+    /// Logs progress instead of displaying the TUI, for runs without a terminal. This is also
+    /// the case when the standard output is not a terminal.
+    pub no_tui: bool,
+}
+
+/// Runs the move matching experiment, displaying its progress in a TUI, then saves and plots
+/// its results. Results saved by a previous run are resumed from.
+pub fn move_matching_performance(config: MatchConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let run_start = Instant::now();
+    let Some(Run {
+        config,
+        matching,
+        running_workers,
+    }) = start_run(config, |config, games| {
+        if config.dry_run {
+            check_setup(games, &config.matching, &config.engine)?;
+            return Ok(None);
+        }
+        save_rating_distribution_of(config, &games)?;
+        resume_matching(config, games).map(Some)
+    })?
+    else {
+        return Ok(());
+    };
+    let name = config.name.as_str();
+
+    // A first interruption quits like `q` does, saving the results, and a second one exits
+    // immediately in case saving is stuck.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 130, interrupted.clone())?;
+    signal_hook::flag::register(SIGINT, interrupted.clone())?;
+
+    let interface = Interface::new(
+        name.to_string(),
+        config.results_format,
+        matching.clone(),
+        interrupted,
+        config.checkpoints,
+    );
+    // Only errors are logged with the TUI, which shows itself that it was chosen
+    let no_tui = if config.no_tui {
+        log::info!("Logging progress instead of displaying the TUI");
+        true
+    } else if !std::io::stdout().is_terminal() {
+        log::info!(
+            "The standard output is not a terminal, logging progress instead of displaying the TUI"
+        );
+        true
+    } else {
+        false
+    };
+    if no_tui {
+        interface.log_loop();
+    } else {
+        let terminal = ratatui::init();
+        if let Err(e) = interface.render_loop(terminal) {
+            eprintln!("Error: interface failed with {e:?}")
+        }
+        ratatui::restore();
+    }
+    // Engines left running by workers would outlive this process.
+    let start = Instant::now();
+    while running_workers.load(Ordering::Relaxed) > 0 {
+        if start.elapsed() > WORKERS_SHUTDOWN_TIMEOUT {
+            log::warn!("Some workers did not close their engine in time");
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10))
+    }
+    save_final_results(&config, &matching, run_start.elapsed(), !no_tui);
+
+    match matching.abort_reason() {
+        Some(reason) => Err(reason.into()),
+        None => Ok(()),
+    }
+}
+
+/// Runs the move matching experiment without any interface, until every game is matched or
+/// every worker stopped, and returns its results instead of saving them.
+///
+/// Nothing is resumed from, and only the game and event logs are written to: the name,
+/// results format, checkpoints, segment plots, dry run and TUI settings are ignored.
+pub fn run_matching_headless(
+    config: MatchConfig,
+) -> Result<MatchingResults, Box<dyn std::error::Error>> {
+    run_headless(config, None)
+}
+
+/// Like [`run_matching_headless`], also calling `observer` with the results of each game once
+/// they are kept, for instance to stream them elsewhere, see [`MoveMatching::with_observer`].
+pub fn run_matching_headless_with_observer(
+    config: MatchConfig,
+    observer: impl Fn(GameResult) + Send + Sync + 'static,
+) -> Result<MatchingResults, Box<dyn std::error::Error>> {
+    run_headless(config, Some(Box::new(observer)))
+}
+
+fn run_headless(
+    config: MatchConfig,
+    observer: Option<GameObserver>,
+) -> Result<MatchingResults, Box<dyn std::error::Error>> {
+    let Some(Run { matching, .. }) = start_run(config, |config, games| {
+        let mut matching = MoveMatching::from_games(games, config.matching)
+            .with_move_bands(config.move_bands.clone());
+        if let Some(observer) = observer {
+            matching = matching.with_observer(observer);
+        }
+        Ok(Some(matching))
+    })?
+    else {
+        unreachable!("headless runs always match their games")
+    };
+    while !matching.is_completed() && !matching.is_stopped() {
+        std::thread::sleep(HEADLESS_POLL_PERIOD)
+    }
+    Ok(matching.results())
+}
+
+/// A run whose workers are matching its games.
+struct Run {
+    config: MatchConfig,
+    matching: Arc<MoveMatching>,
+    running_workers: Arc<AtomicUsize>,
+}
+
+/// Starts a run: applies the settings implied by others, loads the games and starts workers
+/// matching them with the matching built by `setup`, writing to the event log when given.
+/// Nothing is matched when `setup` returns `None`.
+fn start_run(
+    config: MatchConfig,
+    setup: impl FnOnce(
+        &MatchConfig,
+        Arc<[Game]>,
+    ) -> Result<Option<MoveMatching>, Box<dyn std::error::Error>>,
+) -> Result<Option<Run>, Box<dyn std::error::Error>> {
+    let config = prepare_config(config);
+    let (games, indices) = load_games(&config)?;
+    let Some(mut matching) = setup(&config, games)? else {
+        return Ok(None);
+    };
+    matching = matching.with_database_indices(indices);
+    if let Some(event_log) = &config.event_log {
+        matching = matching.with_event_log(EventLog::open(event_log)?);
+    }
+    let matching = Arc::new(matching);
+    let running_workers = start_workers(&config, &matching)?;
+    Ok(Some(Run {
+        config,
+        matching,
+        running_workers,
+    }))
+}
+
+/// Plots the distribution of the ratings of the players of `games`, and saves it as well when
+/// asked to.
+fn save_rating_distribution_of(
+    config: &MatchConfig,
+    games: &[Game],
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let database_name = database_path
-        .as_ref()
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap();
-
-    let games = load_database(database_path.as_ref()).unwrap();
-    let games = Vec::from(if let Some(i) = games_count {
-        &games[0..i]
+    let name = config.name.as_str();
+    log::info!("Saving rating distribution to {name}_rating_distribution.png");
+    plot_rating_distribution(
+        format!("{name}_rating_distribution.png"),
+        games,
+        &PlotStyle::default(),
+    );
+    if config.rating_distribution_csv {
+        log::info!("Saving rating distribution to {name}_rating_distribution.csv");
+        let distribution = rating_distribution(games, config.matching.brackets.width);
+        save_rating_distribution(format!("{name}_rating_distribution.csv"), &distribution)?;
+    }
+    Ok(())
+}
+
+/// Matching of `games`, resumed from the results saved under the name of the run when they
+/// exist.
+fn resume_matching(
+    config: &MatchConfig,
+    games: Arc<[Game]>,
+) -> Result<MoveMatching, Box<dyn std::error::Error>> {
+    let name = config.name.as_str();
+    let matching_options = config.matching;
+    let extension = config.results_format.extension();
+    let checkpoint_path = format!("{name}.{extension}");
+    let settings = run_settings(config);
+    let ratings = ratings_checksum(&games);
+    let matching = if Path::new(&checkpoint_path).exists() {
+        if is_rerated(&checkpoint_path, &ratings)? {
+            log::warn!(
+                "The results were saved with other ratings of the players, which vary between runs rating an XML database: resumed accuracies mix both ratings, use --rating-cache or a compiled database to rate games once"
+            )
+        }
+        let mut matching = MoveMatching::from_checkpoint(
+            games.clone(),
+            matching_options,
+            settings.clone(),
+            &checkpoint_path,
+        )?
+        .with_settings([settings, vec![("ratings", ratings)]].concat())
+        .with_move_bands(config.move_bands.clone());
+        for k in 2..=matching_options.top_k {
+            let path = format!("{name}_top{k}.{extension}");
+            if Path::new(&path).exists() {
+                matching.restore_top_k(k, &load_results(path)?);
+            }
+        }
+        if matching_options.split_colors {
+            for (color, suffix) in [(Color::Black, "black"), (Color::White, "white")] {
+                let path = format!("{name}_{suffix}.{extension}");
+                if Path::new(&path).exists() {
+                    matching.restore_color(color, &load_results(path)?);
+                }
+            }
+        }
+        let players_path = format!("{name}_players.csv");
+        if matching_options.by_player && Path::new(&players_path).exists() {
+            matching.restore_players(&load_player_results(players_path)?);
+        }
+        let weighted_path = format!("{name}_weighted.csv");
+        if matching.is_weighted() && Path::new(&weighted_path).exists() {
+            matching.restore_weighted(&load_weighted_results(weighted_path)?);
+        }
+        let moves_path = format!("{name}_moves.csv");
+        if Path::new(&moves_path).exists() {
+            matching.restore_move_bands(&load_move_results(moves_path)?);
+        }
+        if matching_options.near_miss_distance.is_some() {
+            let path = format!("{name}_near.{extension}");
+            if Path::new(&path).exists() {
+                matching.restore_near_misses(&load_results(path)?);
+            }
+            let distances_path = format!("{name}_distances.csv");
+            if Path::new(&distances_path).exists() {
+                matching.restore_distances(&load_distance_results(distances_path)?);
+            }
+        }
+        let completed_path = format!("{name}_completed.csv");
+        if Path::new(&completed_path).exists() {
+            matching.restore_completed_games(&load_completed_games(completed_path)?);
+        }
+        matching
     } else {
-        &games
-    });
+        MoveMatching::from_games(games.clone(), matching_options)
+            .with_settings([settings, vec![("ratings", ratings)]].concat())
+            .with_move_bands(config.move_bands.clone())
+    };
+    Ok(matching)
+}
+
+/// Logs what the run excluded and its accuracy, then saves and plots its final results.
+///
+/// The summary of the run is logged last, or printed to stderr after the TUI, which only logs
+/// errors by default, so that it is shown either way.
+fn save_final_results(
+    config: &MatchConfig,
+    matching: &MoveMatching,
+    elapsed: Duration,
+    after_tui: bool,
+) {
+    let name = config.name.as_str();
+    if matching.book_positions() > 0 {
+        log::info!(
+            "Excluded {} positions where the engine played a book move",
+            matching.book_positions()
+        );
+    }
+    if matching.swapped_positions() > 0 {
+        log::info!(
+            "Excluded {} positions where the engine chose to swap colors",
+            matching.swapped_positions()
+        );
+    }
+    if matching.failed_games() > 0 {
+        log::warn!(
+            "{} games were interrupted by engine errors and only partially matched",
+            matching.failed_games()
+        );
+    }
+    if matching.truncated_games() > 0 {
+        log::warn!(
+            "{} games took more than their time budget and were only partially matched",
+            matching.truncated_games()
+        );
+    }
+    if let Some((m, fraction)) = matching.stuck_move() {
+        log::warn!(
+            "The engine played {m:?} in {:.0}% of the last positions, it is probably broken",
+            fraction * 100.
+        );
+    }
+    if let Some(accuracy) = matching.overall_accuracy() {
+        log::info!("Overall accuracy: {:.2}%", accuracy * 100.);
+    }
+    for color in [Color::Black, Color::White] {
+        if let Some(accuracy) = matching.color_accuracy(color) {
+            log::info!(
+                "Overall accuracy of {color:?} players: {:.2}%",
+                accuracy * 100.
+            );
+        }
+    }
+    if let Some((distance, accuracy)) = matching
+        .near_miss_distance()
+        .zip(matching.near_miss_accuracy())
+    {
+        log::info!(
+            "Overall accuracy within {distance} points: {:.2}%",
+            accuracy * 100.
+        );
+    }
+    log::info!("Saving final results");
+    let mut paths = save_checkpoint(name, config.results_format, matching);
+    if let Some(width) = config.segment_plots {
+        let ratings = rating_axis_range(
+            matching
+                .snapshot()
+                .filter(|(_, _, total)| *total > 0)
+                .map(|(elo, _, _)| elo),
+        );
+        let mut start = ratings.start;
+        while start < ratings.end {
+            let end = (start + width).min(ratings.end);
+            let path = format!("{name}_{start}-{end}.png");
+            paths.push(path.clone());
+            plot_results_in_range(
+                path,
+                std::iter::once(Performance {
+                    name,
+                    matches: matching.snapshot(),
+                }),
+                matching.brackets(),
+                &PlotOptions::default(),
+                start..end,
+            );
+            start = end;
+        }
+    }
+    let summary = run_summary(matching, elapsed, &paths);
+    if after_tui && !log::log_enabled!(log::Level::Info) {
+        summary.iter().for_each(|line| eprintln!("{line}"));
+    } else {
+        summary.iter().for_each(|line| log::info!("{line}"));
+    }
+}
+
+/// Applies the settings implied by others, warning about inconsistent ones.
+fn prepare_config(mut config: MatchConfig) -> MatchConfig {
+    // The many other rules of `.rif` databases are variants of Renju or gomoku, which do not
+    // map to a single rule of engines: only the default selection of Renju games is checked.
+    let database_path = &config.database_path;
+    let renju_games = config.filter.rules == [1]
+        && !is_compiled_database(database_path)
+        && !is_exported_database(database_path)
+        && !is_psq_database(database_path);
+    if renju_games && config.engine.rule != Rule::Renju {
+        log::warn!(
+            "The engine plays with rule {:?} while the games are played with Renju rules",
+            config.engine.rule
+        )
+    }
+
+    if config.deterministic {
+        if config.threads > 1 || config.auto_tune {
+            log::warn!("Matching with a single worker to get reproducible results");
+        }
+        if config.matching.schedule != Schedule::Sequential {
+            log::warn!("Matching games in database order to get reproducible results");
+        }
+        config.threads = 1;
+        config.auto_tune = false;
+        config.matching.schedule = Schedule::Sequential;
+        config.engine.deterministic = true;
+        config.matching.clear_hash_every = Some(1);
+        if !is_compiled_database(database_path)
+            && !is_exported_database(database_path)
+            && !is_psq_database(database_path)
+        {
+            log::warn!(
+                "Ratings computed from an XML database vary slightly between runs, compile it to get reproducible results"
+            )
+        }
+    }
+    config
+}
+
+/// Lines summarizing what a run did: the games and positions matched, the accuracy of each
+/// bracket of ratings, how long it took and the files it wrote.
+fn run_summary(matching: &MoveMatching, elapsed: Duration, paths: &[String]) -> Vec<String> {
+    let seconds = elapsed.as_secs();
+    let mut summary = vec![format!(
+        "Matched {}/{} games and {}/{} positions in {}h{:02}m{:02}s, {} positions failed",
+        matching.completed_games(),
+        matching.total_games(),
+        matching.completed_positions(),
+        matching.total_positions(),
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        matching.failed_positions()
+    )];
+    let brackets = matching.brackets();
+    let rows = bracketize(matching.snapshot(), brackets);
+    if rows.iter().any(|(_, _, total)| *total > 0) {
+        summary.push("Accuracy by rating:".to_string());
+    }
+    for (start, matches, total) in rows.into_iter().filter(|(_, _, total)| *total > 0) {
+        summary.push(format!(
+            "  {start:>4}-{:<4} {:>6.2}% of {total} positions",
+            start + brackets.width,
+            matches as f64 / total as f64 * 100.
+        ));
+    }
+    summary.push(format!("Saved results to {}", paths.join(", ")));
+    summary
+}
+
+/// Settings of the run its results depend on, saved with them so that they are not resumed with
+/// other settings.
+fn run_settings(config: &MatchConfig) -> Vec<(&'static str, String)> {
+    vec![
+        ("move_time", config.engine.move_time.to_string()),
+        ("rule", format!("{:?}", config.engine.rule).to_lowercase()),
+        ("board_size", config.engine.board_size.to_string()),
+        ("skip_opening", config.matching.skip_opening.to_string()),
+    ]
+}
+
+/// Checksum of the ratings of the players of each game, saved with the results as `ratings`.
+fn ratings_checksum(games: &[Game]) -> String {
+    let mut hasher = crc32fast::Hasher::new();
+    for (index, game) in games.iter().enumerate() {
+        for value in [index as u64, game.black_elo, game.white_elo] {
+            hasher.update(&value.to_le_bytes());
+        }
+    }
+    format!("{:08x}", hasher.finalize())
+}
+
+/// Whether the results were saved with other `ratings`, as given by [`ratings_checksum`].
+///
+/// Unlike other settings, resuming with other ratings is only warned about: ratings computed
+/// from XML databases vary between runs, so refusing them would keep such runs from being
+/// resumed at all.
+fn is_rerated(path: &str, ratings: &str) -> Result<bool, ResultsError> {
+    Ok(load_metadata(path)?
+        .iter()
+        .any(|(key, saved)| key == "ratings" && saved != ratings))
+}
+
+/// Games matched by a run, along with the index of each one in the database.
+type SampledGames = (Arc<[Game]>, Vec<usize>);
+
+/// Loads the games of the database, keeping those sampled when only some of them are used.
+fn load_games(config: &MatchConfig) -> Result<SampledGames, Box<dyn std::error::Error>> {
+    let database_path = &config.database_path;
+    let database_name = database_path.file_name().unwrap().to_str().unwrap();
+    let games = if is_psq_database(database_path) {
+        load_psq_database(database_path, &config.psq)
+    } else {
+        load_database_with_cache(
+            database_path,
+            &config.whr,
+            &config.filter,
+            config.rating_cache.as_deref(),
+        )
+    }
+    .map_err(|e| format!("{database_name}: {e}"))?;
+    let seed = config.seed;
+    let indices = match (config.games_count, config.sample) {
+        (Some(count), Sampling::Head) => (0..count.min(games.len())).collect(),
+        (Some(count), Sampling::Random) => sample_indices(games.len(), count, seed),
+        (Some(count), Sampling::Stratified) => {
+            stratified_indices(&games, count, &config.matching.brackets, seed)
+        }
+        (None, _) => (0..games.len()).collect(),
+    };
+    let games = keep_indices(games, &indices);
     log::info!("Loaded database {database_name}");
-    log::info!("Saving rating distribution to {name}_rating_distribution.png");
-    plot_rating_distribution(format!("{name}_rating_distribution.png"), &games);
+    Ok((games.into(), indices))
+}
 
-    // Open engines
-    let checkpoint_path = format!("{name}.csv");
-    let matching = Arc::new(if Path::new(&checkpoint_path).exists() {
-        MoveMatching::from_checkpoint(&games, &checkpoint_path)
+/// Keeps the games at these sorted indices, moving them rather than copying them.
+fn keep_indices(games: Vec<Game>, indices: &[usize]) -> Vec<Game> {
+    games
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| indices.binary_search(i).is_ok())
+        .map(|(_, game)| game)
+        .collect()
+}
+
+/// Spawns the workers matching games, or the thread spawning them when auto-tuning. Returns
+/// the number of workers whose thread is running.
+fn start_workers(
+    config: &MatchConfig,
+    matching: &Arc<MoveMatching>,
+) -> Result<Arc<AtomicUsize>, Box<dyn std::error::Error>> {
+    let threads = config.threads as usize;
+    let max_workers = threads.min(config.games_count.unwrap_or(threads));
+    let pool = WorkerPool {
+        matching: matching.clone(),
+        engine_config: config.engine.clone(),
+        game_log: config
+            .game_log
+            .as_ref()
+            .map(GameLog::open)
+            .transpose()?
+            .map(Arc::new),
+        active_workers: Arc::new(AtomicUsize::new(max_workers)),
+        running_workers: Arc::new(AtomicUsize::new(0)),
+        max_consecutive_errors: config.max_consecutive_errors,
+    };
+    let running_workers = pool.running_workers.clone();
+    if config.auto_tune {
+        std::thread::spawn(move || pool.auto_tune(max_workers));
     } else {
-        MoveMatching::from_games(&games)
-    });
+        for i in 0..max_workers {
+            pool.spawn_worker(i);
+        }
+    }
+    Ok(running_workers)
+}
 
-    let terminal = ratatui::init();
-    let interface = Interface::new(name.to_string(), matching.clone());
-
-    let interface_handle = { std::thread::spawn(move || interface.render_loop(terminal)) };
-    let _workers_handle = (0..(threads as usize).min(games_count.unwrap_or(threads as usize)))
-        .map(|i| {
-            let matching = matching.clone();
-            let engine_command = engine_command.to_string();
-            std::thread::spawn(move || {
-                let mut engine =
-                    Engine::open_engine(i as usize, &engine_command, move_time).unwrap();
-                log::trace!("thread {i} waiting for next task");
-                while let Some(mut task) = matching.get_next_task() {
-                    if let Err(e) = task.match_challenge(&mut engine) {
-                        log::error!("[{i}] Error when matching: {e:?}")
-                    }
-                    log::info!("[{i}] Completed a move matching task");
-                }
-                engine.close_engine()
-            })
+/// Describes the games to match, and checks that the engine answers the first position with a
+/// move, without matching anything.
+fn check_setup(
+    games: Arc<[Game]>,
+    matching_options: &MatchingOptions,
+    engine_config: &EngineConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let positions = MoveMatching::from_games(games.clone(), *matching_options).total_positions();
+    println!("{} games, {positions} positions to match", games.len());
+    let elos = games.iter().flat_map(|g| [g.black_elo, g.white_elo]);
+    if let (Some(min), Some(max)) = (elos.clone().min(), elos.max()) {
+        println!("Ratings from {min} to {max}");
+    }
+
+    let Some((game, ply)) = games
+        .iter()
+        .find_map(|g| Some((g, matching_options.first_matched_ply(g)?)))
+    else {
+        return Err("no position to match".into());
+    };
+    let mut engine = Engine::open_engine(0, engine_config)?;
+    let info = engine.info();
+    println!("Engine: {} {}", info.name, info.version);
+    let response = engine.send_command(Command::Board(&game.moves[..ply]))?;
+    if let Err(e) = engine.close_engine() {
+        log::warn!("Could not close the engine: {e}")
+    }
+    match response {
+        Response::Move(engine_move) | Response::Suggest(engine_move) => {
+            println!(
+                "The engine played {engine_move:?} where the human played {:?}",
+                game.moves[ply]
+            );
+            Ok(())
+        }
+        r => Err(format!("the engine answered {r:?} instead of a move").into()),
+    }
+}
+
+/// Indices of `count` random games, spread as evenly as possible between rating brackets, by
+/// the average rating of their players. Brackets with too few games are used entirely, and
+/// the rest of their share is given to the others.
+fn stratified_indices(games: &[Game], count: usize, brackets: &Brackets, seed: u64) -> Vec<usize> {
+    let mut strata = vec![vec![]; brackets.count()];
+    for (i, game) in games.iter().enumerate() {
+        if let Some(bracket) = brackets.index((game.black_elo + game.white_elo) / 2) {
+            strata[bracket].push(i)
+        }
+    }
+    let mut quotas = vec![0; strata.len()];
+    let mut remaining = count;
+    let mut open = (0..strata.len())
+        .filter(|b| !strata[*b].is_empty())
+        .collect::<Vec<_>>();
+    let even_share = count / open.len().max(1);
+    while remaining > 0 && !open.is_empty() {
+        let share = (remaining / open.len()).max(1);
+        for b in &open {
+            let quota = share.min(strata[*b].len() - quotas[*b]).min(remaining);
+            quotas[*b] += quota;
+            remaining -= quota;
+        }
+        open.retain(|b| quotas[*b] < strata[*b].len());
+    }
+    for (b, stratum) in strata.iter().enumerate() {
+        if !stratum.is_empty() && stratum.len() < even_share {
+            log::warn!(
+                "Only {} games in the {} bracket, using all of them",
+                stratum.len(),
+                brackets.start(b)
+            )
+        }
+    }
+    if remaining > 0 {
+        log::warn!("Only {} games are in rating brackets", count - remaining)
+    }
+
+    let mut indices = strata
+        .iter()
+        .zip(quotas)
+        .enumerate()
+        .flat_map(|(b, (stratum, quota))| {
+            sample_indices(stratum.len(), quota, seed.wrapping_add(b as u64))
+                .into_iter()
+                .map(|i| stratum[i])
         })
         .collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices
+}
 
-    if let Err(e) = interface_handle.join().unwrap() {
-        eprintln!("Error: interface failed with {e:?}")
-    }
-    ratatui::restore();
+/// Saves the results of the experiment in `format`, with the top-k, per-color, per-player and
+/// near-miss results when kept, the results by band of moves and the games completed, and
+/// plots them.
+///
+/// Per-player, weighted, per-distance and per-move results and completed games are always
+/// saved as CSV. Returns the paths of the files written.
+pub(crate) fn save_checkpoint(
+    name: &str,
+    format: ResultsFormat,
+    matching: &MoveMatching,
+) -> Vec<String> {
+    let mut paths = vec![];
+    let mut output = |path: String| {
+        paths.push(path.clone());
+        path
+    };
+    // Every result is read at once, so that games completed meanwhile are either in every
+    // file or in none, then written once workers can add their results again
+    let results_lock = matching.lock_results();
+    let metadata = matching.results_metadata();
+    let results = matching.snapshot().collect::<Vec<_>>();
+    let top_k_results = (2..=matching.top_k())
+        .map(|k| (k, matching.top_k_snapshot(k).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    let color_results = [(Color::Black, "black"), (Color::White, "white")]
+        .into_iter()
+        .filter(|_| matching.splits_colors())
+        .map(|(color, suffix)| (suffix, matching.color_snapshot(color).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    let player_results = matching
+        .aggregates_by_player()
+        .then(|| matching.player_snapshot().collect::<Vec<_>>());
+    let near_miss_results = matching.near_miss_distance().map(|_| {
+        (
+            matching.near_miss_snapshot().collect::<Vec<_>>(),
+            matching.distance_snapshot().collect::<Vec<_>>(),
+        )
+    });
+    let weighted_results = matching
+        .is_weighted()
+        .then(|| matching.weighted_snapshot().collect::<Vec<_>>());
+    let completed_games = matching.completed_game_indices().collect::<Vec<_>>();
+    let move_results = matching.move_band_snapshot().collect::<Vec<_>>();
+    drop(results_lock);
 
-    log::info!("Saving final results");
-    save_results(
-        format!("{name}.csv"),
+    let extension = format.extension();
+    let save = |path: String, results: &[(u64, u32, u32)]| {
+        let performance = Performance {
+            name,
+            matches: results.iter().copied(),
+        };
+        match format {
+            ResultsFormat::Csv => save_results(path, performance, &metadata),
+            ResultsFormat::Json => save_results_json(path, performance, &metadata),
+        }
+    };
+    save(output(format!("{name}.{extension}")), &results);
+    for (k, results) in &top_k_results {
+        save(output(format!("{name}_top{k}.{extension}")), results);
+    }
+    let mut performances = vec![(name.to_string(), results)];
+    for (suffix, results) in color_results {
+        save(output(format!("{name}_{suffix}.{extension}")), &results);
+        performances.push((format!("{name} ({suffix})"), results));
+    }
+    if let Some(results) = player_results {
+        save_player_results(
+            output(format!("{name}_players.csv")),
+            Performance {
+                name,
+                matches: results.into_iter(),
+            },
+            matching.player_names(),
+            &metadata,
+        );
+    }
+    if let Some((results, distances)) = near_miss_results {
+        save(output(format!("{name}_near.{extension}")), &results);
+        save_distance_results(
+            output(format!("{name}_distances.csv")),
+            distances.into_iter(),
+            &metadata,
+        );
+    }
+    if let Some(results) = weighted_results {
+        save_weighted_results(
+            output(format!("{name}_weighted.csv")),
+            results.into_iter(),
+            &metadata,
+        );
+    }
+    save_completed_games(
+        output(format!("{name}_completed.csv")),
+        completed_games.into_iter(),
+        &metadata,
+    );
+    save_move_results(
+        output(format!("{name}_moves.csv")),
         Performance {
-            name: &name,
-            matches: matching.snapshot(),
+            name,
+            matches: move_results.iter().copied(),
         },
+        &metadata,
     );
-    plot_results(
-        format!("{name}.png"),
+    plot_move_results(
+        output(format!("{name}_moves.png")),
         std::iter::once(Performance {
-            name: &name,
-            matches: matching.snapshot(),
+            name,
+            matches: move_results.iter().copied(),
         }),
+        &PlotOptions::default(),
     );
+    plot_results(
+        output(format!("{name}.png")),
+        performances.iter().map(|(name, results)| Performance {
+            name,
+            matches: results.iter().copied(),
+        }),
+        matching.brackets(),
+        &PlotOptions::default(),
+    );
+    paths
+}
 
-    Ok(())
+/// State shared by all workers.
+#[derive(Clone)]
+struct WorkerPool {
+    matching: Arc<MoveMatching>,
+    engine_config: EngineConfig,
+    game_log: Option<Arc<GameLog>>,
+    /// Workers stop pulling tasks once their id is not below this count.
+    active_workers: Arc<AtomicUsize>,
+    /// Number of workers whose thread is running.
+    running_workers: Arc<AtomicUsize>,
+    /// Number of games in a row a worker can fail to match before it stops.
+    max_consecutive_errors: u32,
+}
+impl WorkerPool {
+    /// Spawns a worker running matching tasks on its own engine, until no task remains or
+    /// its id is not below the number of active workers anymore.
+    ///
+    /// Once the last running worker stops before every game is matched, matching is aborted,
+    /// since no worker is left to match the remaining games.
+    fn spawn_worker(&self, id: usize) -> JoinHandle<()> {
+        let pool = self.clone();
+        pool.running_workers.fetch_add(1, Ordering::Relaxed);
+        std::thread::spawn(move || {
+            pool.run_worker(id);
+            let last = pool.running_workers.fetch_sub(1, Ordering::Relaxed) == 1;
+            if last && !pool.matching.is_completed() && !pool.matching.is_stopped() {
+                let reason = pool
+                    .matching
+                    .worker_statuses()
+                    .into_iter()
+                    .find_map(|w| w.stop_reason);
+                let reason = match reason {
+                    Some(reason) => {
+                        format!("every worker stopped before all games were matched: {reason}")
+                    }
+                    None => "every worker stopped before all games were matched".to_string(),
+                };
+                log::error!("Aborting matching: {reason}");
+                pool.matching.abort(reason)
+            }
+        })
+    }
+
+    /// Runs matching tasks on the engine of a worker.
+    fn run_worker(&self, id: usize) {
+        let mut engine = match self.open_engine(id) {
+            Ok(engine) => engine,
+            Err(e) => {
+                self.matching
+                    .set_worker_stopped(id, format!("its engine could not be opened: {e}"));
+                return;
+            }
+        };
+        let mut consecutive_errors = 0;
+        self.matching.set_engine_info(engine.info());
+        log::trace!("thread {id} waiting for next task");
+        while id < self.active_workers.load(Ordering::Relaxed) {
+            let Some(mut task) = self.matching.get_next_task(id) else {
+                break;
+            };
+            let result = task.match_challenge(&mut engine);
+            if let Some(game_log) = self.game_log.as_ref().filter(|_| !task.is_requeued()) {
+                if let Err(e) = game_log.append(&task.result()) {
+                    log::error!("[{id}] Could not write to the game log: {e:?}")
+                }
+            }
+            log::info!("[{id}] Completed a move matching task");
+            consecutive_errors = if result.is_ok() {
+                0
+            } else {
+                consecutive_errors + 1
+            };
+            match result {
+                Err(e) if consecutive_errors >= self.max_consecutive_errors => {
+                    let reason = format!(
+                        "{consecutive_errors} games failed in a row, the last one with: {e}"
+                    );
+                    log::error!("[{id}] {reason}, this worker stops");
+                    self.matching.set_worker_stopped(id, reason);
+                    return;
+                }
+                // The engine hung or crashed: it is replaced by a new one, configured
+                // the same way.
+                Err(e) if e.is_fatal() => {
+                    let restarts = self.matching.record_engine_restart(id);
+                    log::error!(
+                        "[{id}] Engine failed, restarting it ({restarts} restarts so far): {e}"
+                    );
+                    engine = match self.open_engine(id) {
+                        Ok(engine) => engine,
+                        Err(e) => {
+                            self.matching.set_worker_stopped(
+                                id,
+                                format!("its engine could not be restarted: {e}"),
+                            );
+                            return;
+                        }
+                    };
+                }
+                Err(e) => log::error!("[{id}] Error when matching: {e}"),
+                Ok(()) => {}
+            }
+        }
+        self.matching.set_worker_idle(id);
+        if let Err(e) = engine.close_engine() {
+            log::warn!("[{id}] Could not close the engine: {e}")
+        }
+    }
+
+    /// Opens the engine of a worker, making up to [`ENGINE_OPEN_ATTEMPTS`] attempts, and
+    /// returning the error of the last one.
+    fn open_engine(&self, id: usize) -> Result<Engine, EngineError> {
+        let mut attempt = 1;
+        loop {
+            match Engine::open_engine(id, &self.engine_config) {
+                Ok(engine) => return Ok(engine),
+                Err(e) if attempt < ENGINE_OPEN_ATTEMPTS => log::error!(
+                    "[{id}] Could not open the engine (attempt {attempt} of {ENGINE_OPEN_ATTEMPTS}): {e}"
+                ),
+                Err(e) => {
+                    log::error!("[{id}] Giving up on the engine, this worker stops: {e}");
+                    return Err(e);
+                }
+            }
+            std::thread::sleep(ENGINE_OPEN_RETRY_DELAY);
+            attempt += 1;
+        }
+    }
+
+    /// Searches for the number of workers maximizing throughput, up to `max_workers`.
+    ///
+    /// The worker count grows while each step improves throughput by at least
+    /// [`AUTO_TUNE_MIN_GAIN`]. When the first step does not, the count shrinks below the
+    /// starting one instead, while each step improves throughput the same way. The search then
+    /// settles once on the best count measured, so it cannot oscillate.
+    fn auto_tune(&self, max_workers: usize) {
+        let mut handles = vec![];
+        let initial = (max_workers / 4).max(1);
+        let Some(throughput) = self.measure_throughput(initial, &mut handles) else {
+            return;
+        };
+        let mut best = (initial, throughput);
+        let mut workers = initial;
+        while workers < max_workers {
+            workers = (workers * 3 / 2).max(workers + 1).min(max_workers);
+            let Some(throughput) = self.measure_throughput(workers, &mut handles) else {
+                return;
+            };
+            if throughput < best.1 * AUTO_TUNE_MIN_GAIN {
+                break;
+            }
+            best = (workers, throughput);
+        }
+        if best.0 == initial {
+            workers = initial;
+            while workers > 1 {
+                workers = workers * 2 / 3;
+                let Some(throughput) = self.measure_throughput(workers, &mut handles) else {
+                    return;
+                };
+                if throughput < best.1 * AUTO_TUNE_MIN_GAIN {
+                    break;
+                }
+                best = (workers, throughput);
+            }
+        }
+        self.resize(best.0, &mut handles);
+        log::info!(
+            "Auto-tune settled on {} workers ({:.2} positions/s)",
+            best.0,
+            best.1
+        );
+    }
+
+    /// Runs `workers` workers for [`AUTO_TUNE_WINDOW`], and returns the throughput they
+    /// reached, or `None` once every game is matched.
+    fn measure_throughput(&self, workers: usize, handles: &mut Vec<JoinHandle<()>>) -> Option<f64> {
+        self.resize(workers, handles);
+        let start = Instant::now();
+        let completed_positions = self.matching.completed_positions();
+        std::thread::sleep(AUTO_TUNE_WINDOW);
+        if self.matching.is_completed() {
+            return None;
+        }
+        let throughput = (self.matching.completed_positions() - completed_positions) as f64
+            / start.elapsed().as_secs_f64();
+        log::info!("Auto-tune: {workers} workers matched {throughput:.2} positions/s");
+        Some(throughput)
+    }
+
+    /// Sets the number of active workers, given the `handles` of the running ones by id.
+    ///
+    /// Workers above the count are waited for until their current game is matched, so that
+    /// they can be spawned again later.
+    fn resize(&self, workers: usize, handles: &mut Vec<JoinHandle<()>>) {
+        self.active_workers.store(workers, Ordering::Relaxed);
+        while handles.len() > workers {
+            let _ = handles.pop().unwrap().join();
+        }
+        for id in handles.len()..workers {
+            handles.push(self.spawn_worker(id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{game, match_config, moves, temp_path};
+
+    #[test]
+    fn deterministic_runs_save_identical_results() {
+        let games = (0..6)
+            .map(|i| game(1600 + 100 * i, 2000, moves(12 + i as usize)))
+            .collect::<Vec<_>>();
+        let results = ["deterministic_a", "deterministic_b"].map(|name| {
+            let config = MatchConfig {
+                deterministic: true,
+                threads: 4,
+                ..match_config(name, &games)
+            };
+            let path = format!("{}.csv", config.name);
+            move_matching_performance(config).unwrap();
+            std::fs::read(path).unwrap()
+        });
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn headless_runs_match_like_saved_runs() {
+        let games = (0..4)
+            .map(|i| game(1600 + 200 * i, 2000, moves(20)))
+            .collect::<Vec<_>>();
+        let mut headless = run_matching_headless(match_config("headless", &games))
+            .unwrap()
+            .matches;
+        headless.sort();
+        let config = match_config("saved", &games);
+        let path = format!("{}.csv", config.name);
+        move_matching_performance(config).unwrap();
+        assert_eq!(load_results(path).unwrap(), headless);
+    }
+
+    #[test]
+    fn sampled_games_are_logged_by_their_index_in_the_database() {
+        let games = (0..10)
+            .map(|i| game(1000 + 100 * i, 2000 + 100 * i, moves(12)))
+            .collect::<Vec<_>>();
+        let event_log = crate::test_utils::temp_path("sampled_events.jsonl");
+        let config = MatchConfig {
+            games_count: Some(3),
+            sample: Sampling::Random,
+            seed: 7,
+            event_log: Some(event_log.clone()),
+            ..match_config("sampled", &games)
+        };
+        let sample = sample_indices(games.len(), 3, 7);
+        assert_ne!(sample, vec![0, 1, 2]);
+        run_matching_headless(config).unwrap();
+        let records = event_log::read_event_log(event_log).unwrap();
+        let mut logged = records.iter().map(|r| r.game).collect::<Vec<_>>();
+        logged.dedup();
+        assert_eq!(logged, sample);
+        for record in records {
+            assert_eq!(record.elo, games[record.game].elo(record.color));
+        }
+    }
+
+    #[test]
+    fn segment_plots_cover_every_rating() {
+        let games = [game(1000, 3500, moves(12)), game(3500, 1000, moves(12))];
+        let config = MatchConfig {
+            segment_plots: Some(500),
+            ..match_config("segments", &games)
+        };
+        let name = config.name.clone();
+        move_matching_performance(config).unwrap();
+        for segment in ["900-1400", "3400-3700"] {
+            assert!(
+                Path::new(&format!("{name}_{segment}.png")).exists(),
+                "{segment}"
+            );
+        }
+    }
+
+    #[test]
+    fn dry_runs_write_nothing() {
+        let config = MatchConfig {
+            dry_run: true,
+            rating_distribution_csv: true,
+            ..match_config("dry_run", &[game(1800, 2000, moves(20))])
+        };
+        let name = config.name.clone();
+        move_matching_performance(config).unwrap();
+        for suffix in [
+            ".csv",
+            "_rating_distribution.png",
+            "_rating_distribution.csv",
+        ] {
+            assert!(!Path::new(&format!("{name}{suffix}")).exists(), "{suffix}");
+        }
+    }
+
+    #[test]
+    fn results_saved_with_other_ratings_are_detected() {
+        let games = [game(1800, 2000, moves(20))];
+        let config = match_config("ratings", &games);
+        let path = format!("{}.csv", config.name);
+        move_matching_performance(config).unwrap();
+        assert!(!is_rerated(&path, &ratings_checksum(&games)).unwrap());
+        assert!(is_rerated(&path, &ratings_checksum(&[game(1810, 2000, moves(20))])).unwrap());
+
+        // Results saved before ratings were, are assumed to have the same
+        let path = temp_path("unrated_checkpoint.csv");
+        save_results(
+            &path,
+            Performance {
+                name: "unrated",
+                matches: std::iter::empty(),
+            },
+            &[],
+        );
+        assert!(!is_rerated(path.to_str().unwrap(), "").unwrap());
+    }
+
+    #[test]
+    fn top_k_results_are_resumed_with_ratings_no_game_has_anymore() {
+        let config = |black_elo| MatchConfig {
+            matching: MatchingOptions {
+                top_k: 3,
+                ..MatchingOptions::default()
+            },
+            ..match_config("rerated", &[game(black_elo, 2000, moves(20))])
+        };
+        let path = format!("{}_top3.csv", config(1800).name);
+        move_matching_performance(config(1800)).unwrap();
+        let mut finished = load_results(&path).unwrap();
+        finished.sort();
+        // Rated again, the black player of the finished run has another rating
+        move_matching_performance(config(1810)).unwrap();
+        let mut resumed = load_results(&path).unwrap();
+        resumed.sort();
+        assert_eq!(resumed, [finished[0], (1810, 0, 0), finished[1]]);
+    }
+
+    #[test]
+    fn every_result_keeps_resumed_ratings_no_game_has_anymore() {
+        let config = |black_elo| MatchConfig {
+            matching: MatchingOptions {
+                split_colors: true,
+                by_player: true,
+                weighting: move_matching::Weighting::Length,
+                near_miss_distance: Some(2),
+                ..MatchingOptions::default()
+            },
+            ..match_config("rerated_all", &[game(black_elo, 2000, moves(20))])
+        };
+        let name = config(1800).name;
+        let results = |suffix: &str| {
+            let mut results = load_results(format!("{name}{suffix}.csv")).unwrap();
+            results.retain(|(elo, ..)| *elo != 1810);
+            results.sort();
+            results
+        };
+        let weighted = || {
+            let mut results = load_weighted_results(format!("{name}_weighted.csv")).unwrap();
+            results.retain(|(elo, ..)| *elo != 1810);
+            results.sort_by_key(|(elo, ..)| *elo);
+            results
+        };
+        let players = || {
+            let mut results = load_player_results(format!("{name}_players.csv")).unwrap();
+            results.sort();
+            results
+        };
+        let suffixes = ["", "_black", "_white", "_near"];
+        move_matching_performance(config(1800)).unwrap();
+        let finished = suffixes.map(results);
+        let finished_weighted = weighted();
+        let finished_players = players();
+
+        // Rated again, the black player of the finished run has another rating
+        move_matching_performance(config(1810)).unwrap();
+        assert_eq!(suffixes.map(results), finished);
+        assert_eq!(weighted(), finished_weighted);
+        assert_eq!(players(), finished_players);
+    }
+
+    #[test]
+    fn checkpoints_resume_from_the_games_completed_out_of_order() {
+        let games = (0..7)
+            .map(|i| game(1600 + 100 * i, 2000, moves(20)))
+            .collect::<Vec<_>>();
+        let config = match_config("out_of_order", &games);
+        let games: Arc<[Game]> = games.into();
+        let matching = resume_matching(&config, games.clone()).unwrap();
+        let mut engine = Engine::open_engine(0, &config.engine).unwrap();
+        // Workers are handed games 1, 3 and 4 but are stopped before completing them
+        let tasks = std::iter::from_fn(|| matching.get_next_task(0))
+            .take(6)
+            .collect::<Vec<_>>();
+        for mut task in tasks {
+            if [0, 2, 5].contains(&task.result().game_index) {
+                task.match_challenge(&mut engine).unwrap();
+            }
+        }
+        save_checkpoint(&config.name, config.results_format, &matching);
+
+        let resumed = resume_matching(&config, games).unwrap();
+        assert_eq!(
+            resumed.completed_game_indices().collect::<Vec<_>>(),
+            [0, 2, 5]
+        );
+        let results = |matching: &MoveMatching| {
+            let mut results = matching.snapshot().collect::<Vec<_>>();
+            results.sort();
+            results
+        };
+        assert_eq!(results(&resumed), results(&matching));
+        let matched = std::iter::from_fn(|| resumed.get_next_task(0))
+            .map(|task| task.result().game_index)
+            .collect::<Vec<_>>();
+        assert_eq!(matched, [1, 3, 4, 6]);
+    }
 }
@@ -1,9 +1,28 @@
-use std::path::PathBuf;
+use std::{io::IsTerminal, path::PathBuf, time::Duration};
 
-use clap::{command, Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
 use renju_move_matching::{
+    db::{
+        compile_database, export_database, load_database, ColorFilter, DbFilter, DrawPolicy,
+        PsqConfig, Sampling, SideFilter, WhrConfig,
+    },
+    event_log::{analyze, read_event_log, rescore, save_analysis, Aggregation},
+    interface::CheckpointConfig,
+    move_matching::{
+        MatchingOptions, Schedule, Weighting, DEFAULT_MOVE_BANDS, DEFAULT_SKIP_OPENING,
+    },
     move_matching_performance,
-    plot::{plot_results, Performance},
+    plot::{
+        bracketize, load_combined_results, load_move_results, load_results,
+        plot_accuracy_differences, plot_move_results, plot_results, save_combined_results,
+        save_results, Brackets, OutOfRange, Performance, PlotOptions, PlotStyle, ResultsFormat,
+    },
+    profile::EngineProfile,
+    protocol::{EngineConfig, Rule},
+    selftest::run_selftest_engine,
+    stats::{required_positions, two_proportion_z_test},
+    MatchConfig,
 };
 
 #[derive(Parser, Debug)]
@@ -13,6 +32,125 @@ struct Arguments {
     command: Command,
 }
 
+/// Hyperparameters of the ratings computed for players of a database.
+#[derive(Args, Debug)]
+struct WhrArgs {
+    /// Number of virtual draws stabilizing each player's rating. These pull ratings toward the
+    /// average, use 0 to get raw ratings.
+    #[arg(long, default_value_t = WhrConfig::default().virtual_games)]
+    virtual_games: u32,
+
+    /// Variance of the rating change of a player over a day. Higher values follow the progress
+    /// of players more closely, but make ratings noisier.
+    #[arg(long, default_value_t = WhrConfig::default().w2)]
+    w2: f64,
+
+    /// Added to every rating, shifting the whole rating distribution.
+    #[arg(long, default_value_t = WhrConfig::default().elo_offset)]
+    elo_offset: f64,
+}
+impl From<WhrArgs> for WhrConfig {
+    fn from(args: WhrArgs) -> Self {
+        Self {
+            virtual_games: args.virtual_games,
+            w2: args.w2,
+            elo_offset: args.elo_offset,
+            ..Default::default()
+        }
+    }
+}
+
+/// Selection of the games of a database.
+#[derive(Args, Debug)]
+struct FilterArgs {
+    /// Rules of the games to use, as numbered by the database (1 for Renju).
+    #[arg(long, value_delimiter = ',', default_value = "1")]
+    rules: Vec<u8>,
+
+    /// Also uses games that were not rated.
+    #[arg(long)]
+    include_unrated: bool,
+
+    /// Only uses games where both players are rated at least this much.
+    #[arg(long)]
+    min_elo: Option<u64>,
+
+    /// Only uses games where both players are rated at most this much.
+    #[arg(long)]
+    max_elo: Option<u64>,
+
+    /// Only uses games of tournaments that ended on or after this date (YYYY-MM-DD).
+    #[arg(long)]
+    from_date: Option<NaiveDate>,
+
+    /// Only uses games of tournaments that ended on or before this date (YYYY-MM-DD).
+    #[arg(long)]
+    to_date: Option<NaiveDate>,
+
+    /// Only uses games of these tournaments, by id in the database.
+    #[arg(long, value_delimiter = ',')]
+    tournaments: Option<Vec<usize>>,
+}
+impl From<FilterArgs> for DbFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            rules: args.rules,
+            include_unrated: args.include_unrated,
+            elo_range: (args.min_elo.is_some() || args.max_elo.is_some())
+                .then(|| args.min_elo.unwrap_or(0)..=args.max_elo.unwrap_or(u64::MAX)),
+            date_range: (args.from_date.is_some() || args.to_date.is_some()).then(|| {
+                args.from_date.unwrap_or(NaiveDate::MIN)..=args.to_date.unwrap_or(NaiveDate::MAX)
+            }),
+            tournaments: args.tournaments,
+        }
+    }
+}
+
+/// Brackets of ratings results are aggregated in.
+#[derive(Args, Debug)]
+struct BracketArgs {
+    /// Width of the rating brackets results are aggregated in.
+    #[arg(long, default_value_t = Brackets::default().width, value_parser = clap::value_parser!(u32).range(1..))]
+    bracket_width: u32,
+
+    /// Start of the first rating bracket.
+    #[arg(long, default_value_t = Brackets::default().min)]
+    bracket_min: u32,
+
+    /// End of the last rating bracket.
+    #[arg(long, default_value_t = Brackets::default().max)]
+    bracket_max: u32,
+
+    /// Counts ratings outside of the brackets in the first or last bracket, instead of
+    /// dropping them.
+    #[arg(long)]
+    clamp_ratings: bool,
+}
+impl From<BracketArgs> for Brackets {
+    fn from(args: BracketArgs) -> Self {
+        if args.bracket_max <= args.bracket_min {
+            Arguments::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--bracket-max must be greater than --bracket-min",
+                )
+                .exit()
+        }
+        Self {
+            width: args.bracket_width,
+            min: args.bracket_min,
+            max: args.bracket_max,
+            out_of_range: if args.clamp_ratings {
+                OutOfRange::Clamp
+            } else {
+                OutOfRange::Drop
+            },
+        }
+    }
+}
+
+// Parsed once, so that the size of `Match` does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 enum Command {
     Match {
@@ -23,11 +161,205 @@ enum Command {
         #[arg(short, long)]
         threads: Option<u32>,
 
+        /// Number of games in a row a worker can fail to match before it stops. The run fails
+        /// once every worker stopped.
+        #[arg(long, value_name = "N", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        max_consecutive_errors: u32,
+
+        /// Number of threads each engine searches with.
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        engine_threads: u32,
+
+        /// Memory each engine can use, in bytes (the engine decides by default).
+        #[arg(long, value_name = "BYTES")]
+        engine_memory: Option<u64>,
+
+        /// Seconds each engine is given to acknowledge START, for engines loading large
+        /// weights or opening books.
+        #[arg(long, value_name = "SECS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        start_timeout: u64,
+
+        /// Directory engines are started in, for engines loading files relative to it.
+        #[arg(long, value_name = "DIR")]
+        engine_dir: Option<PathBuf>,
+
+        /// Sets an environment variable for engines, for instance to select a GPU. Can be
+        /// repeated.
+        #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        engine_env: Vec<(String, String)>,
+
         #[arg(short, long)]
         games: Option<usize>,
 
+        /// How the games are picked with `--games`: the first ones (head), a random subset
+        /// (random), or random games balanced between rating brackets (stratified).
+        #[arg(long, default_value = "head", requires = "games")]
+        sample: Sampling,
+
+        /// Shorthand for `--sample stratified`.
+        #[arg(long, requires = "games", conflicts_with = "sample")]
+        stratified: bool,
+
+        /// Seed of the random sampling of games, to pick the same games again.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
         #[arg(short, long)]
         move_time: Option<u32>,
+
+        /// Rule the engine plays with: freestyle, standard or renju.
+        #[arg(short, long, default_value = "renju")]
+        rule: Rule,
+
+        /// Number of rows and columns of the board.
+        #[arg(long, default_value_t = 15, value_parser = clap::value_parser!(u8).range(1..))]
+        board_size: u8,
+
+        /// Engine profile: `yixin`, `gomocup` or the path to a profile file.
+        #[arg(long, default_value = "yixin")]
+        engine_profile: String,
+
+        /// Tunes the number of workers to maximize throughput, using at most `--threads`.
+        #[arg(long)]
+        auto_tune: bool,
+
+        /// Appends the results of each game to this CSV file as soon as it completes.
+        #[arg(long)]
+        game_log: Option<PathBuf>,
+
+        /// Records every position with the human and engine moves to this JSON lines file.
+        #[arg(long, visible_alias = "detailed-log")]
+        event_log: Option<PathBuf>,
+
+        /// Only matches the moves of these players: all, winner or loser.
+        #[arg(long, default_value = "all")]
+        sides: SideFilter,
+
+        /// How draws are handled when selecting players by result: include-both, exclude,
+        /// count-as-black or count-as-white.
+        #[arg(long, default_value = "include-both")]
+        draws: DrawPolicy,
+
+        /// Only matches the moves of players of this color: both, black or white.
+        #[arg(long, default_value = "both")]
+        color: ColorFilter,
+
+        #[command(flatten)]
+        whr: WhrArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        #[command(flatten)]
+        brackets: BracketArgs,
+
+        /// Rating of the players of Piskvork (`.psq`) games.
+        #[arg(long, default_value_t = PsqConfig::default().default_elo)]
+        psq_elo: u64,
+
+        /// CSV file of `file,black_elo,white_elo` rows rating the players of each Piskvork game.
+        #[arg(long)]
+        psq_ratings: Option<PathBuf>,
+
+        /// Asks the engine for its K best moves, reporting top-1 to top-K accuracies.
+        #[arg(long, value_name = "K", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+        top_k: u8,
+
+        /// Also counts moves equivalent to the human move by a symmetry of the position as matches.
+        #[arg(long)]
+        symmetry: bool,
+
+        /// Also reports the results of black and white players separately.
+        #[arg(long)]
+        split_colors: bool,
+
+        /// Also reports the results of each player, by id in the database.
+        #[arg(long)]
+        by_player: bool,
+
+        /// Updates the board of engines with TURN instead of sending it whole when they played
+        /// the human move at the previous position of their side, for engines keeping state
+        /// between moves. Only applies when the moves of a single side are matched.
+        #[arg(long, conflicts_with = "top_k")]
+        incremental: bool,
+
+        /// Clears the hash of engines before every N-th position of each game, so that positions
+        /// do not influence each other.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        clear_hash_every: Option<u32>,
+
+        /// Stops matching a game once it took this many seconds, keeping the positions matched
+        /// so far.
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+        game_time_budget: Option<u64>,
+
+        /// Also counts positions where the engine played at most this many points away from
+        /// the human move, in any direction, as near misses.
+        #[arg(long, value_name = "DISTANCE", value_parser = clap::value_parser!(u8).range(1..))]
+        near_miss_distance: Option<u8>,
+
+        /// Number of opening moves of each game that are not matched. With 0, engines are
+        /// also asked for the first move of games on the empty board.
+        #[arg(long, value_name = "MOVES", default_value_t = DEFAULT_SKIP_OPENING)]
+        skip_opening: usize,
+
+        /// Order in which games are matched: sequential, in database order, or ljf, longest
+        /// games first, for workers to finish closer together.
+        #[arg(long, default_value = "sequential")]
+        schedule: Schedule,
+
+        /// Sends RESTART to engines before each game, for engines keeping state between games.
+        #[arg(long)]
+        reset_between_games: bool,
+
+        /// Minimizes run-to-run variance at the expense of throughput, matching games in order
+        /// with a single worker.
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Also saves results where positions are weighted by their game to
+        /// `<NAME>_weighted.csv`: none, rating-gap or length.
+        #[arg(long, default_value = "none")]
+        weighting: Weighting,
+
+        /// First move number of each band of moves results are also aggregated in, saved to
+        /// `<NAME>_moves.csv`.
+        #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_MOVE_BANDS, value_parser = clap::value_parser!(u32).range(1..))]
+        move_bands: Vec<u32>,
+
+        /// Also plots results for each band of this many rating points, to `<NAME>_<FROM>-<TO>.png`.
+        #[arg(long, value_name = "WIDTH", value_parser = clap::value_parser!(u32).range(1..))]
+        segment_plots: Option<u32>,
+
+        /// Also saves the number of players of each rating bracket to
+        /// `<NAME>_rating_distribution.csv`.
+        #[arg(long)]
+        rating_distribution_csv: bool,
+
+        /// Format results are saved and resumed in: csv or json.
+        #[arg(long, default_value = "csv")]
+        format: ResultsFormat,
+
+        /// Saves a checkpoint every this many seconds.
+        #[arg(long, default_value_t = 900, value_parser = clap::value_parser!(u64).range(1..))]
+        checkpoint_secs: u64,
+
+        /// Also saves a checkpoint every time this many more positions are matched.
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        checkpoint_positions: Option<u64>,
+
+        /// Caches the games of XML databases and their ratings to this file, so that later runs
+        /// neither parse nor rate them again until the database or its settings change.
+        #[arg(long, value_name = "PATH")]
+        rating_cache: Option<PathBuf>,
+
+        /// Only checks the database and the engine on a single position, without matching.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Logs progress instead of displaying the TUI, for runs without a terminal.
+        #[arg(long)]
+        no_tui: bool,
     },
     Plot {
         output_path: PathBuf,
@@ -37,50 +369,617 @@ enum Command {
 
         #[arg(short, long, num_args = 1..)]
         perfs: Vec<PathBuf>,
+
+        /// Plots results by move number, saved to `<NAME>_moves.csv` by `match`, instead of
+        /// results by rating.
+        #[arg(long, conflicts_with_all = ["combined", "save_combined"])]
+        by_move: bool,
+
+        /// Also plots the experiments of a file written with `--save-combined`.
+        #[arg(long)]
+        combined: Option<PathBuf>,
+
+        /// Saves the results of all plotted experiments to this single CSV file.
+        #[arg(long)]
+        save_combined: Option<PathBuf>,
+
+        #[command(flatten)]
+        brackets: BracketArgs,
+
+        /// Draws the 95% confidence interval of the accuracy of each bracket.
+        #[arg(long)]
+        error_bars: bool,
+
+        /// Writes the number of positions of each bracket next to its point.
+        #[arg(long)]
+        show_counts: bool,
+
+        /// Width of the image, in pixels.
+        #[arg(long, default_value_t = PlotStyle::default().width, value_parser = clap::value_parser!(u32).range(1..))]
+        width: u32,
+
+        /// Height of the image, in pixels.
+        #[arg(long, default_value_t = PlotStyle::default().height, value_parser = clap::value_parser!(u32).range(1..))]
+        height: u32,
+    },
+    /// Plots the difference of accuracy between two experiments in each rating bracket, where
+    /// regressions of the second one show as negative bars.
+    Diff {
+        /// Image the plot is saved to, as SVG if it ends with `.svg` and PNG otherwise.
+        output_path: PathBuf,
+
+        /// Results of the experiment compared against.
+        a: PathBuf,
+
+        /// Results of the experiment compared.
+        b: PathBuf,
+
+        /// Marks brackets where the difference is significant at this level with a star.
+        #[arg(long)]
+        alpha: Option<f64>,
+
+        #[command(flatten)]
+        brackets: BracketArgs,
+
+        /// Width of the image, in pixels.
+        #[arg(long, default_value_t = PlotStyle::default().width, value_parser = clap::value_parser!(u32).range(1..))]
+        width: u32,
+
+        /// Height of the image, in pixels.
+        #[arg(long, default_value_t = PlotStyle::default().height, value_parser = clap::value_parser!(u32).range(1..))]
+        height: u32,
+    },
+    /// Tests whether the difference of accuracy between two experiments is significant, overall
+    /// and in each rating bracket, with a two-proportion z-test.
+    Compare {
+        /// Results of the experiment compared against.
+        a: PathBuf,
+
+        /// Results of the experiment compared.
+        b: PathBuf,
+
+        /// Significance level of the test.
+        #[arg(long, default_value_t = 0.05)]
+        alpha: f64,
+
+        #[command(flatten)]
+        brackets: BracketArgs,
+    },
+    /// Parses a database and computes its ratings once, writing the games to a compact binary
+    /// file that can be used as the database of the `match` subcommand.
+    Compile {
+        xml_in: PathBuf,
+        bin_out: PathBuf,
+
+        #[command(flatten)]
+        whr: WhrArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Parses a database and computes its ratings once, writing the games to a JSON lines file
+    /// that can be used as the database of the `match` subcommand, or read by other tools.
+    Export {
+        xml_in: PathBuf,
+        /// Path of the exported games, which ends with `.jsonl` to be loaded by `match`.
+        jsonl_out: PathBuf,
+
+        #[command(flatten)]
+        whr: WhrArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Recomputes results from the event log of a previous run, without running an engine.
+    Rescore {
+        event_log: PathBuf,
+        output_path: PathBuf,
     },
+    /// Computes the accuracy of the positions of an event log grouped by rating, move number
+    /// or color, without running an engine.
+    Analyze {
+        event_log: PathBuf,
+        output_path: PathBuf,
+
+        /// Groups positions by each combination of these: rating, move or color. Without it,
+        /// the overall accuracy is computed.
+        #[arg(long, value_delimiter = ',')]
+        by: Vec<Aggregation>,
+
+        /// First move number of each band of moves positions are grouped in by `move`.
+        #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_MOVE_BANDS, value_parser = clap::value_parser!(u32).range(1..))]
+        move_bands: Vec<u32>,
+
+        /// Format the results are saved in: csv or json.
+        #[arg(long, default_value = "csv")]
+        format: ResultsFormat,
+
+        #[command(flatten)]
+        brackets: BracketArgs,
+    },
+    /// Estimates how many positions each rating bracket needs to detect a difference of
+    /// accuracy between two engines, and compares it to the positions of the database.
+    Power {
+        database_path: PathBuf,
+
+        /// Smallest difference of accuracy to detect, in percentage points.
+        #[arg(long)]
+        effect: f64,
+
+        /// Expected accuracy of the engines, in percent. 50 is the most conservative.
+        #[arg(long, default_value_t = 50., value_parser = parse_percentage)]
+        baseline: f64,
+
+        /// Significance level of the test.
+        #[arg(long, default_value_t = 0.05, value_parser = parse_probability)]
+        alpha: f64,
+
+        /// Probability of detecting the difference when there is one.
+        #[arg(long, default_value_t = 0.8, value_parser = parse_probability)]
+        power: f64,
+
+        /// Only counts the moves of these players: all, winner or loser.
+        #[arg(long, default_value = "all")]
+        sides: SideFilter,
+
+        /// How draws are handled when selecting players by result.
+        #[arg(long, default_value = "include-both")]
+        draws: DrawPolicy,
+
+        /// Only counts the moves of players of this color: both, black or white.
+        #[arg(long, default_value = "both")]
+        color: ColorFilter,
+
+        #[command(flatten)]
+        whr: WhrArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        #[command(flatten)]
+        brackets: BracketArgs,
+    },
+    /// Runs a trivial built-in engine playing the first empty point, to test a setup
+    /// without a real engine.
+    SelftestEngine,
 }
 
-fn main() {
-    env_logger::init();
+/// Parses a `KEY=VALUE` environment variable.
+fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!(
+            "invalid environment variable `{s}`, expected KEY=VALUE"
+        )),
+    }
+}
+
+/// Parses a percentage, from 0 to 100.
+fn parse_percentage(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(percentage) if (0. ..=100.).contains(&percentage) => Ok(percentage),
+        _ => Err(format!(
+            "invalid percentage `{s}`, expected a number from 0 to 100"
+        )),
+    }
+}
+
+/// Parses a probability strictly between 0 and 1.
+fn parse_probability(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(probability) if probability > 0. && probability < 1. => Ok(probability),
+        _ => Err(format!(
+            "invalid probability `{s}`, expected a number between 0 and 1, excluded"
+        )),
+    }
+}
+
+/// Reports an error about a file and exits.
+fn exit_with_error(path: &std::path::Path, error: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {}: {error}", path.display());
+    std::process::exit(1)
+}
 
+fn main() {
     let args = Arguments::parse();
+    // Without the TUI, progress is only shown by logs, unless a progress bar is drawn instead.
+    let default_filter = match args.command {
+        Command::Match { no_tui, .. } if no_tui || !std::io::stdout().is_terminal() => {
+            if cfg!(feature = "progress-bar") && std::io::stderr().is_terminal() {
+                "warn"
+            } else {
+                "info"
+            }
+        }
+        _ => "error",
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_filter))
+        .init();
+
     match args.command {
         Command::Plot {
             output_path,
             names,
             perfs,
+            by_move,
+            combined,
+            save_combined,
+            brackets,
+            error_bars,
+            show_counts,
+            width,
+            height,
         } => {
             if names.len() != perfs.len() {
                 panic!()
             }
-            let perfs = names.iter().zip(perfs.iter()).map(|(name, perf_path)| {
-                let csv = csv::Reader::from_path(&perf_path)
-                    .unwrap()
-                    .into_deserialize();
-                Performance {
+            let mut experiments = names
+                .into_iter()
+                .zip(perfs.iter())
+                .map(|(name, perf_path)| {
+                    let results = if by_move {
+                        load_move_results(perf_path)
+                    } else {
+                        load_results(perf_path)
+                    }
+                    .unwrap_or_else(|e| exit_with_error(perf_path, e));
+                    (name, results)
+                })
+                .collect::<Vec<_>>();
+            if let Some(combined) = combined {
+                experiments.extend(
+                    load_combined_results(&combined)
+                        .unwrap_or_else(|e| exit_with_error(&combined, e)),
+                );
+            }
+            let perfs = || {
+                experiments.iter().map(|(name, results)| Performance {
                     name,
-                    matches: csv.filter_map(|e| e.ok()),
-                }
-            });
-            plot_results(output_path, perfs)
+                    matches: results.iter().copied(),
+                })
+            };
+            if let Some(save_combined) = save_combined {
+                save_combined_results(save_combined, perfs());
+            }
+            let options = PlotOptions {
+                error_bars,
+                show_counts,
+                style: PlotStyle {
+                    width,
+                    height,
+                    ..Default::default()
+                },
+            };
+            if by_move {
+                plot_move_results(output_path, perfs(), &options)
+            } else {
+                plot_results(output_path, perfs(), &brackets.into(), &options)
+            }
+        }
+        Command::Diff {
+            output_path,
+            a,
+            b,
+            alpha,
+            brackets,
+            width,
+            height,
+        } => {
+            let load = |path: &PathBuf| {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                let results = load_results(path).unwrap_or_else(|e| exit_with_error(path, e));
+                (name.to_string(), results)
+            };
+            let ((name_a, results_a), (name_b, results_b)) = (load(&a), load(&b));
+            plot_accuracy_differences(
+                output_path,
+                Performance {
+                    name: &name_a,
+                    matches: results_a.into_iter(),
+                },
+                Performance {
+                    name: &name_b,
+                    matches: results_b.into_iter(),
+                },
+                &brackets.into(),
+                alpha,
+                &PlotStyle {
+                    width,
+                    height,
+                    ..Default::default()
+                },
+            )
+        }
+        Command::Compare {
+            a,
+            b,
+            alpha,
+            brackets,
+        } => {
+            let results_a = load_results(&a).unwrap_or_else(|e| exit_with_error(&a, e));
+            let results_b = load_results(&b).unwrap_or_else(|e| exit_with_error(&b, e));
+            let overall = |results: &[(u64, u32, u32)]| {
+                results.iter().fold((0, 0), |(m, t), (_, matches, total)| {
+                    (m + matches, t + total)
+                })
+            };
+            let (overall_a, overall_b) = (overall(&results_a), overall(&results_b));
+            let accuracy = |(matches, total): (u32, u32)| matches as f64 / total as f64 * 100.;
+            match two_proportion_z_test(overall_a, overall_b) {
+                Some((z, p_value)) => println!(
+                    "Overall accuracy: {:.2}% then {:.2}% ({:+.2} points), z = {z:.3}, p = {p_value:.4}: {}",
+                    accuracy(overall_a),
+                    accuracy(overall_b),
+                    accuracy(overall_b) - accuracy(overall_a),
+                    if p_value < alpha {
+                        "significant"
+                    } else {
+                        "not significant"
+                    }
+                ),
+                None => println!("The overall accuracies cannot be compared"),
+            }
+            let brackets = Brackets::from(brackets);
+            println!(
+                "{:>9} {:>10} {:>10} {:>10} {:>8} {:>12}",
+                "rating", "positions", "difference", "z", "p", "significant"
+            );
+            let bracketized_a = bracketize(results_a.into_iter(), &brackets);
+            let bracketized_b = bracketize(results_b.into_iter(), &brackets);
+            for ((bracket, matches_a, total_a), (_, matches_b, total_b)) in
+                bracketized_a.into_iter().zip(bracketized_b)
+            {
+                let Some((z, p_value)) =
+                    two_proportion_z_test((matches_a, total_a), (matches_b, total_b))
+                else {
+                    continue;
+                };
+                let end = (bracket + brackets.width).min(brackets.max);
+                println!(
+                    "{:>9} {:>10} {:>+10.2} {z:>10.3} {p_value:>8.4} {:>12}",
+                    format!("{bracket}-{end}"),
+                    total_a + total_b,
+                    accuracy((matches_b, total_b)) - accuracy((matches_a, total_a)),
+                    if p_value < alpha { "yes" } else { "no" }
+                );
+            }
         }
         Command::Match {
             name,
             engine_command,
             database_path,
             threads,
+            max_consecutive_errors,
+            engine_threads,
+            engine_memory,
+            start_timeout,
+            engine_dir,
+            engine_env,
             games,
+            sample,
+            stratified,
+            seed,
             move_time,
+            rule,
+            board_size,
+            engine_profile,
+            auto_tune,
+            game_log,
+            event_log,
+            sides,
+            draws,
+            color,
+            whr,
+            filter,
+            brackets,
+            psq_elo,
+            psq_ratings,
+            top_k,
+            symmetry,
+            split_colors,
+            by_player,
+            incremental,
+            reset_between_games,
+            clear_hash_every,
+            game_time_budget,
+            near_miss_distance,
+            skip_opening,
+            schedule,
+            deterministic,
+            weighting,
+            move_bands,
+            segment_plots,
+            rating_distribution_csv,
+            format,
+            checkpoint_secs,
+            checkpoint_positions,
+            rating_cache,
+            dry_run,
+            no_tui,
         } => {
-            move_matching_performance(
-                &name,
-                &engine_command,
+            let threads = threads.unwrap_or(1);
+            if let Ok(cores) = std::thread::available_parallelism() {
+                if !deterministic && (threads * engine_threads) as usize > cores.get() {
+                    eprintln!(
+                        "Warning: {threads} engines of {engine_threads} threads each exceed the {cores} available cores, which slows them down"
+                    )
+                }
+            }
+            let profile = EngineProfile::from_name_or_path(&engine_profile).unwrap_or_else(|e| {
+                exit_with_error(
+                    std::path::Path::new(&engine_profile),
+                    format!("could not load the engine profile: {e}"),
+                )
+            });
+            if let Err(e) = move_matching_performance(MatchConfig {
+                name,
+                engine: EngineConfig {
+                    command: engine_command,
+                    move_time: move_time.unwrap_or(5000),
+                    rule,
+                    board_size,
+                    profile,
+                    threads: engine_threads,
+                    max_memory: engine_memory,
+                    deterministic: false,
+                    start_timeout: Duration::from_secs(start_timeout),
+                    workdir: engine_dir,
+                    env: engine_env,
+                },
                 database_path,
-                threads.unwrap_or(1),
-                games,
-                move_time.unwrap_or(5000),
+                threads,
+                max_consecutive_errors,
+                games_count: games,
+                sample: if stratified {
+                    Sampling::Stratified
+                } else {
+                    sample
+                },
+                seed,
+                auto_tune,
+                game_log,
+                event_log,
+                matching: MatchingOptions {
+                    sides,
+                    draws,
+                    colors: color,
+                    clear_hash_every,
+                    top_k,
+                    symmetry,
+                    split_colors,
+                    by_player,
+                    incremental,
+                    weighting,
+                    reset_between_games,
+                    game_time_budget: game_time_budget.map(Duration::from_secs),
+                    near_miss_distance,
+                    skip_opening,
+                    schedule,
+                    brackets: brackets.into(),
+                },
+                move_bands,
+                whr: whr.into(),
+                filter: filter.into(),
+                rating_cache,
+                psq: PsqConfig {
+                    default_elo: psq_elo,
+                    ratings: psq_ratings,
+                },
+                deterministic,
+                segment_plots,
+                rating_distribution_csv,
+                results_format: format,
+                checkpoints: CheckpointConfig {
+                    interval: Duration::from_secs(checkpoint_secs),
+                    positions: checkpoint_positions,
+                },
+                dry_run,
+                no_tui,
+            }) {
+                eprintln!("Error: {e}");
+                std::process::exit(1)
+            }
+        }
+        Command::Compile {
+            xml_in,
+            bin_out,
+            whr,
+            filter,
+        } => {
+            let games = load_database(&xml_in, &whr.into(), &filter.into())
+                .unwrap_or_else(|e| exit_with_error(&xml_in, e));
+            compile_database(&games, bin_out).unwrap()
+        }
+        Command::Export {
+            xml_in,
+            jsonl_out,
+            whr,
+            filter,
+        } => {
+            let games = load_database(&xml_in, &whr.into(), &filter.into())
+                .unwrap_or_else(|e| exit_with_error(&xml_in, e));
+            export_database(&games, jsonl_out).unwrap()
+        }
+        Command::Rescore {
+            event_log,
+            output_path,
+        } => {
+            let records =
+                read_event_log(&event_log).unwrap_or_else(|e| exit_with_error(&event_log, e));
+            save_results(
+                output_path,
+                Performance {
+                    name: "",
+                    matches: rescore(&records).into_iter(),
+                },
+                &[],
             )
-            .unwrap();
+        }
+        Command::Analyze {
+            event_log,
+            output_path,
+            by,
+            mut move_bands,
+            format,
+            brackets,
+        } => {
+            let records =
+                read_event_log(&event_log).unwrap_or_else(|e| exit_with_error(&event_log, e));
+            move_bands.sort_unstable();
+            move_bands.dedup();
+            let groups = analyze(&records, &by, &brackets.into(), &move_bands);
+            save_analysis(&output_path, &by, &groups, format)
+                .unwrap_or_else(|e| exit_with_error(&output_path, e));
+        }
+        Command::Power {
+            database_path,
+            effect,
+            baseline,
+            alpha,
+            power,
+            sides,
+            draws,
+            color,
+            whr,
+            filter,
+            brackets,
+        } => {
+            let Some(required) = required_positions(baseline / 100., effect / 100., alpha, power)
+            else {
+                Arguments::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "--effect must change the accuracy of {baseline}%, between 0 and 100%"
+                        ),
+                    )
+                    .exit()
+            };
+            let games = load_database(&database_path, &whr.into(), &filter.into())
+                .unwrap_or_else(|e| exit_with_error(&database_path, e));
+            let options = MatchingOptions {
+                sides,
+                draws,
+                colors: color,
+                brackets: brackets.into(),
+                ..Default::default()
+            };
+            println!(
+                "Detecting a difference of {effect} points from {baseline}% (alpha = {alpha}, power = {power}) requires {required} positions per bracket"
+            );
+            println!("{:>9} {:>10} {:>10}", "rating", "available", "enough");
+            for (i, available) in options.positions_per_bracket(&games).iter().enumerate() {
+                let bracket = options.brackets.start(i);
+                let end = (bracket + options.brackets.width).min(options.brackets.max);
+                println!(
+                    "{:>9} {available:>10} {:>10}",
+                    format!("{bracket}-{end}"),
+                    if *available >= required { "yes" } else { "no" }
+                );
+            }
+        }
+        Command::SelftestEngine => {
+            run_selftest_engine(std::io::stdin().lock(), std::io::stdout().lock()).unwrap()
         }
     }
 }
@@ -1,26 +1,423 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    fs::File,
+    ops::Range,
     path::Path,
-    sync::atomic::{AtomicU32, AtomicU64, AtomicUsize},
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+    time::{Duration, Instant},
 };
 
 use crate::{
-    db::Game,
-    protocol::{Command, Engine, EngineError, Response},
+    db::{Color, ColorFilter, DrawPolicy, Game, SideFilter},
+    event_log::{EventLog, PositionRecord},
+    plot::{
+        bracket_accuracies, bracketize, load_metadata, load_results, weighted_bracket_accuracies,
+        Brackets, ResultsError,
+    },
+    protocol::{Command, Engine, EngineError, EngineInfo, Response},
 };
 
+/// A named `(x, y)` series computed from the matching state, which the interface can draw.
+#[derive(Debug, Clone)]
+pub struct Metric {
+    pub name: String,
+    pub x_label: &'static str,
+    pub y_label: &'static str,
+    /// Named series of points, the first one being computed from all positions.
+    pub series: Vec<(&'static str, Vec<(f64, f64)>)>,
+}
+
+/// How much the positions of a game count in weighted results, compared to those of others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Weighting {
+    /// Positions are not weighted, and no weighted results are kept.
+    #[default]
+    None,
+    /// Positions of games between players of close ratings count more, by the chances of an
+    /// upset: games between equal players count fully, while lopsided ones barely count.
+    RatingGap,
+    /// Positions of short games, often decided by an early blunder, count less: games count
+    /// fully from [`FULL_WEIGHT_MOVES`] moves on.
+    Length,
+}
+/// Number of moves from which games count fully with [`Weighting::Length`].
+pub const FULL_WEIGHT_MOVES: usize = 40;
+impl Weighting {
+    /// Weight of the positions of the game, between 0 and 1.
+    pub fn weight(self, game: &Game) -> f64 {
+        match self {
+            Self::None => 1.,
+            Self::RatingGap => {
+                let gap = game.black_elo as f64 - game.white_elo as f64;
+                let expected_score = 1. / (1. + 10f64.powf(-gap / 400.));
+                4. * expected_score * (1. - expected_score)
+            }
+            Self::Length => (game.moves.len() as f64 / FULL_WEIGHT_MOVES as f64).min(1.),
+        }
+    }
+}
+impl std::str::FromStr for Weighting {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "rating-gap" => Ok(Self::RatingGap),
+            "length" => Ok(Self::Length),
+            _ => Err(format!(
+                "unknown weighting `{s}`, expected none, rating-gap or length"
+            )),
+        }
+    }
+}
+
+/// Order in which games are handed out to workers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Schedule {
+    /// Games are matched in database order, so that runs are reproducible.
+    #[default]
+    Sequential,
+    /// Games with the most positions to match are matched first (longest job first), so that
+    /// workers do not end up waiting on a few long games at the end of a run.
+    LongestFirst,
+}
+impl std::str::FromStr for Schedule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sequential" => Ok(Self::Sequential),
+            "ljf" => Ok(Self::LongestFirst),
+            _ => Err(format!(
+                "unknown schedule `{s}`, expected sequential or ljf"
+            )),
+        }
+    }
+}
+
+/// A float that can be added to from several threads, stored as the bits of an [`AtomicU64`].
+#[derive(Debug, Default)]
+struct AtomicF64(AtomicU64);
+impl AtomicF64 {
+    fn new(value: f64) -> Self {
+        Self(AtomicU64::new(value.to_bits()))
+    }
+
+    fn load(&self) -> f64 {
+        f64::from_bits(self.0.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    fn fetch_add(&self, value: f64) {
+        let _ = self.0.fetch_update(
+            std::sync::atomic::Ordering::Relaxed,
+            std::sync::atomic::Ordering::Relaxed,
+            |bits| Some((f64::from_bits(bits) + value).to_bits()),
+        );
+    }
+}
+
+/// Sum of the matches of results over the sum of their totals, or `None` without positions.
+fn accuracy(results: impl Iterator<Item = (u64, u32, u32)>) -> Option<f64> {
+    let (matches, total) = results.fold((0u64, 0u64), |(m, t), (_, matches, total)| {
+        (m + matches as u64, t + total as u64)
+    });
+    (total > 0).then(|| matches as f64 / total as f64)
+}
+
+/// Options selecting which positions are matched, and how.
+#[derive(Debug, Clone, Copy)]
+pub struct MatchingOptions {
+    /// Only matches the moves of players selected by this filter.
+    pub sides: SideFilter,
+    /// How drawn games are handled by `sides`.
+    pub draws: DrawPolicy,
+    /// Only matches the moves of players of this color.
+    pub colors: ColorFilter,
+    /// Clears the engine's hash before every `n`-th position of each game, starting with its
+    /// first one, so that positions do not influence each other.
+    pub clear_hash_every: Option<u32>,
+    /// Number of best moves asked to the engine. Positions where the human move is among the
+    /// `k` best are counted for every `k` up to this.
+    pub top_k: u8,
+    /// Also counts engine moves equivalent to the human move under a symmetry of the position
+    /// as matches.
+    pub symmetry: bool,
+    /// Also keeps the results of black and white players apart.
+    pub split_colors: bool,
+    /// Also keeps the results of each player, by id in the database.
+    pub by_player: bool,
+    /// Updates the board of the engine with `TURN` instead of sending it whole when it played
+    /// the human move at the previous position of the same side, for engines keeping state
+    /// between moves.
+    pub incremental: bool,
+    /// Also keeps results where positions are weighted by their game.
+    pub weighting: Weighting,
+    /// Restarts the engine before each game, for engines keeping state between games.
+    pub reset_between_games: bool,
+    /// Stops matching a game once it took this long, keeping the positions matched so far. The
+    /// game is still completed, so resuming relies on
+    /// [`MoveMatching::restore_completed_games`].
+    pub game_time_budget: Option<Duration>,
+    /// Also counts positions where the engine played at most this far from the human move, by
+    /// Chebyshev distance, so that a move on a neighboring point is a near miss.
+    pub near_miss_distance: Option<u8>,
+    /// Number of opening moves of each game that are not matched.
+    pub skip_opening: usize,
+    /// Order in which games are matched.
+    pub schedule: Schedule,
+    /// Brackets of ratings results are displayed in.
+    pub brackets: Brackets,
+}
+impl Default for MatchingOptions {
+    fn default() -> Self {
+        Self {
+            sides: SideFilter::default(),
+            draws: DrawPolicy::default(),
+            colors: ColorFilter::default(),
+            clear_hash_every: None,
+            top_k: 1,
+            symmetry: false,
+            split_colors: false,
+            by_player: false,
+            incremental: false,
+            weighting: Weighting::default(),
+            reset_between_games: false,
+            game_time_budget: None,
+            near_miss_distance: None,
+            skip_opening: DEFAULT_SKIP_OPENING,
+            schedule: Schedule::default(),
+            brackets: Brackets::default(),
+        }
+    }
+}
+/// Number of opening moves of each game that are not matched by default. These fixed opening
+/// moves say little about the strength of players.
+pub const DEFAULT_SKIP_OPENING: usize = 5;
+/// Number of moves at the end of a game that are not matched, since they are forced.
+const UNMATCHED_LAST_PLIES: usize = 2;
+
+impl MatchingOptions {
+    /// Indices of the moves of the game that can be matched, the single source of truth for
+    /// counting positions and iterating over them.
+    fn matchable_plies(&self, game: &Game) -> Range<usize> {
+        self.skip_opening..game.moves.len().saturating_sub(UNMATCHED_LAST_PLIES)
+    }
+
+    /// Whether the move at index `ply` of the game is matched.
+    fn is_matched(&self, game: &Game, ply: usize) -> bool {
+        let color = Color::of_ply(ply);
+        self.colors.includes(color) && self.sides.includes(game, color, self.draws)
+    }
+
+    /// Indices of the games in the order they are matched.
+    fn dispatch_order(&self, games: &[Game]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..games.len()).collect();
+        if self.schedule == Schedule::LongestFirst {
+            // Stable, so that games of the same length keep their database order.
+            order.sort_by_key(|&i| std::cmp::Reverse(self.matched_positions(&games[i])));
+        }
+        order
+    }
+
+    /// Indices of the moves of the game that are matched, in order.
+    fn matched_plies<'a>(&'a self, game: &'a Game) -> impl Iterator<Item = usize> + 'a {
+        self.matchable_plies(game)
+            .filter(|ply| self.is_matched(game, *ply))
+    }
+
+    /// Number of positions of the game that are matched.
+    fn matched_positions(&self, game: &Game) -> u64 {
+        self.matched_plies(game).count() as u64
+    }
+
+    /// Index of the first move of the game that is matched, if any.
+    pub fn first_matched_ply(&self, game: &Game) -> Option<usize> {
+        self.matched_plies(game).next()
+    }
+
+    /// Number of matched positions in each of the `brackets`.
+    pub fn positions_per_bracket(&self, games: &[Game]) -> Vec<u64> {
+        let mut positions = vec![0; self.brackets.count()];
+        for game in games {
+            for ply in self.matched_plies(game) {
+                if let Some(i) = self.brackets.index(game.elo(Color::of_ply(ply))) {
+                    positions[i] += 1
+                }
+            }
+        }
+        positions
+    }
+}
+
+/// Number of recent engine moves considered to detect an engine stuck on a single move.
+const DIVERSITY_WINDOW: usize = 200;
+/// Fraction of the recent positions answered with the same move above which the engine is
+/// considered stuck. Distinct positions rarely share the same best move, let alone this often.
+const STUCK_FRACTION: f64 = 0.5;
+
+/// First move number of each band of moves results are also aggregated in, by default: the
+/// opening, the middlegame and the endgame.
+pub const DEFAULT_MOVE_BANDS: [u32; 3] = [6, 16, 31];
+
+/// Number of times a game is matched before keeping the results of a failed attempt.
+const MAX_GAME_ATTEMPTS: u32 = 3;
+
+/// Time between two checks of whether matching was resumed, when paused.
+const PAUSE_POLL_PERIOD: Duration = Duration::from_millis(100);
+
+/// What a worker is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkerState {
+    /// Not matching a game, either before its first task or once it stopped.
+    #[default]
+    Idle,
+    Matching,
+    /// Its engine failed on the game it was matching.
+    Errored,
+    /// Its engine failed and could not be restarted.
+    Stopped,
+}
+
+/// State of a worker, displayed to find engines dragging a run.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerStatus {
+    pub state: WorkerState,
+    /// Index of the game it is matching, or matched last.
+    pub game_index: Option<usize>,
+    /// Last move played by its engine.
+    pub last_move: Option<(u8, u8)>,
+    /// Number of games its engine failed on.
+    pub errors: u32,
+    /// Number of times its engine was restarted after hanging or crashing.
+    pub restarts: u32,
+    /// Why it stopped, once stopped.
+    pub stop_reason: Option<String>,
+}
+
+/// Results of a run, each set of `(key, matches, total)` rows sorted by key.
+#[derive(Debug, Clone, Default)]
+pub struct MatchingResults {
+    /// Description of the engine producing the results, if one was opened.
+    pub engine: Option<EngineInfo>,
+    /// Matches and totals for each elo.
+    pub matches: Vec<(u64, u32, u32)>,
+    /// Top-`k` matches and totals for each elo, for `k` from 2 to the number of best moves
+    /// asked to the engine.
+    pub top_k: Vec<Vec<(u64, u32, u32)>>,
+    /// Matches and totals for each elo of black and white players, empty unless colors are
+    /// split.
+    pub black: Vec<(u64, u32, u32)>,
+    pub white: Vec<(u64, u32, u32)>,
+    /// Matches and totals for each player id, empty unless aggregating by player.
+    pub players: Vec<(u64, u32, u32)>,
+    /// Matches and totals for each band of moves, by its first move number.
+    pub move_bands: Vec<(u64, u32, u32)>,
+    /// Weighted matches and totals for each elo, empty unless weighting positions.
+    pub weighted: Vec<(u64, f64, f64)>,
+    /// Near misses and totals for each elo, empty unless counting near misses.
+    pub near_misses: Vec<(u64, u32, u32)>,
+    /// Positions at most each distance away from the human move and totals, for each distance
+    /// up to the near-miss distance.
+    pub distances: Vec<(u64, u32, u32)>,
+    pub completed_games: u64,
+    pub completed_positions: u64,
+    pub book_positions: u64,
+    pub swapped_positions: u64,
+    pub failed_games: u64,
+    pub truncated_games: u64,
+    pub failed_positions: u64,
+}
+impl MatchingResults {
+    /// Proportion of positions where the engine played the human move, over every rating, or
+    /// `None` without positions.
+    pub fn overall_accuracy(&self) -> Option<f64> {
+        accuracy(self.matches.iter().copied())
+    }
+}
+
 pub struct MoveMatching {
-    games: Vec<Game>,
+    /// Games to match, shared with the caller rather than copied, since databases can be large.
+    games: Arc<[Game]>,
+    options: MatchingOptions,
     matches: HashMap<u64, (AtomicU32, AtomicU32)>,
+    /// For each elo, number of positions where the human move was the engine's `i + 1`-th
+    /// best move, for `i` below `top_k`.
+    rank_matches: HashMap<u64, Vec<AtomicU32>>,
+    /// Matches and totals for each elo and color, only kept when splitting colors.
+    color_matches: HashMap<(u64, Color), (AtomicU32, AtomicU32)>,
+    /// Matches and totals for each player id, only kept when aggregating by player.
+    player_matches: HashMap<u64, (AtomicU32, AtomicU32)>,
+    /// Names of the players given one by the database, only kept when aggregating by player.
+    player_names: HashMap<u64, String>,
+    /// Weighted matches and totals for each elo, only kept when weighting positions.
+    weighted_matches: HashMap<u64, (AtomicF64, AtomicF64)>,
+    /// For each elo, number of positions where the engine played at most the near-miss
+    /// distance away from the human move, only kept when counting near misses.
+    near_matches: HashMap<u64, AtomicU32>,
+    /// Number of positions where the engine played at each distance from the human move, up
+    /// to the near-miss distance.
+    distances: Vec<AtomicU32>,
+    /// First move number of each band of moves, in increasing order.
+    move_bands: Vec<u32>,
+    /// Matches and totals for each band of moves.
+    band_matches: Vec<(AtomicU32, AtomicU32)>,
+    /// Indices of the games in the order they are handed out.
+    order: Vec<usize>,
+    /// Position in `order` of the next game to hand out.
     next: AtomicUsize,
+    /// Whether each game was matched, by index, so that checkpoints record which games are
+    /// left whatever order they were completed in.
+    completed: Vec<AtomicBool>,
     total_positions: u64,
     completed_games: AtomicUsize,
+    /// Positions of the games whose results were kept, counted along with them so that it
+    /// never decreases when a failed game is matched again.
     completed_positions: AtomicU64,
+    book_positions: AtomicU64,
+    /// Positions excluded because the engine chose to swap colors instead of playing.
+    swapped_positions: AtomicU64,
+    /// Games whose matching was interrupted by an engine error.
+    failed_games: AtomicU64,
+    /// Games whose matching was stopped early because they took more than their time budget.
+    truncated_games: AtomicU64,
+    /// Positions the engine failed on, including those of games matched again since. They are
+    /// not counted in `completed_positions`.
+    failed_positions: AtomicU64,
+    /// Positions left after the failure of games that are not matched again, or after the time
+    /// budget of truncated games, which are never matched but count toward the progress.
+    unmatched_positions: AtomicU64,
+    event_log: Option<EventLog>,
+    /// Index in the database of each game, recorded in the event log, when only a sample of
+    /// its games is matched.
+    database_indices: Option<Vec<usize>>,
+    /// Last moves played by engines, to detect engines answering the same move to everything.
+    recent_moves: Mutex<VecDeque<(u8, u8)>>,
+    /// Description of the engine producing the results, set by the first engine opened.
+    engine_info: OnceLock<EngineInfo>,
+    /// State of each worker, by id.
+    workers: Mutex<Vec<WorkerStatus>>,
+    /// Games whose matching failed, to match again, with the number of attempts made.
+    retries: Mutex<VecDeque<(usize, u32)>>,
+    /// Held by tasks while they add their results and mark their game as completed, see
+    /// [`MoveMatching::lock_results`].
+    results_lock: Mutex<()>,
+    /// Workers wait instead of starting new tasks while set.
+    paused: AtomicBool,
+    /// Workers do not start new tasks anymore once set.
+    stopped: AtomicBool,
+    /// Why matching was stopped before every game was matched, other than by the user.
+    abort_reason: OnceLock<String>,
+    /// Called with the results of each game once they are kept.
+    observer: Option<GameObserver>,
+    /// Settings of the run the results depend on, saved with them so that resuming with other
+    /// settings is refused.
+    settings: Vec<(&'static str, String)>,
 }
 impl MoveMatching {
-    pub fn from_games(games: &[Game]) -> Self {
+    pub fn from_games(games: Arc<[Game]>, options: MatchingOptions) -> Self {
         Self {
+            options,
             matches: HashMap::from_iter(
                 games
                     .iter()
@@ -31,28 +428,182 @@ impl MoveMatching {
                             .map(|g| (g.black_elo, (AtomicU32::new(0), AtomicU32::new(0)))),
                     ),
             ),
-            games: games.to_vec(),
-            next: AtomicUsize::new(0),
-            total_positions: games
+            rank_matches: games
+                .iter()
+                .flat_map(|g| [g.black_elo, g.white_elo])
+                .map(|elo| {
+                    let ranks = (0..options.top_k).map(|_| AtomicU32::new(0)).collect();
+                    (elo, ranks)
+                })
+                .collect(),
+            color_matches: games
                 .iter()
-                .map(|g| g.moves.len().saturating_sub(6) as u64)
-                .sum(),
+                .filter(|_| options.split_colors)
+                .flat_map(|g| [(g.black_elo, Color::Black), (g.white_elo, Color::White)])
+                .map(|key| (key, (AtomicU32::new(0), AtomicU32::new(0))))
+                .collect(),
+            player_matches: games
+                .iter()
+                .filter(|_| options.by_player)
+                .flat_map(|g| [g.black_id, g.white_id])
+                .map(|id| (id, (AtomicU32::new(0), AtomicU32::new(0))))
+                .collect(),
+            player_names: games
+                .iter()
+                .filter(|_| options.by_player)
+                .flat_map(|g| [(g.black_id, &g.black_name), (g.white_id, &g.white_name)])
+                .filter_map(|(id, name)| Some((id, name.clone()?)))
+                .collect(),
+            weighted_matches: games
+                .iter()
+                .filter(|_| options.weighting != Weighting::None)
+                .flat_map(|g| [g.black_elo, g.white_elo])
+                .map(|elo| (elo, (AtomicF64::default(), AtomicF64::default())))
+                .collect(),
+            near_matches: games
+                .iter()
+                .filter(|_| options.near_miss_distance.is_some())
+                .flat_map(|g| [g.black_elo, g.white_elo])
+                .map(|elo| (elo, AtomicU32::new(0)))
+                .collect(),
+            distances: options
+                .near_miss_distance
+                .map_or(0..0, |d| 0..d as usize + 1)
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            move_bands: DEFAULT_MOVE_BANDS.to_vec(),
+            band_matches: DEFAULT_MOVE_BANDS
+                .iter()
+                .map(|_| (AtomicU32::new(0), AtomicU32::new(0)))
+                .collect(),
+            order: options.dispatch_order(&games),
+            next: AtomicUsize::new(0),
+            completed: games.iter().map(|_| AtomicBool::new(false)).collect(),
+            total_positions: games.iter().map(|g| options.matched_positions(g)).sum(),
             completed_games: AtomicUsize::new(0),
             completed_positions: AtomicU64::new(0),
+            book_positions: AtomicU64::new(0),
+            swapped_positions: AtomicU64::new(0),
+            failed_games: AtomicU64::new(0),
+            truncated_games: AtomicU64::new(0),
+            unmatched_positions: AtomicU64::new(0),
+            failed_positions: AtomicU64::new(0),
+            event_log: None,
+            database_indices: None,
+            recent_moves: Mutex::new(VecDeque::with_capacity(DIVERSITY_WINDOW)),
+            engine_info: OnceLock::new(),
+            workers: Mutex::new(vec![]),
+            retries: Mutex::new(VecDeque::new()),
+            results_lock: Mutex::new(()),
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            abort_reason: OnceLock::new(),
+            observer: None,
+            settings: vec![],
+            games,
         }
     }
 
-    pub fn from_checkpoint<P: AsRef<Path>>(games: &[Game], path: P) -> Self {
-        let mut matching = Self::from_games(games);
+    /// Records every matched position to this event log.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Records games in the event log by these indices in the database, one per game, when
+    /// the games matched are a sample of its games.
+    pub fn with_database_indices(mut self, indices: Vec<usize>) -> Self {
+        assert_eq!(indices.len(), self.games.len());
+        self.database_indices = Some(indices);
+        self
+    }
+
+    /// Index in the database of the game at this index.
+    fn database_index(&self, index: usize) -> usize {
+        self.database_indices
+            .as_ref()
+            .map_or(index, |indices| indices[index])
+    }
+
+    /// Saves these settings of the run, such as the move time of engines, with the results.
+    pub fn with_settings(mut self, settings: Vec<(&'static str, String)>) -> Self {
+        self.settings = settings;
+        self
+    }
 
-        let csv = csv::Reader::from_path(&path).unwrap().into_deserialize();
-        for (elo, matches, total) in csv.filter_map(|e| e.ok()) {
+    /// Calls `observer` with the results of each game once they are kept, from the worker that
+    /// matched it. Games matched again after a failure are only observed once.
+    pub fn with_observer(mut self, observer: impl Fn(GameResult) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Also aggregates results in bands of moves starting at these move numbers, instead of
+    /// [`DEFAULT_MOVE_BANDS`]. Moves before the first band are not counted in any.
+    pub fn with_move_bands(mut self, mut starts: Vec<u32>) -> Self {
+        starts.sort_unstable();
+        starts.dedup();
+        self.band_matches = starts
+            .iter()
+            .map(|_| (AtomicU32::new(0), AtomicU32::new(0)))
+            .collect();
+        self.move_bands = starts;
+        self
+    }
+
+    /// Index of the band of moves the move at index `ply` of a game falls in.
+    fn move_band(&self, ply: usize) -> Option<usize> {
+        let move_number = ply as u32 + 1;
+        self.move_bands
+            .iter()
+            .rposition(|start| *start <= move_number)
+    }
+
+    /// Resumes matching from checkpointed results, refusing corrupted checkpoints and those
+    /// saved with other `settings`, as given to [`MoveMatching::with_settings`]. Settings
+    /// missing from the checkpoint, saved by older versions, are not checked.
+    ///
+    /// Games are assumed to have been completed in order, until the positions of the results
+    /// run out: call [`MoveMatching::restore_completed_games`] to resume from the games
+    /// actually completed instead. This is required when games were stopped early by their
+    /// time budget or by engine errors, since their few positions would otherwise be taken as
+    /// positions of the next games.
+    pub fn from_checkpoint<P: AsRef<Path>>(
+        games: Arc<[Game]>,
+        options: MatchingOptions,
+        settings: Vec<(&'static str, String)>,
+        path: P,
+    ) -> Result<Self, ResultsError> {
+        let saved = load_metadata(&path)?;
+        for (key, current) in &settings {
+            if let Some((_, saved)) = saved.iter().find(|(k, _)| k == key) {
+                if saved != current {
+                    return Err(ResultsError::SettingsMismatch {
+                        key: key.to_string(),
+                        saved: saved.clone(),
+                        current: current.clone(),
+                    });
+                }
+            }
+        }
+        let mut matching = Self::from_games(games.clone(), options).with_settings(settings);
+        if let Some(failed) = saved
+            .iter()
+            .find(|(key, _)| key == "failed_positions")
+            .and_then(|(_, value)| value.parse().ok())
+        {
+            matching.failed_positions = AtomicU64::new(failed);
+        }
+
+        let results = load_results(path)?;
+        // Ratings of XML databases may change between runs, so results may have ratings no
+        // game has anymore: they are kept, as by the restoration of every other result
+        for &(elo, matches, total) in &results {
             matching
                 .matches
-                .entry(elo)
-                .and_modify(|e| *e = (AtomicU32::new(matches), AtomicU32::new(total)))
-                .or_insert((AtomicU32::new(matches), AtomicU32::new(total)));
+                .insert(elo, (AtomicU32::new(matches), AtomicU32::new(total)));
         }
+        matching.restore_top_k(1, &results);
         let mut positions: u64 = matching
             .matches
             .values()
@@ -61,11 +612,13 @@ impl MoveMatching {
 
         let mut completed_games = 0;
         let mut completed_positions = 0;
-        for g in games {
-            if let Some(p) = positions.checked_sub(g.moves.len().saturating_sub(7) as u64) {
+        for &index in &matching.order {
+            let game_positions = options.matched_positions(&games[index]);
+            if let Some(p) = positions.checked_sub(game_positions) {
                 positions = p;
-                completed_positions += g.moves.len().saturating_sub(7) as u64;
-                completed_games += 1
+                completed_positions += game_positions;
+                completed_games += 1;
+                matching.completed[index] = AtomicBool::new(true);
             } else {
                 break;
             }
@@ -74,7 +627,93 @@ impl MoveMatching {
         matching.completed_games = AtomicUsize::new(completed_games);
         matching.completed_positions = AtomicU64::new(completed_positions);
 
-        matching
+        Ok(matching)
+    }
+
+    /// Keeps tasks from adding their results until the guard is dropped, so that the results
+    /// and completed games read meanwhile agree: checkpoints saved while workers run must not
+    /// record a game as completed without its results, or its results without it.
+    pub fn lock_results(&self) -> MutexGuard<'_, ()> {
+        self.results_lock.lock().unwrap()
+    }
+
+    /// Indices of the games completed so far, in increasing order.
+    pub fn completed_game_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.completed
+            .iter()
+            .enumerate()
+            .filter(|(_, completed)| completed.load(std::sync::atomic::Ordering::Acquire))
+            .map(|(index, _)| index)
+    }
+
+    /// Resumes from exactly these completed games, saved from
+    /// [`MoveMatching::completed_game_indices`], instead of the games inferred from the results
+    /// by [`MoveMatching::from_checkpoint`]. Other games are matched, in order. Indices of games
+    /// that are not in the database are ignored.
+    pub fn restore_completed_games(&mut self, indices: &[usize]) {
+        for completed in &mut self.completed {
+            *completed = AtomicBool::new(false);
+        }
+        let mut completed_games = 0;
+        let mut completed_positions = 0;
+        for &index in indices {
+            let Some(completed) = self.completed.get_mut(index) else {
+                continue;
+            };
+            if !*completed.get_mut() {
+                *completed = AtomicBool::new(true);
+                completed_games += 1;
+                completed_positions += self.options.matched_positions(&self.games[index]);
+            }
+        }
+        self.next = AtomicUsize::new(0);
+        self.completed_games = AtomicUsize::new(completed_games);
+        self.completed_positions = AtomicU64::new(completed_positions);
+    }
+
+    /// Records which engine produces the results, keeping the first one set.
+    pub fn set_engine_info(&self, info: &EngineInfo) {
+        let _ = self.engine_info.set(info.clone());
+    }
+
+    /// Metadata describing the results, saved along with them.
+    pub fn results_metadata(&self) -> Vec<(&'static str, String)> {
+        let mut metadata = match self.engine_info.get() {
+            Some(info) => [("engine", &info.name), ("engine_version", &info.version)]
+                .into_iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(key, value)| (key, value.clone()))
+                .collect(),
+            None => vec![],
+        };
+        metadata.extend(self.settings.iter().cloned());
+        metadata.push(("failed_positions", self.failed_positions().to_string()));
+        if let Some(accuracy) = self.overall_accuracy() {
+            metadata.push(("overall_accuracy", format!("{accuracy:.4}")));
+        }
+        if self.splits_colors() {
+            for (key, color) in [
+                ("black_accuracy", Color::Black),
+                ("white_accuracy", Color::White),
+            ] {
+                if let Some(accuracy) = self.color_accuracy(color) {
+                    metadata.push((key, format!("{accuracy:.4}")));
+                }
+            }
+        }
+        metadata
+    }
+
+    /// Proportion of the positions matched so far where the engine played the human move,
+    /// over every rating, or `None` before any position is matched.
+    pub fn overall_accuracy(&self) -> Option<f64> {
+        accuracy(self.snapshot())
+    }
+
+    /// Like [`MoveMatching::overall_accuracy`], for the players of this color only. Always
+    /// `None` unless splitting colors.
+    pub fn color_accuracy(&self, color: Color) -> Option<f64> {
+        accuracy(self.color_snapshot(color))
     }
 
     pub fn completed_games(&self) -> u64 {
@@ -87,6 +726,141 @@ impl MoveMatching {
             .load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Number of positions excluded from the results because the engine played a book move.
+    pub fn book_positions(&self) -> u64 {
+        self.book_positions
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Number of positions excluded from the results because the engine chose to swap colors
+    /// instead of playing.
+    pub fn swapped_positions(&self) -> u64 {
+        self.swapped_positions
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Number of games whose matching was interrupted by an engine error, and only partially
+    /// counted.
+    pub fn failed_games(&self) -> u64 {
+        self.failed_games.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Number of games whose matching was stopped early because they took more than their time
+    /// budget, and only partially counted.
+    pub fn truncated_games(&self) -> u64 {
+        self.truncated_games
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Number of positions the engine failed on during this run.
+    pub fn failed_positions(&self) -> u64 {
+        self.failed_positions
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Number of positions done with: either matched, or left unmatched because their game
+    /// failed or ran over its time budget. It reaches [`MoveMatching::total_positions`] once
+    /// every game is done with.
+    pub fn processed_positions(&self) -> u64 {
+        self.completed_positions()
+            + self
+                .unmatched_positions
+                .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// The move engines answered to an improbable fraction of the last positions, along with
+    /// this fraction. This indicates a broken engine, producing a plausible but wrong accuracy.
+    pub fn stuck_move(&self) -> Option<((u8, u8), f64)> {
+        let recent_moves = self.recent_moves.lock().unwrap();
+        if recent_moves.len() < DIVERSITY_WINDOW {
+            return None;
+        }
+        let mut counts = HashMap::<(u8, u8), usize>::new();
+        for m in recent_moves.iter() {
+            *counts.entry(*m).or_default() += 1
+        }
+        let (m, count) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+        let fraction = count as f64 / recent_moves.len() as f64;
+        (fraction > STUCK_FRACTION).then_some((m, fraction))
+    }
+
+    fn record_engine_move(&self, engine_move: (u8, u8)) {
+        let mut recent_moves = self.recent_moves.lock().unwrap();
+        if recent_moves.len() == DIVERSITY_WINDOW {
+            recent_moves.pop_front();
+        }
+        recent_moves.push_back(engine_move)
+    }
+
+    /// State of the workers that matched positions so far, by id.
+    pub fn worker_statuses(&self) -> Vec<WorkerStatus> {
+        self.workers.lock().unwrap().clone()
+    }
+
+    fn update_worker(&self, id: usize, update: impl FnOnce(&mut WorkerStatus)) {
+        let mut workers = self.workers.lock().unwrap();
+        if workers.len() <= id {
+            workers.resize(id + 1, WorkerStatus::default());
+        }
+        update(&mut workers[id])
+    }
+
+    /// Records that a worker stopped pulling tasks.
+    pub fn set_worker_idle(&self, id: usize) {
+        self.update_worker(id, |w| w.state = WorkerState::Idle)
+    }
+
+    pub fn set_worker_stopped(&self, id: usize, reason: String) {
+        self.update_worker(id, |w| {
+            w.state = WorkerState::Stopped;
+            w.stop_reason = Some(reason);
+        })
+    }
+
+    /// Counts a restart of the engine of a worker, returning its number of restarts.
+    pub fn record_engine_restart(&self, id: usize) -> u32 {
+        let mut restarts = 0;
+        self.update_worker(id, |w| {
+            w.restarts += 1;
+            restarts = w.restarts
+        });
+        restarts
+    }
+
+    /// Pauses or resumes the dispatch of tasks. Tasks started before pausing run to completion,
+    /// and engines are kept open.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused
+            .store(paused, std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Stops the dispatch of tasks, for instance when the run is interrupted. Tasks started
+    /// before are not interrupted.
+    pub fn stop(&self) {
+        self.stopped
+            .store(true, std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Stops the dispatch of tasks because matching cannot go on, keeping the first reason
+    /// given.
+    pub fn abort(&self, reason: String) {
+        let _ = self.abort_reason.set(reason);
+        self.stop()
+    }
+
+    /// Why matching was aborted, if it was.
+    pub fn abort_reason(&self) -> Option<&str> {
+        self.abort_reason.get().map(String::as_str)
+    }
+
     pub fn total_games(&self) -> u64 {
         self.games.len() as u64
     }
@@ -109,83 +883,1439 @@ impl MoveMatching {
         })
     }
 
-    pub fn get_next_task<'a>(&'a self) -> Option<MoveMatchingTask<'a>> {
-        let next = self.next.fetch_add(1, std::sync::atomic::Ordering::Acquire);
-        if let Some(game) = self.games.get(next) {
-            let black_matches = self.matches.get(&game.black_elo).unwrap();
-            let white_matches = self.matches.get(&game.white_elo).unwrap();
-            Some(MoveMatchingTask {
-                moves: &game.moves,
-                idx: 5,
-                black_matches,
-                white_matches,
-                completed_games: &self.completed_games,
-                completed_positions: &self.completed_positions,
+    /// Number of best moves asked to the engine.
+    pub fn top_k(&self) -> u8 {
+        self.options.top_k
+    }
+
+    /// Like [`MoveMatching::snapshot`], counting a match when the human move is among the `k`
+    /// best moves of the engine.
+    pub fn top_k_snapshot(&self, k: u8) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        self.matches.iter().map(move |(elo, (_, total))| {
+            let matches = self.rank_matches[elo]
+                .iter()
+                .take(k as usize)
+                .map(|m| m.load(std::sync::atomic::Ordering::Relaxed))
+                .sum();
+            (
+                *elo,
+                matches,
+                total.load(std::sync::atomic::Ordering::Relaxed),
+            )
+        })
+    }
+
+    /// Restores top-`k` matches saved from [`MoveMatching::top_k_snapshot`], once the top
+    /// `k - 1` matches were restored. Like the other restored results, those of ratings no game
+    /// has are kept.
+    pub fn restore_top_k(&mut self, k: u8, results: &[(u64, u32, u32)]) {
+        let top_k = self.options.top_k;
+        for (elo, matches, _) in results {
+            let ranks = self
+                .rank_matches
+                .entry(*elo)
+                .or_insert_with(|| (0..top_k).map(|_| AtomicU32::new(0)).collect());
+            let Some((rank, better)) = ranks.get_mut(..k as usize).and_then(|r| r.split_last_mut())
+            else {
+                continue;
+            };
+            let better: u32 = better.iter_mut().map(|m| *m.get_mut()).sum();
+            *rank.get_mut() = matches.saturating_sub(better);
+        }
+    }
+
+    /// Whether the results of black and white players are kept apart.
+    pub fn splits_colors(&self) -> bool {
+        self.options.split_colors
+    }
+
+    /// Like [`MoveMatching::snapshot`], for the players of this color only. Empty unless colors
+    /// are split.
+    pub fn color_snapshot(&self, color: Color) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        self.color_matches
+            .iter()
+            .filter(move |((_, c), _)| *c == color)
+            .map(|((elo, _), (matches, total))| {
+                (
+                    *elo,
+                    matches.load(std::sync::atomic::Ordering::Relaxed),
+                    total.load(std::sync::atomic::Ordering::Relaxed),
+                )
             })
-        } else {
-            None
+    }
+
+    /// Restores results saved from [`MoveMatching::color_snapshot`].
+    pub fn restore_color(&mut self, color: Color, results: &[(u64, u32, u32)]) {
+        for (elo, matches, total) in results {
+            self.color_matches.insert(
+                (*elo, color),
+                (AtomicU32::new(*matches), AtomicU32::new(*total)),
+            );
+        }
+    }
+
+    /// Whether the results of each player are kept.
+    pub fn aggregates_by_player(&self) -> bool {
+        self.options.by_player
+    }
+
+    /// Matches and totals of each player, by id in the database. Empty unless aggregating by
+    /// player.
+    pub fn player_snapshot(&self) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        self.player_matches.iter().map(|(id, (matches, total))| {
+            (
+                *id,
+                matches.load(std::sync::atomic::Ordering::Relaxed),
+                total.load(std::sync::atomic::Ordering::Relaxed),
+            )
+        })
+    }
+
+    /// Names of the players in [`MoveMatching::player_snapshot`] given one by the database, by
+    /// id.
+    pub fn player_names(&self) -> &HashMap<u64, String> {
+        &self.player_names
+    }
+
+    /// Restores results saved from [`MoveMatching::player_snapshot`].
+    pub fn restore_players(&mut self, results: &[(u64, u32, u32)]) {
+        for (id, matches, total) in results {
+            self.player_matches
+                .insert(*id, (AtomicU32::new(*matches), AtomicU32::new(*total)));
+        }
+    }
+
+    /// Current results of the run.
+    pub fn results(&self) -> MatchingResults {
+        let sorted = |results: &mut dyn Iterator<Item = (u64, u32, u32)>| {
+            let mut results = results.collect::<Vec<_>>();
+            results.sort();
+            results
+        };
+        let mut weighted = self.weighted_snapshot().collect::<Vec<_>>();
+        weighted.sort_by_key(|(elo, _, _)| *elo);
+        MatchingResults {
+            engine: self.engine_info.get().cloned(),
+            matches: sorted(&mut self.snapshot()),
+            top_k: (2..=self.top_k())
+                .map(|k| sorted(&mut self.top_k_snapshot(k)))
+                .collect(),
+            black: sorted(&mut self.color_snapshot(Color::Black)),
+            white: sorted(&mut self.color_snapshot(Color::White)),
+            players: sorted(&mut self.player_snapshot()),
+            move_bands: sorted(&mut self.move_band_snapshot()),
+            weighted,
+            near_misses: sorted(&mut self.near_miss_snapshot()),
+            distances: self.distance_snapshot().collect(),
+            completed_games: self.completed_games(),
+            completed_positions: self.completed_positions(),
+            book_positions: self.book_positions(),
+            swapped_positions: self.swapped_positions(),
+            failed_games: self.failed_games(),
+            truncated_games: self.truncated_games(),
+            failed_positions: self.failed_positions(),
+        }
+    }
+
+    /// Whether weighted results are kept.
+    pub fn is_weighted(&self) -> bool {
+        self.options.weighting != Weighting::None
+    }
+
+    /// Like [`MoveMatching::snapshot`], where each position counts as much as the weight of its
+    /// game. Empty unless weighting positions.
+    pub fn weighted_snapshot(&self) -> impl Iterator<Item = (u64, f64, f64)> + '_ {
+        self.weighted_matches
+            .iter()
+            .map(|(elo, (matches, total))| (*elo, matches.load(), total.load()))
+    }
+
+    /// Restores results saved from [`MoveMatching::weighted_snapshot`].
+    pub fn restore_weighted(&mut self, results: &[(u64, f64, f64)]) {
+        for (elo, matches, total) in results {
+            self.weighted_matches
+                .insert(*elo, (AtomicF64::new(*matches), AtomicF64::new(*total)));
+        }
+    }
+
+    /// Distance up to which positions where the engine did not play the human move are counted
+    /// as near misses, if they are.
+    pub fn near_miss_distance(&self) -> Option<u8> {
+        self.options.near_miss_distance
+    }
+
+    /// Like [`MoveMatching::snapshot`], counting positions where the engine played at most the
+    /// near-miss distance away from the human move as matches. Empty unless counting near
+    /// misses.
+    pub fn near_miss_snapshot(&self) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        self.near_matches.iter().map(|(elo, near)| {
+            (
+                *elo,
+                near.load(std::sync::atomic::Ordering::Relaxed),
+                self.matches.get(elo).map_or(0, |(_, total)| {
+                    total.load(std::sync::atomic::Ordering::Relaxed)
+                }),
+            )
+        })
+    }
+
+    /// Like [`MoveMatching::overall_accuracy`], counting near misses as matches. Always `None`
+    /// unless counting near misses.
+    pub fn near_miss_accuracy(&self) -> Option<f64> {
+        accuracy(self.near_miss_snapshot())
+    }
+
+    /// Restores results saved from [`MoveMatching::near_miss_snapshot`].
+    pub fn restore_near_misses(&mut self, results: &[(u64, u32, u32)]) {
+        for (elo, near, _) in results {
+            self.near_matches.insert(*elo, AtomicU32::new(*near));
+        }
+    }
+
+    /// For each distance up to the near-miss distance, number of positions where the engine
+    /// played at most this far from the human move, and the number of positions. Empty unless
+    /// counting near misses.
+    pub fn distance_snapshot(&self) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        let total = self
+            .matches
+            .values()
+            .map(|(_, total)| total.load(std::sync::atomic::Ordering::Relaxed))
+            .sum::<u32>();
+        self.distances
+            .iter()
+            .enumerate()
+            .scan(0, move |within, (distance, count)| {
+                *within += count.load(std::sync::atomic::Ordering::Relaxed);
+                Some((distance as u64, *within, total))
+            })
+    }
+
+    /// Restores results saved from [`MoveMatching::distance_snapshot`].
+    pub fn restore_distances(&mut self, results: &[(u64, u32, u32)]) {
+        let mut results = results.to_vec();
+        results.sort();
+        let mut previous = 0;
+        for (distance, within, _) in results {
+            if let Some(counter) = self.distances.get_mut(distance as usize) {
+                *counter = AtomicU32::new(within.saturating_sub(previous));
+                previous = within;
+            }
+        }
+    }
+
+    /// Matches and totals of each band of moves, by the first move number of the band.
+    pub fn move_band_snapshot(&self) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        self.move_bands
+            .iter()
+            .zip(&self.band_matches)
+            .map(|(start, (matches, total))| {
+                (
+                    *start as u64,
+                    matches.load(std::sync::atomic::Ordering::Relaxed),
+                    total.load(std::sync::atomic::Ordering::Relaxed),
+                )
+            })
+    }
+
+    /// Restores results saved from [`MoveMatching::move_band_snapshot`]. Bands that are not
+    /// used anymore are ignored.
+    pub fn restore_move_bands(&mut self, results: &[(u64, u32, u32)]) {
+        for (start, matches, total) in results {
+            if let Some(i) = self.move_bands.iter().position(|s| *s as u64 == *start) {
+                self.band_matches[i] = (AtomicU32::new(*matches), AtomicU32::new(*total));
+            }
+        }
+    }
+
+    /// Brackets of ratings results are displayed in.
+    pub fn brackets(&self) -> &Brackets {
+        &self.options.brackets
+    }
+
+    /// All metrics currently available, in the order they are cycled through by the interface.
+    pub fn metrics(&self) -> Vec<Metric> {
+        let options = &self.options.brackets;
+        let brackets = bracketize(self.snapshot(), options);
+        let accuracy = |brackets: &[(u32, u32, u32)]| {
+            bracket_accuracies(brackets)
+                .map(|(bracket, accuracy)| (bracket as f64, accuracy))
+                .collect()
+        };
+        let positions = |brackets: &[(u32, u32, u32)]| {
+            brackets
+                .iter()
+                .map(|(bracket, _, total)| (*bracket as f64, *total as f64))
+                .collect()
+        };
+        let mut brackets = vec![("all", brackets)];
+        if self.splits_colors() {
+            brackets.push((
+                "black",
+                bracketize(self.color_snapshot(Color::Black), options),
+            ));
+            brackets.push((
+                "white",
+                bracketize(self.color_snapshot(Color::White), options),
+            ));
+        }
+        let mut metrics = vec![
+            Metric {
+                name: "Move matching accuracy".to_string(),
+                x_label: "Rating",
+                y_label: "Move matching %",
+                series: brackets.iter().map(|(n, b)| (*n, accuracy(b))).collect(),
+            },
+            Metric {
+                name: "Matched positions".to_string(),
+                x_label: "Rating",
+                y_label: "Positions",
+                series: brackets.iter().map(|(n, b)| (*n, positions(b))).collect(),
+            },
+        ];
+        metrics.push(Metric {
+            name: "Move matching accuracy by move number".to_string(),
+            x_label: "Move number",
+            y_label: "Move matching %",
+            series: vec![(
+                "all",
+                self.move_band_snapshot()
+                    .filter(|(_, _, total)| *total > 0)
+                    .map(|(start, matches, total)| {
+                        (start as f64, matches as f64 / total as f64 * 100.)
+                    })
+                    .collect(),
+            )],
+        });
+        if self.is_weighted() {
+            metrics.push(Metric {
+                name: "Weighted move matching accuracy".to_string(),
+                x_label: "Rating",
+                y_label: "Move matching %",
+                series: vec![(
+                    "all",
+                    weighted_bracket_accuracies(self.weighted_snapshot(), options)
+                        .into_iter()
+                        .map(|(bracket, accuracy)| (bracket as f64, accuracy))
+                        .collect(),
+                )],
+            });
+        }
+        if let Some(distance) = self.near_miss_distance() {
+            metrics.push(Metric {
+                name: format!("Near-miss accuracy (within {distance} points)"),
+                x_label: "Rating",
+                y_label: "Move matching %",
+                series: vec![(
+                    "all",
+                    accuracy(&bracketize(self.near_miss_snapshot(), options)),
+                )],
+            });
+            metrics.push(Metric {
+                name: "Positions by distance to the human move".to_string(),
+                x_label: "Distance",
+                y_label: "Positions within %",
+                series: vec![(
+                    "all",
+                    self.distance_snapshot()
+                        .filter(|(_, _, total)| *total > 0)
+                        .map(|(distance, within, total)| {
+                            (distance as f64, within as f64 / total as f64 * 100.)
+                        })
+                        .collect(),
+                )],
+            });
+        }
+        let k = self.top_k();
+        if k > 1 {
+            metrics.push(Metric {
+                name: format!("Top-{k} move matching accuracy"),
+                x_label: "Rating",
+                y_label: "Move matching %",
+                series: vec![(
+                    "all",
+                    accuracy(&bracketize(self.top_k_snapshot(k), options)),
+                )],
+            });
+        }
+        metrics
+    }
+
+    /// Task matching the next game for this worker, which is then shown matching it. Games
+    /// completed before resuming are skipped, as well as games too short to have any position
+    /// to match, such as truncated games, which are counted as completed.
+    pub fn get_next_task(&self, worker: usize) -> Option<MoveMatchingTask<'_>> {
+        let task = self.next_task()?;
+        self.update_worker(worker, |w| {
+            w.state = WorkerState::Matching;
+            w.game_index = Some(task.game_index);
+        });
+        Some(task)
+    }
+
+    /// Task matching the next game, see [`MoveMatching::get_next_task`].
+    fn next_task(&self) -> Option<MoveMatchingTask<'_>> {
+        while self.is_paused() && !self.is_stopped() {
+            std::thread::sleep(PAUSE_POLL_PERIOD)
+        }
+        if self.is_stopped() {
+            return None;
+        }
+        if let Some((index, attempts)) = self.retries.lock().unwrap().pop_front() {
+            return Some(self.task(index, attempts));
+        }
+        loop {
+            if self.is_stopped() {
+                return None;
+            }
+            let next = self.next.fetch_add(1, std::sync::atomic::Ordering::Acquire);
+            let index = *self.order.get(next)?;
+            let game = &self.games[index];
+            if self.completed[index].load(std::sync::atomic::Ordering::Acquire) {
+                continue;
+            }
+            if self.options.matchable_plies(game).is_empty() {
+                self.skip_game(index);
+                continue;
+            }
+            return Some(self.task(index, 0));
+        }
+    }
+
+    /// Counts a game too short to have any position to match as completed.
+    fn skip_game(&self, index: usize) {
+        log::debug!(
+            "Skipping game {index}, which only has {} moves",
+            self.games[index].moves.len()
+        );
+        self.completed[index].store(true, std::sync::atomic::Ordering::Release);
+        self.completed_games
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Task matching this game, after this many failed attempts.
+    fn task(&self, game_index: usize, attempts: u32) -> MoveMatchingTask<'_> {
+        let game = &self.games[game_index];
+        MoveMatchingTask {
+            game_index,
+            game,
+            attempts,
+            requeued: false,
+            idx: self.options.matchable_plies(game).start,
+            black: (0, 0),
+            white: (0, 0),
+            black_ranks: vec![0; self.options.top_k as usize],
+            white_ranks: vec![0; self.options.top_k as usize],
+            bands: vec![(0, 0); self.move_bands.len()],
+            black_near_misses: 0,
+            white_near_misses: 0,
+            distances: vec![0; self.distances.len()],
+            black_matches: &self.matches[&game.black_elo],
+            white_matches: &self.matches[&game.white_elo],
+            matching: self,
         }
     }
+
+    /// Queues a game to be matched again after a failed attempt, unless it failed too many
+    /// times already. Returns whether it was queued.
+    fn requeue(&self, game_index: usize, attempts: u32) -> bool {
+        if attempts >= MAX_GAME_ATTEMPTS {
+            return false;
+        }
+        self.retries
+            .lock()
+            .unwrap()
+            .push_back((game_index, attempts));
+        true
+    }
+}
+
+/// Result of presenting a single position to an engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionOutcome {
+    pub engine_move: (u8, u8),
+    /// Whether the engine played the human move.
+    pub matched: bool,
+    /// Index of the human move among the best moves of the engine, if it is one of them.
+    pub rank: Option<u8>,
+    /// Time the engine took to answer.
+    pub latency: Duration,
+    /// Whether the engine reported playing from its opening book.
+    pub book: bool,
+    /// Whether the engine answered with `SUGGEST` instead of playing its move, which is then not
+    /// on its board.
+    pub suggested: bool,
+    /// Chebyshev distance between the move of the engine and the human move, 0 when they
+    /// match.
+    pub distance: u8,
+}
+
+/// Applies one of the 8 symmetries of a square board of this size to a point.
+fn transform((x, y): (u8, u8), symmetry: u8, size: u8) -> (u8, u8) {
+    let n = size - 1;
+    match symmetry {
+        0 => (x, y),
+        1 => (n - y, x),
+        2 => (n - x, n - y),
+        3 => (y, n - x),
+        4 => (n - x, y),
+        5 => (x, n - y),
+        6 => (y, x),
+        _ => (n - y, n - x),
+    }
+}
+
+/// Whether playing `a` or `b` on the board leads to the same position, up to a symmetry of
+/// the board leaving the current position unchanged.
+fn equivalent_moves(board: &[(u8, u8)], a: (u8, u8), b: (u8, u8), size: u8) -> bool {
+    if a == b {
+        return true;
+    }
+    let stones = |symmetry| {
+        let (mut black, mut white): (Vec<_>, Vec<_>) = (vec![], vec![]);
+        for (ply, m) in board.iter().enumerate() {
+            match Color::of_ply(ply) {
+                Color::Black => black.push(transform(*m, symmetry, size)),
+                Color::White => white.push(transform(*m, symmetry, size)),
+            }
+        }
+        black.sort();
+        white.sort();
+        (black, white)
+    };
+    let position = stones(0);
+    (1..8).any(|symmetry| transform(a, symmetry, size) == b && stones(symmetry) == position)
+}
+
+/// Asks the engine for its best moves on the board, where `board` holds the moves played so
+/// far, and compares them to the move played by the human.
+///
+/// When `incremental`, the board of the engine holds `board` without its last move, and the
+/// engine played the stones of the side to move: only the last move is sent with `TURN`
+/// instead of the whole board, falling back to sending it on protocol errors.
+///
+/// Returns `None` when the engine chose to swap colors instead of playing.
+pub fn match_position(
+    engine: &mut Engine,
+    board: &[(u8, u8)],
+    incremental: bool,
+    human_move: (u8, u8),
+    options: &MatchingOptions,
+) -> Result<Option<PositionOutcome>, EngineError> {
+    let start = Instant::now();
+    let mut suggested = false;
+    let best_moves = if options.top_k > 1 {
+        // Like `BEGIN` below, the empty board of a new game is only analysed by a reset engine
+        if board.is_empty() {
+            engine.restart()?;
+        }
+        engine.best_moves(board, options.top_k)?
+    } else {
+        let response = match board.last() {
+            Some(last_move) if incremental => {
+                match engine.send_command(Command::Turn(*last_move)) {
+                    Err(e) if !e.is_fatal() => {
+                        log::debug!(
+                            "[{}] Could not update the board of the engine, sending it again: {e}",
+                            engine.id
+                        );
+                        engine.send_command(Command::Board(board))?
+                    }
+                    response => response?,
+                }
+            }
+            // Engines are only asked to play first with `BEGIN` on the board of a new game
+            None => {
+                engine.restart()?;
+                engine.send_command(Command::Begin)?
+            }
+            _ => engine.send_command(Command::Board(board))?,
+        };
+        match response {
+            Response::Move(engine_move) => vec![engine_move],
+            Response::Suggest(engine_move) => {
+                log::debug!("[{}] Engine suggested {engine_move:?}", engine.id);
+                suggested = true;
+                vec![engine_move]
+            }
+            Response::Swap => vec![],
+            r => return Err(EngineError::UnexpectedResponse(r)),
+        }
+    };
+    let Some(&engine_move) = best_moves.first() else {
+        log::debug!("[{}] Engine chose to swap colors", engine.id);
+        return Ok(None);
+    };
+    // Coordinates outside of the board are already refused when parsing responses
+    if let Some(&occupied) = best_moves.iter().find(|m| board.contains(m)) {
+        return Err(EngineError::IllegalMove(occupied));
+    }
+    let size = engine.board_size();
+    let rank = best_moves
+        .iter()
+        .position(|m| {
+            *m == human_move
+                || options.symmetry
+                    && m.0.max(m.1).max(human_move.0).max(human_move.1) < size
+                    && equivalent_moves(board, *m, human_move, size)
+        })
+        .map(|rank| rank as u8);
+    let matched = rank == Some(0);
+    let distance = if matched {
+        0
+    } else {
+        engine_move
+            .0
+            .abs_diff(human_move.0)
+            .max(engine_move.1.abs_diff(human_move.1))
+    };
+    Ok(Some(PositionOutcome {
+        engine_move,
+        matched,
+        rank,
+        latency: start.elapsed(),
+        book: engine.played_book_move().unwrap_or(false),
+        suggested,
+        distance,
+    }))
+}
+
+/// Matches and totals of a single game, per side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GameResult {
+    pub game_index: usize,
+    pub black_matches: u32,
+    pub black_total: u32,
+    pub white_matches: u32,
+    pub white_total: u32,
+}
+
+/// Callback receiving the results of each game, see [`MoveMatching::with_observer`].
+pub type GameObserver = Box<dyn Fn(GameResult) + Send + Sync>;
+
+/// Append-only CSV of per-game results, shared by all workers.
+pub struct GameLog {
+    writer: Mutex<csv::Writer<File>>,
+}
+impl GameLog {
+    /// Opens the log for appending, writing a header if the file is new.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let mut writer = csv::Writer::from_writer(file);
+        if is_new {
+            writer.write_record([
+                "game",
+                "black_matches",
+                "black_total",
+                "white_matches",
+                "white_total",
+            ])?;
+            writer.flush()?;
+        }
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Appends the result of a game, flushing it immediately.
+    pub fn append(&self, result: &GameResult) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_record([
+            result.game_index.to_string(),
+            result.black_matches.to_string(),
+            result.black_total.to_string(),
+            result.white_matches.to_string(),
+            result.white_total.to_string(),
+        ])?;
+        writer.flush()
+    }
 }
 
 pub struct MoveMatchingTask<'a> {
-    moves: &'a [(u8, u8)],
+    game_index: usize,
+    game: &'a Game,
+    /// Number of failed attempts to match this game before this task.
+    attempts: u32,
+    /// Whether this task failed and its game was queued to be matched again.
+    requeued: bool,
     idx: usize,
+    black: (u32, u32),
+    white: (u32, u32),
+    /// Matches of each rank among the best moves of the engine, per side.
+    black_ranks: Vec<u32>,
+    white_ranks: Vec<u32>,
+    /// Matches and totals of each band of moves.
+    bands: Vec<(u32, u32)>,
+    /// Positions where the engine played at most the near-miss distance away from the human
+    /// move, per side.
+    black_near_misses: u32,
+    white_near_misses: u32,
+    /// Number of positions at each distance from the human move, up to the near-miss distance.
+    distances: Vec<u32>,
     black_matches: &'a (AtomicU32, AtomicU32),
     white_matches: &'a (AtomicU32, AtomicU32),
-    completed_games: &'a AtomicUsize,
-    completed_positions: &'a AtomicU64,
+    matching: &'a MoveMatching,
 }
 impl<'a> MoveMatchingTask<'a> {
+    /// Matches and totals accumulated by this task so far.
+    pub fn result(&self) -> GameResult {
+        GameResult {
+            game_index: self.game_index,
+            black_matches: self.black.0,
+            black_total: self.black.1,
+            white_matches: self.white.0,
+            white_total: self.white.1,
+        }
+    }
+
+    /// Whether this task failed and its game will be matched again by another task, its
+    /// results being discarded.
+    pub fn is_requeued(&self) -> bool {
+        self.requeued
+    }
+
+    /// Matches the positions of the game with this engine.
+    ///
+    /// When the engine fails, the results of the game are discarded and it is queued to be
+    /// matched again, up to [`MAX_GAME_ATTEMPTS`] times. After that, the positions matched
+    /// before the failure are kept and the game is counted as failed. Games without any
+    /// position to match are counted as completed without using the engine.
     pub fn match_challenge(&mut self, engine: &mut Engine) -> Result<(), EngineError> {
+        if self.matching.options.matchable_plies(self.game).is_empty() {
+            self.matching.skip_game(self.game_index);
+            return Ok(());
+        }
+        let start = Instant::now();
+        let mut truncated = false;
+        let mut result = if self.matching.options.reset_between_games {
+            engine.restart()
+        } else {
+            Ok(())
+        };
+        // Ply of the last position matched and the reply of the engine to it, when it is still
+        // on the board of the engine.
+        let mut last_reply: Option<(usize, (u8, u8))> = None;
+        // Positions matched, played from the book and where the engine swapped, and records,
+        // only counted and written once the results of the game are kept, so that the counts
+        // of the run never decrease when it is matched again.
+        let (mut positions, mut book_positions, mut swapped_positions) = (0, 0, 0);
+        let mut records = vec![];
         // Loop over moves and try to match them
-        let mut black_matches = (0, 0);
-        let mut white_matches = (0, 0);
-        let mut result = Ok(());
-        while self.idx < self.moves.len() - 2 {
-            std::thread::sleep(Duration::from_millis(500));
-            let matches = if self.idx % 2 == 0 {
-                &mut black_matches
+        while result.is_ok() && self.idx < self.matching.options.matchable_plies(self.game).end {
+            if !self.matching.options.is_matched(self.game, self.idx) {
+                self.idx += 1;
+                continue;
+            }
+            let (matches, ranks, near_misses) = if self.idx.is_multiple_of(2) {
+                (
+                    &mut self.black,
+                    &mut self.black_ranks,
+                    &mut self.black_near_misses,
+                )
             } else {
-                &mut white_matches
+                (
+                    &mut self.white,
+                    &mut self.white_ranks,
+                    &mut self.white_near_misses,
+                )
             };
-            match engine.send_command(Command::Board(&self.moves[0..self.idx])) {
-                Ok(Response::Move((x, y))) => {
-                    log::trace!("[{}] Move: {:?}", engine.id, (x, y));
-                    if (x, y) == self.moves[self.idx] {
-                        matches.0 += 1;
-                    }
+            if let Some(budget) = self.matching.options.game_time_budget {
+                if start.elapsed() >= budget {
+                    log::debug!(
+                        "[{}] Game {} took more than {budget:?}, stopping it",
+                        engine.id,
+                        self.game_index
+                    );
+                    truncated = true;
+                    break;
                 }
-                Ok(r) => {
-                    log::error!("Unexpected response from engine: {r:?}");
-                    result = Err(EngineError::UnexpectedResponse(r));
+            }
+            let human_move = self.game.moves[self.idx];
+            let clear_hash = self
+                .matching
+                .options
+                .clear_hash_every
+                .is_some_and(|n| positions % n as u64 == 0);
+            if clear_hash {
+                if let Err(e) = engine.send_command(Command::HashClear) {
+                    result = Err(e);
                     break;
                 }
+            }
+            // The engine plays a single side, so its board can only be updated when it played
+            // the human move of the same side at the previous position matched: the human move
+            // of the other side in between is then its last move.
+            let incremental = self.matching.options.incremental
+                && last_reply.is_some_and(|(ply, reply)| {
+                    ply + 2 == self.idx && reply == self.game.moves[ply]
+                });
+            match match_position(
+                engine,
+                &self.game.moves[0..self.idx],
+                incremental,
+                human_move,
+                &self.matching.options,
+            ) {
+                Ok(None) => {
+                    last_reply = None;
+                    swapped_positions += 1;
+                }
+                Ok(Some(outcome)) => {
+                    // A suggested move is not on the board of the engine
+                    last_reply = (!outcome.suggested).then_some((self.idx, outcome.engine_move));
+                    log::trace!("[{}] Move: {:?}", engine.id, outcome.engine_move);
+                    self.matching.record_engine_move(outcome.engine_move);
+                    self.matching
+                        .update_worker(engine.id, |w| w.last_move = Some(outcome.engine_move));
+                    if outcome.book {
+                        book_positions += 1;
+                    } else {
+                        matches.1 += 1;
+                        if outcome.matched {
+                            matches.0 += 1;
+                        }
+                        if let Some(band) = self.matching.move_band(self.idx) {
+                            self.bands[band].1 += 1;
+                            if outcome.matched {
+                                self.bands[band].0 += 1;
+                            }
+                        }
+                        if let Some(rank) = outcome.rank.and_then(|r| ranks.get_mut(r as usize)) {
+                            *rank += 1;
+                        }
+                        if let Some(count) = self.distances.get_mut(outcome.distance as usize) {
+                            *count += 1;
+                            *near_misses += 1;
+                        }
+                    }
+                    if self.matching.event_log.is_some() {
+                        let color = Color::of_ply(self.idx);
+                        records.push(PositionRecord {
+                            game: self.matching.database_index(self.game_index),
+                            ply: self.idx,
+                            color,
+                            elo: self.game.elo(color),
+                            human_move,
+                            engine_move: outcome.engine_move,
+                            book: outcome.book,
+                            matched: Some(outcome.matched),
+                        });
+                    }
+                }
                 Err(e) => {
-                    log::error!("Error when receiving response: {e:?}");
+                    log::error!("Error when matching position: {e}");
                     result = Err(e);
                     break;
                 }
             }
-            self.completed_positions
-                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            matches.1 += 1;
+            positions += 1;
             self.idx += 1
         }
+        if result.is_err() {
+            self.matching
+                .failed_positions
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.matching.update_worker(engine.id, |w| {
+                w.state = WorkerState::Errored;
+                w.errors += 1;
+            });
+            if self.matching.requeue(self.game_index, self.attempts + 1) {
+                log::warn!(
+                    "[{}] Matching game {} again (attempt {} of {MAX_GAME_ATTEMPTS})",
+                    engine.id,
+                    self.game_index,
+                    self.attempts + 2
+                );
+                self.requeued = true;
+                return result;
+            }
+        }
+        if let Some(event_log) = &self.matching.event_log {
+            for record in &records {
+                if let Err(e) = event_log.append(record) {
+                    log::error!("Could not write to the event log: {e:?}")
+                }
+            }
+        }
+        let results_lock = self.matching.lock_results();
+        self.matching
+            .completed_positions
+            .fetch_add(positions, std::sync::atomic::Ordering::Relaxed);
+        self.matching
+            .book_positions
+            .fetch_add(book_positions, std::sync::atomic::Ordering::Relaxed);
+        self.matching
+            .swapped_positions
+            .fetch_add(swapped_positions, std::sync::atomic::Ordering::Relaxed);
         self.black_matches
             .0
-            .fetch_add(black_matches.0, std::sync::atomic::Ordering::Relaxed);
+            .fetch_add(self.black.0, std::sync::atomic::Ordering::Relaxed);
         self.white_matches
             .0
-            .fetch_add(white_matches.0, std::sync::atomic::Ordering::Relaxed);
+            .fetch_add(self.white.0, std::sync::atomic::Ordering::Relaxed);
         self.black_matches
             .1
-            .fetch_add(black_matches.1, std::sync::atomic::Ordering::Relaxed);
+            .fetch_add(self.black.1, std::sync::atomic::Ordering::Relaxed);
         self.white_matches
             .1
-            .fetch_add(white_matches.1, std::sync::atomic::Ordering::Relaxed);
-        self.completed_games
+            .fetch_add(self.white.1, std::sync::atomic::Ordering::Relaxed);
+        if self.matching.options.split_colors {
+            for (key, (matches, total)) in [
+                ((self.game.black_elo, Color::Black), self.black),
+                ((self.game.white_elo, Color::White), self.white),
+            ] {
+                let counters = &self.matching.color_matches[&key];
+                counters
+                    .0
+                    .fetch_add(matches, std::sync::atomic::Ordering::Relaxed);
+                counters
+                    .1
+                    .fetch_add(total, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        if self.matching.options.by_player {
+            for (id, (matches, total)) in [
+                (self.game.black_id, self.black),
+                (self.game.white_id, self.white),
+            ] {
+                let counters = &self.matching.player_matches[&id];
+                counters
+                    .0
+                    .fetch_add(matches, std::sync::atomic::Ordering::Relaxed);
+                counters
+                    .1
+                    .fetch_add(total, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        if self.matching.is_weighted() {
+            let weight = self.matching.options.weighting.weight(self.game);
+            for (elo, (matches, total)) in [
+                (self.game.black_elo, self.black),
+                (self.game.white_elo, self.white),
+            ] {
+                let counters = &self.matching.weighted_matches[&elo];
+                counters.0.fetch_add(matches as f64 * weight);
+                counters.1.fetch_add(total as f64 * weight);
+            }
+        }
+        for ((matches, total), counters) in self.bands.iter().zip(&self.matching.band_matches) {
+            counters
+                .0
+                .fetch_add(*matches, std::sync::atomic::Ordering::Relaxed);
+            counters
+                .1
+                .fetch_add(*total, std::sync::atomic::Ordering::Relaxed);
+        }
+        for (elo, near_misses) in [
+            (self.game.black_elo, self.black_near_misses),
+            (self.game.white_elo, self.white_near_misses),
+        ] {
+            if let Some(counter) = self.matching.near_matches.get(&elo) {
+                counter.fetch_add(near_misses, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        for (counter, count) in self.matching.distances.iter().zip(&self.distances) {
+            counter.fetch_add(*count, std::sync::atomic::Ordering::Relaxed);
+        }
+        for (elo, ranks) in [
+            (self.game.black_elo, &self.black_ranks),
+            (self.game.white_elo, &self.white_ranks),
+        ] {
+            for (counter, matches) in self.matching.rank_matches[&elo].iter().zip(ranks) {
+                counter.fetch_add(*matches, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        if result.is_err() {
+            self.matching
+                .failed_games
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        } else if truncated {
+            self.matching
+                .truncated_games
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+        if result.is_err() || truncated {
+            let unmatched = self
+                .matching
+                .options
+                .matched_plies(self.game)
+                .filter(|ply| *ply >= self.idx)
+                .count();
+            self.matching
+                .unmatched_positions
+                .fetch_add(unmatched as u64, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.matching.completed[self.game_index].store(true, std::sync::atomic::Ordering::Release);
+        self.matching
+            .completed_games
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        drop(results_lock);
+        // No lock is held, so that a slow observer only delays this worker
+        if let Some(observer) = &self.matching.observer {
+            observer(self.result())
+        }
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        plot::{save_results, save_results_json, Performance},
+        protocol::{fake_engine, Engine},
+        test_utils::{game, mock_engine, moves, temp_path},
+    };
+
+    /// Matches every game with a single engine, including games matched again after failing.
+    fn match_all(matching: &MoveMatching, engine: &mut Engine) {
+        while let Some(mut task) = matching.get_next_task(0) {
+            let _ = task.match_challenge(engine);
+        }
+    }
+
+    #[test]
+    fn corrupted_checkpoint_is_refused() {
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(20))].into();
+        let path = temp_path("corrupted.csv");
+        save_results(
+            &path,
+            Performance {
+                name: "corrupted",
+                matches: [(1800, 3, 7), (2000, 2, 6)].into_iter(),
+            },
+            &[],
+        );
+        assert!(MoveMatching::from_checkpoint(
+            games.clone(),
+            MatchingOptions::default(),
+            vec![],
+            &path
+        )
+        .is_ok());
+
+        let mut content = std::fs::read(&path).unwrap();
+        let last = content.len() - 2;
+        content[last] ^= 1;
+        std::fs::write(&path, content).unwrap();
+        assert!(matches!(
+            MoveMatching::from_checkpoint(games, MatchingOptions::default(), vec![], &path),
+            Err(ResultsError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn matching_resumes_from_json_checkpoints() {
+        let games: Arc<[Game]> = (0..3).map(|_| game(1800, 2000, moves(20))).collect();
+        let matching = MoveMatching::from_games(games.clone(), MatchingOptions::default());
+        let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+        match_all(&matching, &mut engine);
+        let path = temp_path("resumed.json");
+        save_results_json(
+            &path,
+            Performance {
+                name: "resumed",
+                matches: matching.snapshot(),
+            },
+            &[],
+        );
+
+        let resumed =
+            MoveMatching::from_checkpoint(games, MatchingOptions::default(), vec![], &path)
+                .unwrap();
+        let sorted = |matching: &MoveMatching| {
+            let mut results = matching.snapshot().collect::<Vec<_>>();
+            results.sort();
+            results
+        };
+        assert_eq!(sorted(&resumed), sorted(&matching));
+        assert_eq!(resumed.completed_games(), 3);
+        assert!(resumed.is_completed());
+    }
+
+    #[test]
+    fn incremental_boards_are_only_used_for_a_single_side() {
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(200))].into();
+        let run = |colors, incremental| {
+            let options = MatchingOptions {
+                colors,
+                incremental,
+                skip_opening: 0,
+                ..MatchingOptions::default()
+            };
+            let matching = MoveMatching::from_games(games.clone(), options);
+            let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+            match_all(&matching, &mut engine);
+            let mut results = matching.snapshot().collect::<Vec<_>>();
+            results.sort();
+            (results, std::mem::take(&mut engine.sent))
+        };
+        let count =
+            |sent: &[String], command: &str| sent.iter().filter(|c| c.starts_with(command)).count();
+
+        // The engine plays every human move, so black positions after the first one, played
+        // with BEGIN, only need the white move in between
+        let (results, full) = run(ColorFilter::Black, false);
+        let (incremental_results, incremental) = run(ColorFilter::Black, true);
+        assert_eq!(incremental_results, results);
+        assert_eq!(results, vec![(1800, 99, 99), (2000, 0, 0)]);
+        assert_eq!(count(&full, "BOARD"), 98);
+        assert_eq!(count(&incremental, "BOARD"), 0);
+        assert_eq!(count(&incremental, "TURN"), 98);
+        let bytes = |sent: &[String]| sent.iter().map(String::len).sum::<usize>();
+        assert!(
+            bytes(&incremental) * 20 < bytes(&full),
+            "{} bytes sent incrementally, {} otherwise",
+            bytes(&incremental),
+            bytes(&full)
+        );
+
+        // The side to move changes at every position
+        let (results, sent) = run(ColorFilter::Both, true);
+        assert_eq!(results, vec![(1800, 99, 99), (2000, 99, 99)]);
+        assert_eq!(count(&sent, "TURN"), 0);
+        assert_eq!(count(&sent, "BOARD"), 197);
+    }
+
+    #[test]
+    fn first_moves_are_only_ranked_by_reset_engines() {
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(10))].into();
+        for top_k in [1, 3] {
+            let options = MatchingOptions {
+                skip_opening: 0,
+                top_k,
+                ..MatchingOptions::default()
+            };
+            let matching = MoveMatching::from_games(games.clone(), options);
+            let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+            match_all(&matching, &mut engine);
+            assert!(matching.is_completed());
+            let mut results = matching.snapshot().collect::<Vec<_>>();
+            results.sort();
+            assert_eq!(results, vec![(1800, 4, 4), (2000, 4, 4)]);
+            let first_query = engine
+                .sent
+                .iter()
+                .position(|c| c.starts_with("BEGIN") || c.starts_with("yxboard"))
+                .unwrap();
+            assert_eq!(engine.sent[first_query - 1], "RESTART\r\n");
+            let first_query = &engine.sent[first_query..];
+            if top_k == 1 {
+                assert_eq!(first_query[0], "BEGIN\r\n");
+            } else {
+                assert_eq!(first_query[..2], ["yxboard\r\nDONE\r\n", "yxnbest 3\r\n"]);
+            }
+        }
+    }
+
+    #[test]
+    fn games_are_shared_with_tasks_without_being_cloned() {
+        let games: Arc<[Game]> = (0..3).map(|_| game(1800, 2000, moves(20))).collect();
+        let matching = MoveMatching::from_games(games.clone(), MatchingOptions::default());
+        assert_eq!(Arc::strong_count(&games), 2);
+        assert!(Arc::ptr_eq(&matching.games, &games));
+        let task = matching.get_next_task(0).unwrap();
+        assert!(std::ptr::eq(task.game, &games[task.game_index]));
+        drop(matching);
+        assert_eq!(Arc::strong_count(&games), 1);
+    }
+
+    #[test]
+    fn engines_are_restarted_once_per_game() {
+        let games: Arc<[Game]> = (0..3).map(|_| game(1800, 2000, moves(20))).collect();
+        for reset_between_games in [false, true] {
+            let options = MatchingOptions {
+                reset_between_games,
+                ..MatchingOptions::default()
+            };
+            let matching = MoveMatching::from_games(games.clone(), options);
+            let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+            match_all(&matching, &mut engine);
+            let restarts = engine.sent.iter().filter(|c| *c == "RESTART\r\n").count();
+            assert_eq!(restarts, if reset_between_games { 3 } else { 0 });
+            assert!(matching.is_completed());
+        }
+    }
+
+    /// Opens an engine playing the first empty point, which is the human move in games of
+    /// [`moves`], only when its hash was cleared since its last move, and the last empty point
+    /// otherwise, as if its previous searches misled it.
+    fn hash_sensitive_engine() -> Engine {
+        let (mut stones, mut reading_board, mut cleared) = (vec![], false, true);
+        fake_engine(move |line, output| {
+            let _ = match line {
+                "START 15" | "RESTART" => write!(output, "OK\r\n"),
+                "ABOUT" => write!(output, "name=\"hash\"\r\n"),
+                "yxhashclear" => {
+                    cleared = true;
+                    Ok(())
+                }
+                "BOARD" => {
+                    stones.clear();
+                    reading_board = true;
+                    Ok(())
+                }
+                "DONE" => {
+                    reading_board = false;
+                    let mut empty = (0..15u8)
+                        .flat_map(|y| (0..15u8).map(move |x| (x, y)))
+                        .filter(|p| !stones.contains(p));
+                    let (x, y) = if cleared {
+                        empty.next()
+                    } else {
+                        empty.next_back()
+                    }
+                    .unwrap();
+                    cleared = false;
+                    write!(output, "{x},{y}\r\n")
+                }
+                line if reading_board => {
+                    let mut coordinates = line.split(',').map(|c| c.parse::<u8>().unwrap());
+                    stones.extend(coordinates.next().zip(coordinates.next()));
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+        })
+    }
+
+    #[test]
+    fn clearing_the_hash_keeps_searches_from_misleading_the_engine() {
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(30))].into();
+        let accuracy = |clear_hash_every| {
+            let options = MatchingOptions {
+                clear_hash_every,
+                ..MatchingOptions::default()
+            };
+            let matching = MoveMatching::from_games(games.clone(), options);
+            let mut engine = hash_sensitive_engine();
+            match_all(&matching, &mut engine);
+            let clears = engine.sent.iter().filter(|c| *c == "yxhashclear\r\n");
+            (matching.overall_accuracy().unwrap(), clears.count())
+        };
+        // 23 positions are matched, from the 6th move to the 3rd to last
+        assert_eq!(accuracy(None), (1. / 23., 0));
+        assert_eq!(accuracy(Some(2)), (12. / 23., 12));
+        assert_eq!(accuracy(Some(1)), (1., 23));
+    }
+
+    #[test]
+    fn matching_only_waits_for_the_engine() {
+        let games: Arc<[Game]> = (0..4).map(|_| game(1800, 2000, moves(30))).collect();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        let config = mock_engine("");
+        let mut engine = Engine::open_engine(0, &config).unwrap();
+        let start = Instant::now();
+        match_all(&matching, &mut engine);
+        assert!(matching.completed_positions() > 0);
+        assert_eq!(matching.completed_positions(), matching.total_positions());
+        let bound =
+            Duration::from_millis(config.move_time as u64) * matching.total_positions() as u32;
+        assert!(start.elapsed() < bound, "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn positions_of_failed_games_count_toward_the_progress() {
+        let games: Arc<[Game]> =
+            vec![game(1800, 2000, moves(30)), game(1800, 2000, moves(30))].into();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        // Every attempt at the first game fails on its first position, the point being occupied
+        let mut engine = Engine::open_engine(0, &mock_engine("0,0;0,0;0,0")).unwrap();
+        match_all(&matching, &mut engine);
+        assert_eq!(matching.failed_games(), 1);
+        assert!(matching.completed_positions() < matching.total_positions());
+        assert_eq!(matching.processed_positions(), matching.total_positions());
+    }
+
+    #[test]
+    fn failed_games_are_not_completed_when_requeued() {
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(30))].into();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        // The first two positions are matched, then the engine plays on an occupied point
+        let mut engine = Engine::open_engine(0, &mock_engine("5,0;6,0;0,0")).unwrap();
+        let mut task = matching.get_next_task(0).unwrap();
+        assert!(task.match_challenge(&mut engine).is_err());
+        assert!(task.is_requeued());
+        assert_eq!(task.idx, 7);
+        assert_eq!(matching.completed_games(), 0);
+        assert_eq!(matching.completed_positions(), 0);
+        assert!(matching.snapshot().all(|(_, _, total)| total == 0));
+
+        let mut task = matching.get_next_task(0).unwrap();
+        assert_eq!(task.game_index, 0);
+        task.match_challenge(&mut engine).unwrap();
+        assert_eq!(matching.completed_games(), 1);
+        assert_eq!(matching.completed_positions(), matching.total_positions());
+    }
+
+    #[test]
+    fn counted_positions_never_decrease_when_games_are_matched_again() {
+        let games: Arc<[Game]> = (0..2).map(|_| game(1800, 2000, moves(30))).collect();
+        let matching = Arc::new(MoveMatching::from_games(games, MatchingOptions::default()));
+        // Positions counted when each search starts, as sampled by throughput measures
+        let counted = Arc::new(Mutex::new(vec![]));
+        let mut engine = fake_engine({
+            let (matching, counted) = (matching.clone(), counted.clone());
+            let mut searches = 0;
+            move |line, output| {
+                let _ = match line {
+                    "START 15" | "RESTART" => write!(output, "OK\r\n"),
+                    "ABOUT" => write!(output, "name=\"counted\"\r\n"),
+                    "DONE" => {
+                        counted.lock().unwrap().push(matching.completed_positions());
+                        searches += 1;
+                        // The third search plays on an occupied point, failing the first game
+                        let reply = if searches == 3 { "0,0" } else { "14,14" };
+                        write!(output, "{reply}\r\n")
+                    }
+                    _ => Ok(()),
+                };
+            }
+        });
+        match_all(&matching, &mut engine);
+        assert!(matching.is_completed());
+        assert_eq!(matching.completed_positions(), matching.total_positions());
+        let counted = counted.lock().unwrap();
+        assert_eq!(counted.len(), 3 + 2 * 23);
+        assert!(counted.windows(2).all(|w| w[0] <= w[1]), "{counted:?}");
+        assert_eq!(counted[3 + 22], 0);
+        assert_eq!(counted[3 + 23], 23);
+    }
+
+    #[test]
+    fn moves_on_occupied_points_are_engine_errors() {
+        let board = [(7, 7), (8, 8)];
+        let mut engine = Engine::open_engine(0, &mock_engine("8,8")).unwrap();
+        assert!(matches!(
+            match_position(
+                &mut engine,
+                &board,
+                false,
+                (9, 9),
+                &MatchingOptions::default()
+            ),
+            Err(EngineError::IllegalMove((8, 8)))
+        ));
+
+        // Failed positions are not counted, and are resumed from
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(30))].into();
+        let matching = MoveMatching::from_games(games.clone(), MatchingOptions::default());
+        let mut engine = Engine::open_engine(0, &mock_engine("0,0;0,0;0,0")).unwrap();
+        match_all(&matching, &mut engine);
+        assert_eq!(matching.failed_positions(), 3);
+        assert!(matching.snapshot().all(|(_, _, total)| total == 0));
+        let path = temp_path("failed.csv");
+        save_results(
+            &path,
+            Performance {
+                name: "failed",
+                matches: matching.snapshot(),
+            },
+            &matching.results_metadata(),
+        );
+        let resumed =
+            MoveMatching::from_checkpoint(games, MatchingOptions::default(), vec![], &path)
+                .unwrap();
+        assert_eq!(resumed.failed_positions(), 3);
+    }
+
+    #[test]
+    fn games_stopped_early_are_resumed_from_the_completed_games() {
+        let games: Arc<[Game]> = (0..3).map(|_| game(1800, 2000, moves(30))).collect();
+        let options = MatchingOptions {
+            game_time_budget: Some(Duration::ZERO),
+            ..MatchingOptions::default()
+        };
+        let matching = MoveMatching::from_games(games.clone(), options);
+        let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+        match_all(&matching, &mut engine);
+        assert_eq!(matching.truncated_games(), 3);
+        assert!(matching.is_completed());
+        let path = temp_path("truncated.csv");
+        save_results(
+            &path,
+            Performance {
+                name: "truncated",
+                matches: matching.snapshot(),
+            },
+            &[],
+        );
+
+        // The results hold no position, so no game seems completed from them alone
+        let mut resumed = MoveMatching::from_checkpoint(games, options, vec![], &path).unwrap();
+        assert_eq!(resumed.completed_games(), 0);
+        resumed.restore_completed_games(&matching.completed_game_indices().collect::<Vec<_>>());
+        assert!(resumed.is_completed());
+        assert!(resumed.get_next_task(0).is_none());
+    }
+
+    #[test]
+    fn mirrored_moves_match_on_symmetric_boards() {
+        // Both stones are on the main diagonal, so mirroring the board along it leaves it
+        // unchanged
+        let board = [(7, 7), (8, 8)];
+        assert!(equivalent_moves(&board, (9, 7), (7, 9), 15));
+        assert!(!equivalent_moves(&board, (9, 7), (5, 7), 15));
+        assert!(!equivalent_moves(&[(7, 7), (8, 7)], (9, 7), (7, 9), 15));
+
+        for symmetry in [false, true] {
+            let options = MatchingOptions {
+                symmetry,
+                ..MatchingOptions::default()
+            };
+            let mut engine = Engine::open_engine(0, &mock_engine("9,7")).unwrap();
+            let outcome = match_position(&mut engine, &board, false, (7, 9), &options)
+                .unwrap()
+                .unwrap();
+            assert_eq!(outcome.engine_move, (9, 7));
+            assert_eq!(outcome.matched, symmetry);
+        }
+    }
+
+    #[test]
+    fn full_runs_complete_every_position() {
+        let games: Arc<[Game]> = (0..20).map(|n| game(1800, 2000, moves(n))).collect();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+        match_all(&matching, &mut engine);
+        assert!(matching.is_completed());
+        assert_eq!(matching.completed_positions(), matching.total_positions());
+        let results = matching.results();
+        let total = results
+            .matches
+            .iter()
+            .map(|(_, _, total)| *total as u64)
+            .sum::<u64>();
+        assert_eq!(total, matching.total_positions());
+    }
+
+    #[test]
+    fn games_without_positions_are_skipped() {
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(1))].into();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+        matching.task(0, 0).match_challenge(&mut engine).unwrap();
+        assert!(matching.is_completed());
+        assert_eq!(matching.total_positions(), 0);
+        assert_eq!(matching.completed_positions(), 0);
+
+        let games: Arc<[Game]> = vec![game(1800, 2000, moves(1))].into();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        assert!(matching.get_next_task(0).is_none());
+        assert!(matching.is_completed());
+    }
+
+    #[test]
+    fn workers_are_shown_matching_the_game_of_their_task() {
+        let games: Arc<[Game]> =
+            vec![game(1800, 2000, moves(1)), game(1800, 2000, moves(20))].into();
+        let matching = MoveMatching::from_games(games, MatchingOptions::default());
+        let task = matching.get_next_task(2).unwrap();
+        assert_eq!(task.game_index, 1);
+        let workers = matching.worker_statuses();
+        assert_eq!(workers.len(), 3);
+        assert_eq!(workers[2].state, WorkerState::Matching);
+        assert_eq!(workers[2].game_index, Some(1));
+        assert_eq!(workers[0].state, WorkerState::Idle);
+    }
+}
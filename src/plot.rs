@@ -1,146 +1,1403 @@
 use plotters::{
-    backend::BitMapBackend,
+    backend::{BitMapBackend, DrawingBackend, SVGBackend},
     chart::ChartBuilder,
+    coord::Shift,
     coord::{combinators::IntoLinspace, ranged1d::IntoSegmentedCoord},
+    drawing::DrawingArea,
     drawing::IntoDrawingArea,
-    element::Rectangle,
+    element::{EmptyElement, ErrorBar, Rectangle, Text},
     series::{Histogram, LineSeries},
     style::*,
 };
-use std::path::Path;
-
-use crate::db::Game;
-
-pub fn plot_rating_distribution<'a, P: AsRef<Path>>(path: P, games: &[Game]) {
-    let rating_distribution_file = BitMapBackend::new(&path, (1024, 720)).into_drawing_area();
-    rating_distribution_file.fill(&WHITE).unwrap();
-
-    let mut rating_distribution_chart = ChartBuilder::on(&rating_distribution_file)
-        .x_label_area_size(40)
-        .y_label_area_size(40)
-        .margin(5)
-        .caption("Renju ratings distribution", ("sans-serif", 50.0))
-        .build_cartesian_2d((1400u32..2900u32).into_segmented(), 0u32..300u32)
-        .unwrap();
-    rating_distribution_chart
-        .configure_mesh()
-        .disable_x_mesh()
-        .bold_line_style(WHITE.mix(0.3))
-        .y_desc("Number of games")
-        .x_desc("Rating")
-        .axis_desc_style(("sans-serif", 15))
-        .draw()
-        .unwrap();
-    rating_distribution_chart
-        .draw_series(
-            Histogram::vertical(&rating_distribution_chart)
-                .style(RED.mix(0.5).filled())
-                .data(
-                    games
-                        .iter()
-                        .map(|g| (g.black_elo as u32, 1))
-                        .chain(games.iter().map(|g| (g.white_elo as u32, 1))),
-                ),
-        )
-        .unwrap();
-    rating_distribution_file
-        .present()
-        .expect("Could not open file");
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    ops::Range,
+    path::Path,
+};
+
+use crate::{
+    db::Game,
+    stats::{two_proportion_z_test, wilson_interval},
+};
+
+/// Size and fonts of plots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlotStyle {
+    /// Size of the image, in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Font family of captions.
+    pub title_font: String,
+    /// Font family of labels, legends and other text.
+    pub label_font: String,
+    /// Size of labels, other text being scaled from it.
+    pub font_size: f64,
+}
+impl Default for PlotStyle {
+    fn default() -> Self {
+        // Generic families are resolved to a font available on the system, unlike named ones
+        Self {
+            width: 1024,
+            height: 720,
+            title_font: "sans-serif".to_string(),
+            label_font: "sans-serif".to_string(),
+            font_size: 30.,
+        }
+    }
+}
+impl PlotStyle {
+    fn title(&self, scale: f64) -> (&str, f64) {
+        (&self.title_font, self.font_size * scale)
+    }
+
+    fn label(&self, scale: f64) -> (&str, f64) {
+        (&self.label_font, self.font_size * scale)
+    }
+}
+
+/// Something drawn by [`with_backend`], on any backend.
+trait Drawing {
+    fn draw<DB: DrawingBackend>(self, area: &DrawingArea<DB, Shift>);
+}
+
+/// Draws to an SVG file if the path has the `.svg` extension, and to a bitmap image, such as
+/// PNG, otherwise.
+fn with_backend<P: AsRef<Path>>(path: P, style: &PlotStyle, drawing: impl Drawing) {
+    let path = path.as_ref();
+    let size = (style.width, style.height);
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg"))
+    {
+        let area = SVGBackend::new(path, size).into_drawing_area();
+        drawing.draw(&area);
+        area.present().expect("Could not open file");
+    } else {
+        let area = BitMapBackend::new(path, size).into_drawing_area();
+        drawing.draw(&area);
+        area.present().expect("Could not open file");
+    }
+}
+
+/// Number of players of each bucket of `bracket_width` rating points, counting both players of
+/// every game, by start of bucket. Buckets range from the lowest rating to the highest one, so
+/// that some of them may be empty.
+pub fn rating_distribution(games: &[Game], bracket_width: u32) -> Vec<(u32, usize)> {
+    let width = bracket_width.max(1) as u64;
+    let mut counts = BTreeMap::<u64, usize>::new();
+    for game in games {
+        for elo in [game.black_elo, game.white_elo] {
+            *counts.entry(elo / width).or_default() += 1;
+        }
+    }
+    let (Some(&first), Some(&last)) = (counts.keys().next(), counts.keys().next_back()) else {
+        return vec![];
+    };
+    (first..=last)
+        .map(|bucket| {
+            let count = counts.get(&bucket).copied().unwrap_or(0);
+            ((bucket * width) as u32, count)
+        })
+        .collect()
+}
+
+/// Saves a [`rating_distribution`] as CSV rows of `elo,players`, where `elo` is the start of
+/// each bucket.
+pub fn save_rating_distribution<P: AsRef<Path>>(
+    path: P,
+    distribution: &[(u32, usize)],
+) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["elo", "players"])?;
+    for (elo, players) in distribution {
+        writer.write_record([elo.to_string(), players.to_string()])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn plot_rating_distribution<P: AsRef<Path>>(path: P, games: &[Game], style: &PlotStyle) {
+    with_backend(path, style, RatingDistribution(games, style))
+}
+
+/// Range of ratings an axis shows so that all of `elos` fall within it, with a margin of 100
+/// points on both sides and rounded to hundreds, or [`RESULTS_RATING_RANGE`] without ratings.
+pub fn rating_axis_range(elos: impl Iterator<Item = u64>) -> Range<u32> {
+    let (min, max) = elos.fold((u64::MAX, 0), |(min, max), elo| {
+        (min.min(elo), max.max(elo))
+    });
+    if min > max {
+        return RESULTS_RATING_RANGE;
+    }
+    let start = (min / 100 * 100).saturating_sub(100);
+    let end = (max / 100 + 2) * 100;
+    start as u32..end as u32
+}
+
+struct RatingDistribution<'a>(&'a [Game], &'a PlotStyle);
+impl Drawing for RatingDistribution<'_> {
+    fn draw<DB: DrawingBackend>(self, rating_distribution_file: &DrawingArea<DB, Shift>) {
+        let RatingDistribution(games, style) = self;
+        rating_distribution_file.fill(&WHITE).unwrap();
+
+        let ratings = rating_axis_range(games.iter().flat_map(|g| [g.black_elo, g.white_elo]));
+        let distribution = rating_distribution(games, 1);
+        let max_count = distribution.iter().map(|(_, c)| *c).max().unwrap_or(0);
+        let max_count = (max_count as f64 * 1.1).ceil().max(10.) as u32;
+
+        let mut rating_distribution_chart = ChartBuilder::on(rating_distribution_file)
+            .x_label_area_size(40)
+            .y_label_area_size(40)
+            .margin(5)
+            .caption("Renju ratings distribution", style.title(5. / 3.))
+            .build_cartesian_2d(ratings.into_segmented(), 0u32..max_count)
+            .unwrap();
+        rating_distribution_chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .bold_line_style(WHITE.mix(0.3))
+            .y_desc("Number of games")
+            .x_desc("Rating")
+            .axis_desc_style(style.label(0.5))
+            .draw()
+            .unwrap();
+        rating_distribution_chart
+            .draw_series(
+                Histogram::vertical(&rating_distribution_chart)
+                    .style(RED.mix(0.5).filled())
+                    .data(
+                        distribution
+                            .iter()
+                            .map(|(elo, count)| (*elo, *count as u32)),
+                    ),
+            )
+            .unwrap();
+    }
 }
 
 pub struct Performance<'a, I: Iterator<Item = (u64, u32, u32)>> {
     pub name: &'a str,
     pub matches: I,
 }
+/// Ratings shown by charts without any rating to fit, see [`rating_axis_range`].
+pub const RESULTS_RATING_RANGE: Range<u32> = 1400..2900;
+
+/// What happens to ratings outside of the range of [`Brackets`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfRange {
+    /// Results of these ratings are not counted.
+    #[default]
+    Drop,
+    /// Results of these ratings are counted in the first or last bracket.
+    Clamp,
+}
+
+/// Brackets of ratings results are aggregated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Brackets {
+    pub width: u32,
+    /// Start of the first bracket.
+    pub min: u32,
+    /// End of the last bracket, which is shorter when the range is not a multiple of `width`.
+    pub max: u32,
+    pub out_of_range: OutOfRange,
+}
+impl Default for Brackets {
+    fn default() -> Self {
+        Self {
+            width: 100,
+            min: 1100,
+            max: 2900,
+            out_of_range: OutOfRange::Drop,
+        }
+    }
+}
+impl Brackets {
+    pub fn count(&self) -> usize {
+        self.max.saturating_sub(self.min).div_ceil(self.width) as usize
+    }
+
+    /// Rating the bracket at this index starts at.
+    pub fn start(&self, index: usize) -> u32 {
+        self.min + index as u32 * self.width
+    }
+
+    /// Index of the bracket this rating falls in, or `None` for dropped ratings outside of the
+    /// brackets, which can happen without virtual games.
+    pub fn index(&self, elo: u64) -> Option<usize> {
+        let last = self.count().checked_sub(1)?;
+        let index = if elo < self.min as u64 {
+            None
+        } else {
+            Some(((elo - self.min as u64) / self.width as u64) as usize).filter(|i| *i <= last)
+        };
+        match (index, self.out_of_range) {
+            (Some(index), _) => Some(index),
+            (None, OutOfRange::Drop) => None,
+            (None, OutOfRange::Clamp) if elo < self.min as u64 => Some(0),
+            (None, OutOfRange::Clamp) => Some(last),
+        }
+    }
+}
+
+/// Matches and totals of results aggregated in brackets, as `(start, matches, total)` for
+/// each bracket.
+pub fn bracketize(
+    results: impl Iterator<Item = (u64, u32, u32)>,
+    brackets: &Brackets,
+) -> Vec<(u32, u32, u32)> {
+    let mut bracketized = (0..brackets.count())
+        .map(|i| (brackets.start(i), 0, 0))
+        .collect::<Vec<_>>();
+    for (elo, matches, total) in results {
+        let Some(i) = brackets.index(elo) else {
+            continue;
+        };
+        bracketized[i].1 += matches;
+        bracketized[i].2 += total;
+    }
+    bracketized
+}
+
+/// Accuracy in percent of each bracket of [`bracketize`], skipping empty brackets rather than
+/// turning them into NaN.
+pub fn bracket_accuracies(brackets: &[(u32, u32, u32)]) -> impl Iterator<Item = (u32, f64)> + '_ {
+    brackets
+        .iter()
+        .filter(|(_, _, total)| *total > 0)
+        .map(|(bracket, matches, total)| (*bracket, *matches as f64 / *total as f64 * 100f64))
+}
+
+/// Accuracy in percent of each bracket of ratings of weighted results, skipping empty brackets.
+pub fn weighted_bracket_accuracies(
+    results: impl Iterator<Item = (u64, f64, f64)>,
+    brackets: &Brackets,
+) -> Vec<(u32, f64)> {
+    let mut bracketized = vec![(0., 0.); brackets.count()];
+    for (elo, matches, total) in results {
+        if let Some(i) = brackets.index(elo) {
+            bracketized[i].0 += matches;
+            bracketized[i].1 += total;
+        }
+    }
+    bracketized
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, total))| *total > 0.)
+        .map(|(i, (matches, total))| (brackets.start(i), matches / total * 100.))
+        .collect()
+}
+
+/// Confidence level of the error bars of [`plot_results`].
+const ERROR_BARS_CONFIDENCE: f64 = 0.95;
+
+/// Optional elements of plots of results.
+#[derive(Debug, Clone, Default)]
+pub struct PlotOptions {
+    /// Draws the 95% Wilson confidence interval of the accuracy of each bracket.
+    pub error_bars: bool,
+    /// Writes the number of positions of each bracket next to its point.
+    pub show_counts: bool,
+    pub style: PlotStyle,
+}
+
+/// Plots the accuracy of each bracket of results, over the ratings of the brackets holding
+/// positions, see [`results_axis_range`].
 pub fn plot_results<'a, P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
     path: P,
     perfs: impl Iterator<Item = Performance<'a, I>>,
+    brackets: &Brackets,
+    options: &PlotOptions,
+) {
+    let perfs = bracketize_performances(perfs, brackets);
+    let ratings = results_axis_range(&perfs);
+    draw_results(
+        path,
+        perfs,
+        options,
+        ratings,
+        "Move matching performance".to_string(),
+    )
+}
+
+/// Name and [`bracketize`]d results of an experiment.
+type BracketizedPerformance<'a> = (&'a str, Vec<(u32, u32, u32)>);
+
+/// Aggregates the results of each experiment in brackets.
+fn bracketize_performances<'a, I: Iterator<Item = (u64, u32, u32)>>(
+    perfs: impl Iterator<Item = Performance<'a, I>>,
+    brackets: &Brackets,
+) -> Vec<BracketizedPerformance<'a>> {
+    perfs
+        .map(|Performance { name, matches }| (name, bracketize(matches, brackets)))
+        .collect()
+}
+
+/// Range of ratings showing every bracket holding positions of these results, see
+/// [`rating_axis_range`].
+fn results_axis_range(perfs: &[BracketizedPerformance]) -> Range<u32> {
+    rating_axis_range(
+        perfs
+            .iter()
+            .flat_map(|(_, brackets)| brackets)
+            .filter(|(_, _, total)| *total > 0)
+            .map(|(bracket, _, _)| *bracket as u64),
+    )
+}
+
+/// Plots results like [`plot_results`], zoomed on a range of ratings.
+pub fn plot_results_in_range<'a, P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    perfs: impl Iterator<Item = Performance<'a, I>>,
+    brackets: &Brackets,
+    options: &PlotOptions,
+    ratings: Range<u32>,
+) {
+    let caption = format!(
+        "Move matching performance ({}-{})",
+        ratings.start, ratings.end
+    );
+    let perfs = bracketize_performances(perfs, brackets);
+    draw_results(path, perfs, options, ratings, caption)
+}
+
+/// Plots the brackets of `perfs` falling in the range of ratings.
+fn draw_results<P: AsRef<Path>>(
+    path: P,
+    perfs: Vec<BracketizedPerformance>,
+    options: &PlotOptions,
+    ratings: Range<u32>,
+    caption: String,
 ) {
-    const PALETTE: [RGBColor; 3] = [GREEN, BLUE, RED];
-    let move_matching_file = BitMapBackend::new(&path, (1024, 720)).into_drawing_area();
-    move_matching_file.fill(&WHITE).unwrap();
-
-    let mut move_matching_chart = ChartBuilder::on(&move_matching_file)
-        .x_label_area_size(80)
-        .y_label_area_size(80)
-        .margin(5)
-        .caption("Move matching performance", ("Calibri", 60))
-        .build_cartesian_2d(1400u32..2900u32, (0f64..80f64).step(5f64))
-        .unwrap();
-    move_matching_chart
-        .configure_mesh()
-        .disable_x_mesh()
-        .bold_line_style(WHITE.mix(0.3))
-        .x_desc("Rating")
-        .x_label_style(("Calibri", 30))
-        .y_label_style(("Calibri", 30))
-        .axis_desc_style(("Calibri", 40))
-        .draw()
-        .unwrap();
-
-    for (i, Performance { name, matches }) in perfs.into_iter().enumerate() {
-        let mut brackets_performance = vec![(0, 0); 18];
-        for (elo, matches, total) in matches {
-            let bracket_index = (elo / 100) - 11;
-            brackets_performance[bracket_index as usize].0 += matches;
-            brackets_performance[bracket_index as usize].1 += total;
+    with_backend(
+        path,
+        &options.style,
+        Results {
+            perfs,
+            options,
+            ratings,
+            caption,
+        },
+    )
+}
+
+struct Results<'a, 'b> {
+    perfs: Vec<BracketizedPerformance<'a>>,
+    options: &'b PlotOptions,
+    ratings: Range<u32>,
+    caption: String,
+}
+impl Drawing for Results<'_, '_> {
+    fn draw<DB: DrawingBackend>(self, move_matching_file: &DrawingArea<DB, Shift>) {
+        let Self {
+            perfs,
+            options,
+            ratings,
+            caption,
+        } = self;
+        const PALETTE: [RGBColor; 3] = [GREEN, BLUE, RED];
+        move_matching_file.fill(&WHITE).unwrap();
+
+        let mut move_matching_chart = ChartBuilder::on(move_matching_file)
+            .x_label_area_size(80)
+            .y_label_area_size(80)
+            .margin(5)
+            .caption(caption, options.style.title(2.))
+            .build_cartesian_2d(ratings.clone(), (0f64..80f64).step(5f64))
+            .unwrap();
+        move_matching_chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .bold_line_style(WHITE.mix(0.3))
+            .x_desc("Rating")
+            .x_label_style(options.style.label(1.))
+            .y_label_style(options.style.label(1.))
+            .axis_desc_style(options.style.label(4. / 3.))
+            .draw()
+            .unwrap();
+
+        for (i, (name, bracketized)) in perfs.into_iter().enumerate() {
+            let color = PALETTE[i % PALETTE.len()];
+            let bracketized = bracketized
+                .into_iter()
+                .filter(|(bracket, _, _)| ratings.contains(bracket))
+                .collect::<Vec<_>>();
+            if options.error_bars {
+                move_matching_chart
+                    .draw_series(bracketized.iter().filter(|(_, _, total)| *total > 0).map(
+                        |(bracket, matches, total)| {
+                            let (low, high) =
+                                wilson_interval(*matches, *total, ERROR_BARS_CONFIDENCE);
+                            ErrorBar::new_vertical(
+                                *bracket,
+                                low * 100.,
+                                *matches as f64 / *total as f64 * 100.,
+                                high * 100.,
+                                color.filled().stroke_width(2),
+                                10,
+                            )
+                        },
+                    ))
+                    .unwrap();
+            }
+            if options.show_counts {
+                // Counts are written alternately above and below points, so that those of
+                // neighbouring brackets, or of the same bracket of the next experiment, do not
+                // overlap
+                move_matching_chart
+                    .draw_series(
+                        bracket_accuracies(&bracketized)
+                            .zip(bracketized.iter().filter(|(_, _, total)| *total > 0))
+                            .enumerate()
+                            .map(|(j, ((bracket, accuracy), (_, _, total)))| {
+                                let offset = if (i + j) % 2 == 0 { -30 } else { 12 };
+                                EmptyElement::at((bracket, accuracy))
+                                    + Text::new(
+                                        total.to_string(),
+                                        (-10, offset),
+                                        options.style.label(0.6).into_font().color(&color),
+                                    )
+                            }),
+                    )
+                    .unwrap();
+            }
+            move_matching_chart
+                .draw_series(
+                    LineSeries::new(
+                        bracket_accuracies(&bracketized).collect::<Vec<_>>(),
+                        color.filled().stroke_width(3),
+                    )
+                    .point_size(5),
+                )
+                .unwrap()
+                .label(name.to_string())
+                .legend(move |(x, y)| {
+                    Rectangle::new([(x - 30, y + 3), (x, y)], color.filled().stroke_width(3))
+                });
         }
-        let brackets_performance = brackets_performance
-            .into_iter()
-            .map(|(matches, total)| matches as f64 / total as f64)
-            .collect::<Vec<_>>();
 
         move_matching_chart
-            .draw_series(
-                LineSeries::new(
-                    brackets_performance
-                        .into_iter()
-                        .enumerate()
-                        .filter_map(|(i, v)| {
-                            let bracket = (i as u32 + 11) * 100;
-                            let accuracy = v * 100f64;
-                            if bracket < 1500 {
-                                None
-                            } else {
-                                Some((bracket, accuracy))
-                            }
-                        }),
-                    PALETTE[i].filled().stroke_width(3),
+            .configure_series_labels()
+            .position(plotters::chart::SeriesLabelPosition::UpperRight)
+            .margin(40)
+            .legend_area_size(10)
+            .border_style(BLACK.mix(0.1))
+            .background_style(WHITE)
+            .label_font(options.style.label(1.))
+            .draw()
+            .unwrap();
+    }
+}
+
+/// Plots the accuracy of experiments by band of moves, from results saved with
+/// [`save_move_results`]. Each point is drawn at the first move number of its band.
+pub fn plot_move_results<'a, P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    perfs: impl Iterator<Item = Performance<'a, I>>,
+    options: &PlotOptions,
+) {
+    let perfs = perfs
+        .map(|Performance { name, matches }| {
+            let mut bands = matches
+                .map(|(start, matches, total)| (start as u32, matches, total))
+                .collect::<Vec<_>>();
+            bands.sort();
+            (name, bands)
+        })
+        .collect::<Vec<_>>();
+    with_backend(path, &options.style, MoveResults { perfs, options })
+}
+
+/// The name of an experiment and its `(move number, matches, total)` results, sorted.
+type MoveSeries<'a> = (&'a str, Vec<(u32, u32, u32)>);
+
+struct MoveResults<'a, 'b> {
+    perfs: Vec<MoveSeries<'a>>,
+    options: &'b PlotOptions,
+}
+impl Drawing for MoveResults<'_, '_> {
+    fn draw<DB: DrawingBackend>(self, area: &DrawingArea<DB, Shift>) {
+        let Self { perfs, options } = self;
+        const PALETTE: [RGBColor; 3] = [GREEN, BLUE, RED];
+        area.fill(&WHITE).unwrap();
+
+        // Leaves room after the last band, which has no end
+        let last_move = perfs
+            .iter()
+            .flat_map(|(_, bands)| bands.last())
+            .map(|(start, _, _)| *start)
+            .max()
+            .unwrap_or(0);
+        let mut chart = ChartBuilder::on(area)
+            .x_label_area_size(80)
+            .y_label_area_size(80)
+            .margin(5)
+            .caption("Performance by move number", options.style.title(2.))
+            .build_cartesian_2d(0..last_move + 10, (0f64..80f64).step(5f64))
+            .unwrap();
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .bold_line_style(WHITE.mix(0.3))
+            .x_desc("Move number")
+            .x_label_style(options.style.label(1.))
+            .y_label_style(options.style.label(1.))
+            .axis_desc_style(options.style.label(4. / 3.))
+            .draw()
+            .unwrap();
+
+        for (i, (name, bands)) in perfs.iter().enumerate() {
+            let color = PALETTE[i % PALETTE.len()];
+            if options.error_bars {
+                chart
+                    .draw_series(bands.iter().filter(|(_, _, total)| *total > 0).map(
+                        |(start, matches, total)| {
+                            let (low, high) =
+                                wilson_interval(*matches, *total, ERROR_BARS_CONFIDENCE);
+                            ErrorBar::new_vertical(
+                                *start,
+                                low * 100.,
+                                *matches as f64 / *total as f64 * 100.,
+                                high * 100.,
+                                color.filled().stroke_width(2),
+                                10,
+                            )
+                        },
+                    ))
+                    .unwrap();
+            }
+            chart
+                .draw_series(
+                    LineSeries::new(
+                        bracket_accuracies(bands).collect::<Vec<_>>(),
+                        color.filled().stroke_width(3),
+                    )
+                    .point_size(5),
                 )
-                .point_size(5),
-            )
-            .unwrap()
-            .label(name.to_string())
-            .legend(move |(x, y)| {
+                .unwrap()
+                .label(name.to_string())
+                .legend(move |(x, y)| {
+                    Rectangle::new([(x - 30, y + 3), (x, y)], color.filled().stroke_width(3))
+                });
+        }
+
+        chart
+            .configure_series_labels()
+            .position(plotters::chart::SeriesLabelPosition::UpperRight)
+            .margin(40)
+            .legend_area_size(10)
+            .border_style(BLACK.mix(0.1))
+            .background_style(WHITE)
+            .label_font(options.style.label(1.))
+            .draw()
+            .unwrap();
+    }
+}
+
+/// Difference of accuracy in percentage points between the results `b` and `a` in each
+/// bracket, as `(start, difference, p-value)` where the p-value is given by
+/// [`two_proportion_z_test`]. Brackets missing from one of them are empty, and brackets
+/// without positions in either of them have no difference.
+pub fn accuracy_differences(
+    a: impl Iterator<Item = (u64, u32, u32)>,
+    b: impl Iterator<Item = (u64, u32, u32)>,
+    brackets: &Brackets,
+) -> Vec<(u32, Option<f64>, Option<f64>)> {
+    bracketize(a, brackets)
+        .into_iter()
+        .zip(bracketize(b, brackets))
+        .map(|((start, matches_a, total_a), (_, matches_b, total_b))| {
+            let difference = (total_a > 0 && total_b > 0).then(|| {
+                let accuracy_a = matches_a as f64 / total_a as f64;
+                let accuracy_b = matches_b as f64 / total_b as f64;
+                (accuracy_b - accuracy_a) * 100.
+            });
+            let p_value = two_proportion_z_test((matches_a, total_a), (matches_b, total_b))
+                .map(|(_, p_value)| p_value);
+            (start, difference, p_value)
+        })
+        .collect()
+}
+
+/// Plots the difference of accuracy between the experiments `b` and `a` in each bracket as
+/// bars around a zero line, so that brackets where `b` is less accurate show as negative bars.
+/// With a significance level `alpha`, bars of brackets where the difference is significant
+/// are marked with a star.
+pub fn plot_accuracy_differences<P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    a: Performance<'_, I>,
+    b: Performance<'_, I>,
+    brackets: &Brackets,
+    alpha: Option<f64>,
+    style: &PlotStyle,
+) {
+    let caption = format!("Accuracy of {} minus {}", b.name, a.name);
+    let differences = accuracy_differences(a.matches, b.matches, brackets);
+    with_backend(
+        path,
+        style,
+        AccuracyDifferences {
+            differences,
+            brackets,
+            alpha,
+            style,
+            caption,
+        },
+    )
+}
+
+struct AccuracyDifferences<'a> {
+    differences: Vec<(u32, Option<f64>, Option<f64>)>,
+    brackets: &'a Brackets,
+    alpha: Option<f64>,
+    style: &'a PlotStyle,
+    caption: String,
+}
+impl Drawing for AccuracyDifferences<'_> {
+    fn draw<DB: DrawingBackend>(self, area: &DrawingArea<DB, Shift>) {
+        let Self {
+            differences,
+            brackets,
+            alpha,
+            style,
+            caption,
+        } = self;
+        area.fill(&WHITE).unwrap();
+        // Brackets without a difference have no bar
+        let differences = differences
+            .into_iter()
+            .filter_map(|(start, difference, p_value)| Some((start, difference?, p_value)))
+            .collect::<Vec<_>>();
+
+        // Symmetric around zero, by steps of 5 points
+        let extent = differences
+            .iter()
+            .map(|(_, difference, _)| difference.abs())
+            .fold(0., f64::max);
+        let extent = ((extent / 5.).ceil() * 5.).max(5.);
+        let mut chart = ChartBuilder::on(area)
+            .x_label_area_size(80)
+            .y_label_area_size(120)
+            .margin(5)
+            .caption(caption, style.title(2.))
+            .build_cartesian_2d(brackets.min..brackets.max, -extent..extent)
+            .unwrap();
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .bold_line_style(WHITE.mix(0.3))
+            .x_desc("Rating")
+            .y_desc("Difference (points)")
+            .x_label_style(style.label(1.))
+            .y_label_style(style.label(1.))
+            .axis_desc_style(style.label(4. / 3.))
+            .draw()
+            .unwrap();
+
+        chart
+            .draw_series(differences.iter().map(|(start, difference, _)| {
+                let color = if *difference < 0. { RED } else { GREEN };
+                let end = (start + brackets.width).min(brackets.max);
+                // Leaves a gap between the bars of neighbouring brackets
+                let margin = (end - start) / 10;
                 Rectangle::new(
-                    [(x - 30, y + 3), (x, y)],
-                    PALETTE[i].filled().stroke_width(3),
+                    [(start + margin, 0.), (end - margin, *difference)],
+                    color.mix(0.7).filled(),
                 )
-            });
+            }))
+            .unwrap();
+        chart
+            .draw_series(LineSeries::new(
+                [(brackets.min, 0.), (brackets.max, 0.)],
+                BLACK.stroke_width(2),
+            ))
+            .unwrap();
+        if let Some(alpha) = alpha {
+            chart
+                .draw_series(
+                    differences
+                        .iter()
+                        .filter(|(_, _, p_value)| p_value.is_some_and(|p| p < alpha))
+                        .map(|(start, difference, _)| {
+                            let middle = (start + (start + brackets.width).min(brackets.max)) / 2;
+                            // Above positive bars and below negative ones
+                            let offset = if *difference < 0. { 5 } else { -35 };
+                            EmptyElement::at((middle, *difference))
+                                + Text::new("*", (-8, offset), style.label(1.2).into_font())
+                        }),
+                )
+                .unwrap();
+        }
+    }
+}
+
+/// Prefix of the header line holding the checksum of the data rows in a results file.
+const CHECKSUM_PREFIX: &str = "#crc32=";
+
+/// A row of a results file.
+#[derive(Serialize, Deserialize)]
+struct ResultRow {
+    elo: u64,
+    matches: u32,
+    total: u32,
+}
+
+/// A row of a per-player results file.
+#[derive(Serialize, Deserialize)]
+struct PlayerRow {
+    player: u64,
+    matches: u32,
+    total: u32,
+    /// Name of the player, empty when the database does not give one or in files written
+    /// before names were saved.
+    #[serde(default)]
+    name: String,
+}
+
+/// A row of a results file by move number.
+#[derive(Serialize, Deserialize)]
+struct MoveRow {
+    /// First move number of the band of moves.
+    #[serde(rename = "move")]
+    move_number: u64,
+    matches: u32,
+    total: u32,
+}
+
+/// A row of a results file by distance to the human move.
+#[derive(Serialize, Deserialize)]
+struct DistanceRow {
+    distance: u64,
+    /// Number of positions where the engine played at most `distance` away from the human move.
+    matches: u32,
+    total: u32,
+}
+
+/// A row of the list of games completed by a run.
+#[derive(Serialize, Deserialize)]
+struct CompletedGameRow {
+    /// Index of the game in the database, as used by game logs.
+    game: usize,
+}
+
+/// A row of a weighted results file.
+#[derive(Serialize, Deserialize)]
+struct WeightedRow {
+    elo: u64,
+    matches: f64,
+    total: f64,
+}
+
+/// A row of a combined results file.
+#[derive(Serialize, Deserialize)]
+struct CombinedRow {
+    name: String,
+    elo: u64,
+    matches: u32,
+    total: u32,
+}
+
+/// Results saved as JSON.
+#[derive(Serialize, Deserialize)]
+struct JsonResults {
+    name: String,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    brackets: Vec<ResultRow>,
+}
+
+/// Format results are saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResultsFormat {
+    #[default]
+    Csv,
+    Json,
+}
+impl ResultsFormat {
+    /// Extension of the files saved in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+impl std::str::FromStr for ResultsFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown format `{s}`, expected csv or json")),
+        }
     }
+}
 
-    move_matching_chart
-        .configure_series_labels()
-        .position(plotters::chart::SeriesLabelPosition::UpperRight)
-        .margin(40)
-        .legend_area_size(10)
-        .border_style(BLACK.mix(0.1))
-        .background_style(WHITE)
-        .label_font(("Calibri", 30))
-        .draw()
-        .unwrap();
-    move_matching_file.present().expect("Could not open file");
+#[derive(Debug)]
+pub enum ResultsError {
+    IoError(std::io::Error),
+    CsvError(csv::Error),
+    JsonError(serde_json::Error),
+    InvalidChecksum(String),
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// Results were saved with another value of a setting they depend on.
+    SettingsMismatch {
+        key: String,
+        saved: String,
+        current: String,
+    },
+}
+impl std::fmt::Display for ResultsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "could not read results: {e}"),
+            Self::CsvError(e) => write!(f, "malformed results: {e}"),
+            Self::JsonError(e) => write!(f, "malformed results: {e}"),
+            Self::InvalidChecksum(s) => write!(f, "malformed checksum header `{s}`"),
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "results are corrupted (checksum {found:08x}, expected {expected:08x})"
+            ),
+            Self::SettingsMismatch {
+                key,
+                saved,
+                current,
+            } => write!(
+                f,
+                "results were saved with {key} {saved} instead of {current}, resume with the same settings or use another name"
+            ),
+        }
+    }
 }
+impl std::error::Error for ResultsError {}
 
-pub fn save_results<'a, P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+/// Saves results as CSV rows of `elo,matches,total` sorted by elo, under a header row and
+/// preceded by `#key=value` lines of metadata and a checksum of the rows.
+///
+/// The file is written to a temporary path then renamed, so that an interrupted save never
+/// leaves a truncated file behind.
+pub fn save_results<P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
     path: P,
-    Performance { matches, .. }: Performance<'a, I>,
+    Performance { matches, .. }: Performance<'_, I>,
+    metadata: &[(&str, String)],
 ) {
-    let mut csv = csv::Writer::from_path(path).unwrap();
+    let mut matches = matches.collect::<Vec<_>>();
+    matches.sort();
+    let rows = matches.into_iter().map(|(elo, matches, total)| ResultRow {
+        elo,
+        matches,
+        total,
+    });
+    write_checksummed(path, metadata, &serialize_rows(rows))
+}
 
-    for (elo, matches, total) in matches {
-        csv.write_record(&[&elo.to_string(), &matches.to_string(), &total.to_string()])
-            .unwrap();
-        csv.flush().unwrap();
+/// Saves results as a JSON object holding the name of the experiment, the metadata and the
+/// results of each bracket sorted by elo:
+/// `{"name": .., "metadata": {..}, "brackets": [{"elo": .., "matches": .., "total": ..}]}`.
+///
+/// Like [`save_results`], the file is written to a temporary path then renamed.
+pub fn save_results_json<P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    Performance { name, matches }: Performance<'_, I>,
+    metadata: &[(&str, String)],
+) {
+    let mut matches = matches.collect::<Vec<_>>();
+    matches.sort();
+    let results = JsonResults {
+        name: name.to_string(),
+        metadata: metadata
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+        brackets: matches
+            .into_iter()
+            .map(|(elo, matches, total)| ResultRow {
+                elo,
+                matches,
+                total,
+            })
+            .collect(),
+    };
+    write_atomically(path, &serde_json::to_vec_pretty(&results).unwrap())
+}
+
+/// Saves per-player results as CSV rows of `player,matches,total,name` sorted by player id,
+/// like [`save_results`], naming players from `names` by id.
+pub fn save_player_results<P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    Performance { matches, .. }: Performance<'_, I>,
+    names: &HashMap<u64, String>,
+    metadata: &[(&str, String)],
+) {
+    let mut matches = matches.collect::<Vec<_>>();
+    matches.sort();
+    let rows = matches
+        .into_iter()
+        .map(|(player, matches, total)| PlayerRow {
+            player,
+            matches,
+            total,
+            name: names.get(&player).cloned().unwrap_or_default(),
+        });
+    write_checksummed(path, metadata, &serialize_rows(rows))
+}
+
+/// Saves results by band of moves as CSV rows of `move,matches,total`, where `move` is the first
+/// move number of the band, like [`save_results`].
+pub fn save_move_results<P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    Performance { matches, .. }: Performance<'_, I>,
+    metadata: &[(&str, String)],
+) {
+    let mut matches = matches.collect::<Vec<_>>();
+    matches.sort();
+    let rows = matches
+        .into_iter()
+        .map(|(move_number, matches, total)| MoveRow {
+            move_number,
+            matches,
+            total,
+        });
+    write_checksummed(path, metadata, &serialize_rows(rows))
+}
+
+/// Saves results by distance to the human move as CSV rows of `distance,matches,total`, where
+/// `matches` counts the positions where the engine played at most `distance` away from the
+/// human move, like [`save_results`].
+pub fn save_distance_results<P: AsRef<Path>>(
+    path: P,
+    results: impl Iterator<Item = (u64, u32, u32)>,
+    metadata: &[(&str, String)],
+) {
+    let mut results = results.collect::<Vec<_>>();
+    results.sort();
+    let rows = results
+        .into_iter()
+        .map(|(distance, matches, total)| DistanceRow {
+            distance,
+            matches,
+            total,
+        });
+    write_checksummed(path, metadata, &serialize_rows(rows))
+}
+
+/// Saves the indices of completed games as CSV rows of `game`, like [`save_results`], so that a
+/// run can be resumed exactly.
+pub fn save_completed_games<P: AsRef<Path>>(
+    path: P,
+    games: impl Iterator<Item = usize>,
+    metadata: &[(&str, String)],
+) {
+    let rows = games.map(|game| CompletedGameRow { game });
+    write_checksummed(path, metadata, &serialize_rows(rows))
+}
+
+/// Saves weighted results as CSV rows of `elo,matches,total`, where matches and totals are sums
+/// of weights, like [`save_results`].
+pub fn save_weighted_results<P: AsRef<Path>>(
+    path: P,
+    results: impl Iterator<Item = (u64, f64, f64)>,
+    metadata: &[(&str, String)],
+) {
+    let mut results = results.collect::<Vec<_>>();
+    results.sort_by_key(|(elo, _, _)| *elo);
+    let rows = results
+        .into_iter()
+        .map(|(elo, matches, total)| WeightedRow {
+            elo,
+            matches,
+            total,
+        });
+    write_checksummed(path, metadata, &serialize_rows(rows))
+}
+
+/// Saves the results of several experiments to a single file, as CSV rows of
+/// `name,elo,matches,total`, checksummed like [`save_results`].
+pub fn save_combined_results<'a, P: AsRef<Path>, I: Iterator<Item = (u64, u32, u32)>>(
+    path: P,
+    perfs: impl Iterator<Item = Performance<'a, I>>,
+) {
+    let rows = perfs.flat_map(|Performance { name, matches }| {
+        let mut matches = matches.collect::<Vec<_>>();
+        matches.sort();
+        matches
+            .into_iter()
+            .map(move |(elo, matches, total)| CombinedRow {
+                name: name.to_string(),
+                elo,
+                matches,
+                total,
+            })
+    });
+    write_checksummed(path, &[], &serialize_rows(rows))
+}
+
+/// Serializes rows to CSV, under a header row naming their fields.
+fn serialize_rows<R: Serialize>(rows: impl Iterator<Item = R>) -> Vec<u8> {
+    let mut csv = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        csv.serialize(row).unwrap();
+    }
+    csv.into_inner().unwrap()
+}
+
+/// Deserializes the CSV rows of a file written by [`write_checksummed`], by the name of their
+/// fields.
+///
+/// Files saved before header rows were written are deserialized by position instead: they are
+/// recognized by their first field not being `first_field`.
+fn deserialize_rows<R: DeserializeOwned, P: AsRef<Path>>(
+    path: P,
+    first_field: &str,
+) -> Result<Vec<R>, ResultsError> {
+    let data = read_checksummed(path)?;
+    let has_headers = data
+        .split([',', '\n'])
+        .next()
+        .is_some_and(|field| field.trim() == first_field);
+    csv::ReaderBuilder::new()
+        .has_headers(has_headers)
+        .from_reader(data.as_bytes())
+        .into_deserialize()
+        .collect::<Result<_, _>>()
+        .map_err(ResultsError::CsvError)
+}
+
+/// Writes rows preceded by `#key=value` lines of metadata and a checksum of the rows.
+///
+/// The file is written to a temporary path then renamed, so that an interrupted save never
+/// leaves a truncated file behind.
+fn write_checksummed<P: AsRef<Path>>(path: P, metadata: &[(&str, String)], data: &[u8]) {
+    let mut content = vec![];
+    for (key, value) in metadata {
+        writeln!(content, "#{key}={}", value.replace('\n', " ")).unwrap();
+    }
+    writeln!(content, "{CHECKSUM_PREFIX}{:08x}", crc32fast::hash(data)).unwrap();
+    content.extend_from_slice(data);
+    write_atomically(path, &content)
+}
+
+/// Writes a file to a temporary path then renames it.
+fn write_atomically<P: AsRef<Path>>(path: P, content: &[u8]) {
+    let mut temporary_path = path.as_ref().as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let mut file = std::fs::File::create(&temporary_path).unwrap();
+    file.write_all(content).unwrap();
+    file.sync_all().unwrap();
+    std::fs::rename(&temporary_path, path).unwrap();
+}
+
+/// Reads the rows of a file written by [`write_checksummed`], refusing them if their checksum
+/// does not match.
+///
+/// Metadata lines are skipped, and files without a checksum header are loaded as-is.
+fn read_checksummed<P: AsRef<Path>>(path: P) -> Result<String, ResultsError> {
+    let content = std::fs::read_to_string(path).map_err(ResultsError::IoError)?;
+    let mut data = content.as_str();
+    let mut checksum = None;
+    while data.starts_with('#') {
+        let (line, rest) = data.split_once('\n').unwrap_or((data, ""));
+        if let Some(c) = line.strip_prefix(CHECKSUM_PREFIX) {
+            checksum = Some(c);
+        }
+        data = rest;
+    }
+    if let Some(checksum) = checksum {
+        let expected = u32::from_str_radix(checksum.trim(), 16)
+            .map_err(|_| ResultsError::InvalidChecksum(checksum.to_string()))?;
+        let found = crc32fast::hash(data.as_bytes());
+        if expected != found {
+            return Err(ResultsError::ChecksumMismatch { expected, found });
+        }
+    }
+    Ok(data.to_string())
+}
+
+/// Loads the `key=value` metadata saved along with results by [`save_results`] or
+/// [`save_results_json`], without checking the results.
+pub fn load_metadata<P: AsRef<Path>>(path: P) -> Result<Vec<(String, String)>, ResultsError> {
+    if path
+        .as_ref()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+    {
+        let content = std::fs::read(path).map_err(ResultsError::IoError)?;
+        let results: JsonResults =
+            serde_json::from_slice(&content).map_err(ResultsError::JsonError)?;
+        return Ok(results.metadata.into_iter().collect());
+    }
+    let content = std::fs::read_to_string(path).map_err(ResultsError::IoError)?;
+    Ok(content
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .filter(|line| !line.starts_with(CHECKSUM_PREFIX))
+        .filter_map(|line| line[1..].split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+/// Loads results saved with [`save_results`], refusing them if their checksum does not match,
+/// or with [`save_results_json`] when the path ends with `.json`.
+///
+/// Metadata lines are skipped, and files without a checksum header are loaded as-is.
+pub fn load_results<P: AsRef<Path>>(path: P) -> Result<Vec<(u64, u32, u32)>, ResultsError> {
+    if path
+        .as_ref()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+    {
+        let content = std::fs::read(path).map_err(ResultsError::IoError)?;
+        let results: JsonResults =
+            serde_json::from_slice(&content).map_err(ResultsError::JsonError)?;
+        return Ok(results
+            .brackets
+            .into_iter()
+            .map(|row| (row.elo, row.matches, row.total))
+            .collect());
+    }
+    let rows = deserialize_rows::<ResultRow, _>(path, "elo")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.elo, row.matches, row.total))
+        .collect())
+}
+
+/// Loads per-player results saved with [`save_player_results`].
+pub fn load_player_results<P: AsRef<Path>>(path: P) -> Result<Vec<(u64, u32, u32)>, ResultsError> {
+    let rows = deserialize_rows::<PlayerRow, _>(path, "player")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.player, row.matches, row.total))
+        .collect())
+}
+
+/// Loads results by band of moves saved with [`save_move_results`].
+pub fn load_move_results<P: AsRef<Path>>(path: P) -> Result<Vec<(u64, u32, u32)>, ResultsError> {
+    let rows = deserialize_rows::<MoveRow, _>(path, "move")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.move_number, row.matches, row.total))
+        .collect())
+}
+
+/// Loads results by distance to the human move saved with [`save_distance_results`].
+pub fn load_distance_results<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<(u64, u32, u32)>, ResultsError> {
+    let rows = deserialize_rows::<DistanceRow, _>(path, "distance")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.distance, row.matches, row.total))
+        .collect())
+}
+
+/// Loads the indices of completed games saved with [`save_completed_games`].
+pub fn load_completed_games<P: AsRef<Path>>(path: P) -> Result<Vec<usize>, ResultsError> {
+    let rows = deserialize_rows::<CompletedGameRow, _>(path, "game")?;
+    Ok(rows.into_iter().map(|row| row.game).collect())
+}
+
+/// Loads weighted results saved with [`save_weighted_results`].
+pub fn load_weighted_results<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<(u64, f64, f64)>, ResultsError> {
+    let rows = deserialize_rows::<WeightedRow, _>(path, "elo")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.elo, row.matches, row.total))
+        .collect())
+}
+
+/// The name of an experiment and its results, as `(elo, matches, total)` rows.
+pub type Experiment = (String, Vec<(u64, u32, u32)>);
+
+/// Loads results saved with [`save_combined_results`], as the experiments in the order they
+/// were saved.
+pub fn load_combined_results<P: AsRef<Path>>(path: P) -> Result<Vec<Experiment>, ResultsError> {
+    let mut perfs: Vec<Experiment> = vec![];
+    for row in deserialize_rows::<CombinedRow, _>(path, "name")? {
+        let results = (row.elo, row.matches, row.total);
+        match perfs.iter_mut().find(|(name, _)| *name == row.name) {
+            Some((_, perf)) => perf.push(results),
+            None => perfs.push((row.name, vec![results])),
+        }
+    }
+    Ok(perfs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    #[test]
+    fn player_results_are_named() {
+        let path = temp_path("players.csv");
+        let names = HashMap::from([(1, "Ando Meritee".to_string())]);
+        save_player_results(
+            &path,
+            Performance {
+                name: "players",
+                matches: [(2, 1, 4), (1, 3, 7)].into_iter(),
+            },
+            &names,
+            &[],
+        );
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with("player,matches,total,name\n1,3,7,Ando Meritee\n2,1,4,\n"));
+        assert_eq!(
+            load_player_results(&path).unwrap(),
+            vec![(1, 3, 7), (2, 1, 4)]
+        );
+
+        // Files written before names were saved can still be read
+        write_checksummed(&path, &[], b"player,matches,total\n1,3,7\n");
+        assert_eq!(load_player_results(&path).unwrap(), vec![(1, 3, 7)]);
+    }
+
+    #[test]
+    fn json_results_round_trip() {
+        let path = temp_path("results.json");
+        save_results_json(
+            &path,
+            Performance {
+                name: "json",
+                matches: [(2000, 5, 9), (1800, 3, 7)].into_iter(),
+            },
+            &[("engine", "yixin".to_string())],
+        );
+        let content: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(content["name"], "json");
+        assert_eq!(content["brackets"][0]["elo"], 1800);
+        assert_eq!(content["brackets"][1]["matches"], 5);
+        assert_eq!(
+            load_results(&path).unwrap(),
+            vec![(1800, 3, 7), (2000, 5, 9)]
+        );
+        assert_eq!(
+            load_metadata(&path).unwrap(),
+            vec![("engine".to_string(), "yixin".to_string())]
+        );
+    }
+
+    #[test]
+    fn results_outside_of_the_usual_ratings_are_plotted() {
+        let results = [(1050, 3, 10), (2000, 5, 10), (3200, 7, 10)];
+        let perf = || Performance {
+            name: "wide",
+            matches: results.into_iter(),
+        };
+        for out_of_range in [OutOfRange::Drop, OutOfRange::Clamp] {
+            let brackets = Brackets {
+                out_of_range,
+                ..Brackets::default()
+            };
+            let perfs = bracketize_performances(std::iter::once(perf()), &brackets);
+            let plotted = perfs[0].1.iter().filter(|(_, _, total)| *total > 0);
+            let starts = plotted.map(|(bracket, _, _)| *bracket).collect::<Vec<_>>();
+            let expected = match out_of_range {
+                OutOfRange::Drop => vec![2000],
+                OutOfRange::Clamp => vec![1100, 2000, 2800],
+            };
+            assert_eq!(starts, expected);
+            let axis = results_axis_range(&perfs);
+            assert!(starts.iter().all(|start| axis.contains(start)), "{axis:?}");
+        }
+
+        let brackets = Brackets {
+            min: 1000,
+            max: 3300,
+            ..Brackets::default()
+        };
+        let perfs = bracketize_performances(std::iter::once(perf()), &brackets);
+        assert_eq!(results_axis_range(&perfs), 900..3400);
+        plot_results(
+            temp_path("wide.png"),
+            std::iter::once(perf()),
+            &brackets,
+            &PlotOptions::default(),
+        );
+    }
+
+    #[test]
+    fn brackets_missing_from_a_file_are_kept_empty() {
+        let a = vec![(1250, 5, 10), (1550, 5, 10)];
+        let b = vec![(1550, 8, 10)];
+        let brackets = Brackets::default();
+        let differences = accuracy_differences(a.iter().copied(), b.iter().copied(), &brackets);
+        assert_eq!(differences.len(), brackets.count());
+        let difference = |start| {
+            let (_, difference, _) = differences.iter().find(|(s, _, _)| *s == start).unwrap();
+            *difference
+        };
+        assert_eq!(difference(1200), None);
+        assert_eq!(difference(1400), None);
+        assert!((difference(1500).unwrap() - 30.).abs() < 1e-9);
+        plot_accuracy_differences(
+            temp_path("differences.png"),
+            Performance {
+                name: "a",
+                matches: a.into_iter(),
+            },
+            Performance {
+                name: "b",
+                matches: b.into_iter(),
+            },
+            &brackets,
+            Some(0.05),
+            &PlotStyle::default(),
+        );
+    }
+
+    #[test]
+    fn axes_contain_every_rating() {
+        let results = (1000..=3500)
+            .step_by(250)
+            .map(|elo| (elo, 1, 2))
+            .collect::<Vec<_>>();
+        let ratings = rating_axis_range(results.iter().map(|(elo, _, _)| *elo));
+        assert!(results
+            .iter()
+            .all(|(elo, _, _)| ratings.contains(&(*elo as u32))));
+
+        let brackets = Brackets {
+            min: 1000,
+            max: 3600,
+            ..Brackets::default()
+        };
+        let perf = Performance {
+            name: "wide",
+            matches: results.iter().copied(),
+        };
+        let perfs = bracketize_performances(std::iter::once(perf), &brackets);
+        let axis = results_axis_range(&perfs);
+        assert!(results
+            .iter()
+            .all(|(elo, _, _)| axis.contains(&brackets.start(brackets.index(*elo).unwrap()))));
+    }
+
+    #[test]
+    fn empty_brackets_are_not_plotted() {
+        let brackets = bracketize(
+            [
+                (1250, 1, 4),
+                (1710, 3, 4),
+                (1790, 1, 4),
+                (2600, 0, 5),
+                (2700, 2, 0),
+            ]
+            .into_iter(),
+            &Brackets::default(),
+        );
+        assert_eq!(brackets.len(), 18);
+        let accuracies = bracket_accuracies(&brackets).collect::<Vec<_>>();
+        assert_eq!(accuracies, vec![(1200, 25.), (1700, 50.), (2600, 0.)]);
+        assert!(accuracies.iter().all(|(_, accuracy)| accuracy.is_finite()));
     }
 }
@@ -0,0 +1,113 @@
+//! # Engine profiles
+//! Engines do not all speak the exact same dialect of the protocol: for instance the
+//! integer sent with `INFO rule` differs between Yixin and Gomocup engines. A profile
+//! describes these engine-specific details.
+//!
+//! Profiles can be loaded from a file of `key = value` lines, where `#` starts a comment:
+//! ```text
+//! name = rapfi
+//! rule.freestyle = 0
+//! rule.standard = 1
+//! rule.renju = 4
+//! book_marker = BOOK
+//! deterministic.pondering = 0
+//! shutdown = yxstop
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use crate::protocol::Rule;
+
+#[derive(Debug, Clone, Default)]
+pub struct EngineProfile {
+    pub name: String,
+    /// Integer sent with `INFO rule` for each rule, overriding the Yixin mapping.
+    pub rules: HashMap<Rule, u8>,
+    /// Text found in the `MESSAGE` or `DEBUG` lines of the engine when it plays a move from its
+    /// opening book. Book moves cannot be detected when this is not set.
+    pub book_marker: Option<String>,
+    /// `INFO` commands making the engine deterministic (disabling pondering, learning...),
+    /// sent in deterministic mode.
+    pub deterministic_info: Vec<(String, String)>,
+    /// Commands sent, in order, before `END` when closing the engine, for instance to flush
+    /// persistent files.
+    pub shutdown: Vec<String>,
+}
+impl EngineProfile {
+    /// Profile for Yixin and engines following its protocol.
+    pub fn yixin() -> Self {
+        Self {
+            name: "yixin".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Profile for engines following the Gomocup protocol, where rules are a bitmask.
+    pub fn gomocup() -> Self {
+        Self {
+            name: "gomocup".to_string(),
+            rules: HashMap::from([(Rule::Freestyle, 0), (Rule::Standard, 1), (Rule::Renju, 4)]),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the built-in profile with the given name, or loads the profile file at this path.
+    pub fn from_name_or_path(name: &str) -> std::io::Result<Self> {
+        match name {
+            "yixin" => Ok(Self::yixin()),
+            "gomocup" => Ok(Self::gomocup()),
+            path => Self::load(path),
+        }
+    }
+
+    /// Loads a profile from a file of `key = value` lines.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut profile = Self {
+            name: path.as_ref().display().to_string(),
+            ..Default::default()
+        };
+        let content = std::fs::read_to_string(path)?;
+        for (i, line) in content.lines().enumerate() {
+            let invalid = |message: &str| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {}: {message}", i + 1),
+                )
+            };
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            match key.split_once('.') {
+                None if key == "name" => profile.name = value.to_string(),
+                None if key == "book_marker" => profile.book_marker = Some(value.to_string()),
+                None if key == "shutdown" => profile.shutdown.push(value.to_string()),
+                Some(("deterministic", info)) => profile
+                    .deterministic_info
+                    .push((info.to_string(), value.to_string())),
+                Some(("rule", rule)) => {
+                    let rule = rule.parse::<Rule>().map_err(|e| invalid(&e))?;
+                    let value = value
+                        .parse::<u8>()
+                        .map_err(|_| invalid("rule values must be integers"))?;
+                    profile.rules.insert(rule, value);
+                }
+                _ => return Err(invalid(&format!("unknown key `{key}`"))),
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Integer to send with `INFO rule` to configure this rule, falling back to the Yixin
+    /// mapping when the profile does not override it.
+    pub fn rule_value(&self, rule: Rule) -> u8 {
+        self.rules
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.yixin_value())
+    }
+}
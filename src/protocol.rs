@@ -4,10 +4,20 @@
 //! to interface with various engines easily.
 
 use std::{
-    io::{BufRead, BufReader, Write},
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    path::PathBuf,
     process::{Child, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+use crate::profile::EngineProfile;
+
 #[derive(Debug)]
 pub enum EngineError {
     Error(String),
@@ -15,56 +25,811 @@ pub enum EngineError {
     ResponseParseError(ResponseParseErr),
     IoError(std::io::Error),
     UnexpectedResponse(Response),
+    /// The engine played on a point of the board that is already occupied.
+    IllegalMove((u8, u8)),
+    /// The engine did not answer in time, it is probably hung.
+    Timeout,
+    /// The engine did not acknowledge `START`, so it cannot be used.
+    NotStarted(Box<EngineError>),
+    /// A command configuring the engine when it is opened could not be sent.
+    Configuration {
+        command: String,
+        error: Box<EngineError>,
+    },
+    /// An error along with the last lines the engine wrote to stderr, which usually explain it.
+    WithStderr {
+        error: Box<EngineError>,
+        stderr: Vec<String>,
+    },
+}
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error(s) => write!(f, "engine error: {s}"),
+            Self::Unknown(s) => write!(f, "engine did not understand a command: {s}"),
+            Self::ResponseParseError(e) => write!(f, "malformed response: {e:?}"),
+            Self::IoError(e) => write!(f, "could not communicate with the engine: {e}"),
+            Self::UnexpectedResponse(r) => write!(f, "unexpected response: {r:?}"),
+            Self::IllegalMove(m) => write!(f, "engine played on the occupied point {m:?}"),
+            Self::Timeout => write!(f, "engine did not answer in time"),
+            Self::NotStarted(e) => write!(f, "engine did not acknowledge START: {e}"),
+            Self::Configuration { command, error } => {
+                write!(
+                    f,
+                    "could not configure the engine with `{command}`: {error}"
+                )
+            }
+            Self::WithStderr { error, stderr } => {
+                write!(f, "{error}\nlast lines of the engine's stderr:")?;
+                for line in stderr {
+                    write!(f, "\n  {line}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+impl std::error::Error for EngineError {}
+impl EngineError {
+    /// Whether the engine cannot be used anymore, because it hung or the connection to it is
+    /// lost, rather than having answered a command wrongly.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            Self::IoError(_) | Self::Timeout => true,
+            Self::WithStderr { error, .. }
+            | Self::NotStarted(error)
+            | Self::Configuration { error, .. } => error.is_fatal(),
+            _ => false,
+        }
+    }
+}
+
+/// Game rules an engine can be configured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Rule {
+    Freestyle,
+    Standard,
+    #[default]
+    Renju,
+}
+impl Rule {
+    /// Integer identifying this rule in the Yixin protocol.
+    pub fn yixin_value(self) -> u8 {
+        match self {
+            Self::Freestyle => 0,
+            Self::Standard => 1,
+            Self::Renju => 2,
+        }
+    }
+}
+impl std::str::FromStr for Rule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "freestyle" => Ok(Self::Freestyle),
+            "standard" => Ok(Self::Standard),
+            "renju" => Ok(Self::Renju),
+            _ => Err(format!(
+                "unknown rule `{s}`, expected freestyle, standard or renju"
+            )),
+        }
+    }
+}
+
+/// Description of an engine, as answered to `ABOUT`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineInfo {
+    pub name: String,
+    pub version: String,
+    pub author: String,
+}
+impl EngineInfo {
+    /// Parses an answer to `ABOUT` made of comma-separated `key="value"` pairs, tolerating
+    /// missing quotes. Answers without any pair give the default, empty, description.
+    pub fn from_about(line: &str) -> Self {
+        let mut info = Self::default();
+        info.update(line);
+        info
+    }
+
+    /// Sets the fields given by a line of `key="value"` pairs, keeping the others.
+    fn update(&mut self, line: &str) {
+        let mut rest = line.trim();
+        while let Some((key, value)) = rest.split_once('=') {
+            let value = value.trim_start();
+            let (value, next) = match value.strip_prefix('"') {
+                Some(quoted) => {
+                    let (value, next) = quoted.split_once('"').unwrap_or((quoted, ""));
+                    (value, next.split_once(',').map_or("", |(_, next)| next))
+                }
+                None => value.split_once(',').unwrap_or((value, "")),
+            };
+            let value = value.trim().to_string();
+            match key.trim().to_lowercase().as_str() {
+                "name" => self.name = value,
+                "version" => self.version = value,
+                "author" => self.author = value,
+                _ => {}
+            }
+            rest = next;
+        }
+    }
+}
+
+/// Settings used to open and configure an engine.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// Command line used to start the engine.
+    pub command: String,
+    /// Time the engine can use to think on each move, in milliseconds.
+    pub move_time: u32,
+    pub rule: Rule,
+    /// Number of rows and columns of the board.
+    pub board_size: u8,
+    pub profile: EngineProfile,
+    /// Number of threads the engine searches with.
+    pub threads: u32,
+    /// Memory the engine can use, mostly for its hash table, in bytes. The engine decides when
+    /// `None`.
+    pub max_memory: Option<u64>,
+    /// Configures the engine to be as deterministic as possible: single-threaded, and with the
+    /// profile's deterministic `INFO` commands.
+    pub deterministic: bool,
+    /// Time the engine is given to answer `START`, which includes loading its opening book or
+    /// evaluation weights.
+    pub start_timeout: Duration,
+    /// Directory the engine is started in, where it may look for its weights or configuration.
+    /// The current directory is used when `None`.
+    pub workdir: Option<PathBuf>,
+    /// Environment variables set for the engine, on top of the inherited environment.
+    pub env: Vec<(String, String)>,
+}
+
+/// Number of lines of the engine's stderr kept to explain errors.
+const STDERR_LINES: usize = 20;
+
+/// Time an engine is given to answer a command on top of twice its move time, after which it
+/// is considered hung.
+const RESPONSE_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Time an engine is given to exit on its own after `END` before being killed.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Splits an engine command line into the program and its arguments, at whitespace outside of
+/// single or double quotes, so that `"C:\Program Files\engine.exe" --name 'my engine'` runs
+/// `C:\Program Files\engine.exe` with the arguments `--name` and `my engine`.
+///
+/// Unlike a shell, backslashes are kept as is, since they separate the directories of Windows
+/// paths.
+fn split_command(command: &str) -> Result<Vec<String>, &'static str> {
+    let mut parts = vec![];
+    let mut part = None::<String>;
+    let mut quote = None;
+    for c in command.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => part.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                part.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => parts.extend(part.take()),
+            None => part.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote in the engine command");
+    }
+    parts.extend(part);
+    Ok(parts)
+}
+
+/// Channel the protocol is spoken over with an engine.
+trait Transport: Send {
+    /// Stream commands are written to.
+    fn input(&mut self) -> &mut dyn Write;
+    /// Whether the engine cannot be talked to anymore.
+    fn is_closed(&mut self) -> bool;
+    /// Releases the engine once it was sent `END`.
+    fn close(&mut self, id: usize) -> std::io::Result<()>;
+}
+
+impl Transport for Child {
+    fn input(&mut self) -> &mut dyn Write {
+        self.stdin.as_mut().unwrap()
+    }
+
+    fn is_closed(&mut self) -> bool {
+        matches!(self.try_wait(), Ok(Some(_)))
+    }
+
+    /// Gives the engine [`SHUTDOWN_GRACE_PERIOD`] to exit on its own, then kills it and waits
+    /// for it to exit, so that it does not linger as a zombie process.
+    fn close(&mut self, id: usize) -> std::io::Result<()> {
+        let start = Instant::now();
+        while start.elapsed() < SHUTDOWN_GRACE_PERIOD {
+            match self.try_wait() {
+                Ok(Some(_)) => return Ok(()),
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(_) => break,
+            }
+        }
+        log::warn!("[{id}] Engine did not exit, killing it");
+        // The engine may exit on its own just before being killed, which is not an error.
+        if let Err(e) = self.kill() {
+            if !matches!(self.try_wait(), Ok(Some(_))) {
+                return Err(e);
+            }
+        }
+        self.wait().map(|_| ())
+    }
+}
+
+/// Connection to an engine running as a server, which keeps running once disconnected.
+struct TcpTransport {
+    stream: TcpStream,
+    closed: bool,
+}
+impl Transport for TcpTransport {
+    fn input(&mut self) -> &mut dyn Write {
+        &mut self.stream
+    }
+
+    fn is_closed(&mut self) -> bool {
+        self.closed
+    }
+
+    fn close(&mut self, _: usize) -> std::io::Result<()> {
+        self.closed = true;
+        match self.stream.shutdown(Shutdown::Both) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Prefix of engine commands designating an engine server to connect to instead, as in
+/// `tcp://localhost:5000`.
+pub const TCP_PREFIX: &str = "tcp://";
+
+/// Prefix of engine commands designating a [`MockEngine`] instead, followed by the moves it
+/// answers with as in `mock://7,7;8,7`.
+#[cfg(any(test, feature = "testing"))]
+pub const MOCK_PREFIX: &str = "mock://";
+
+/// The self-test engine running in a thread of the process, talked to through in-memory pipes
+/// so that matching can be tested deterministically without spawning an engine.
+///
+/// It answers its first move requests with its script, then plays the first empty point of the
+/// board, see [`crate::selftest::run_scripted_engine`].
+#[cfg(any(test, feature = "testing"))]
+struct MockEngine {
+    input: MockPipe,
+    engine: Option<std::thread::JoinHandle<()>>,
+}
+#[cfg(any(test, feature = "testing"))]
+impl MockEngine {
+    /// Starts the engine, returning the transport and the stream of what it writes.
+    fn start(script: Vec<(u8, u8)>) -> (Self, MockPipeReader) {
+        let (input, engine_input) = mock_pipe();
+        let (engine_output, output) = mock_pipe();
+        let engine = std::thread::spawn(move || {
+            let _ = crate::selftest::run_scripted_engine(
+                BufReader::new(engine_input),
+                engine_output,
+                &script,
+            );
+        });
+        (
+            Self {
+                input,
+                engine: Some(engine),
+            },
+            output,
+        )
+    }
+}
+#[cfg(any(test, feature = "testing"))]
+impl Transport for MockEngine {
+    fn input(&mut self) -> &mut dyn Write {
+        &mut self.input
+    }
+
+    fn is_closed(&mut self) -> bool {
+        self.engine.as_ref().is_none_or(|e| e.is_finished())
+    }
+
+    fn close(&mut self, _: usize) -> std::io::Result<()> {
+        self.input.0 = None;
+        if let Some(engine) = self.engine.take() {
+            let _ = engine.join();
+        }
+        Ok(())
+    }
+}
+
+/// Writing end of an in-memory pipe, closed once dropped or set to `None`.
+#[cfg(any(test, feature = "testing"))]
+struct MockPipe(Option<mpsc::Sender<Vec<u8>>>);
+#[cfg(any(test, feature = "testing"))]
+impl Write for MockPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &self.0 {
+            Some(sender) if sender.send(buf.to_vec()).is_ok() => Ok(buf.len()),
+            _ => Err(std::io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reading end of an in-memory pipe.
+#[cfg(any(test, feature = "testing"))]
+struct MockPipeReader {
+    receiver: Receiver<Vec<u8>>,
+    /// Part of the last chunk received that was not read yet.
+    pending: VecDeque<u8>,
+}
+#[cfg(any(test, feature = "testing"))]
+impl Read for MockPipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.receiver.recv() {
+                Ok(chunk) => self.pending.extend(chunk),
+                Err(_) => return Ok(0),
+            }
+        }
+        self.pending.read(buf)
+    }
 }
 
+#[cfg(any(test, feature = "testing"))]
+fn mock_pipe() -> (MockPipe, MockPipeReader) {
+    let (sender, receiver) = mpsc::channel();
+    (
+        MockPipe(Some(sender)),
+        MockPipeReader {
+            receiver,
+            pending: VecDeque::new(),
+        },
+    )
+}
+
+/// Opens an engine answering each line it receives with `answer`, which writes its
+/// responses. The end of `yxboard` commands, which are not answered, is skipped.
+#[cfg(test)]
+pub(crate) fn fake_engine(mut answer: impl FnMut(&str, &mut dyn Write) + Send + 'static) -> Engine {
+    let (input, engine_input) = mock_pipe();
+    let (mut engine_output, output) = mock_pipe();
+    let engine = std::thread::spawn(move || {
+        let mut yixin_board = false;
+        for line in BufReader::new(engine_input).lines().map_while(Result::ok) {
+            match line.trim() {
+                "yxboard" => yixin_board = true,
+                "DONE" if yixin_board => yixin_board = false,
+                line => answer(line, &mut engine_output),
+            }
+        }
+    });
+    let transport = MockEngine {
+        input,
+        engine: Some(engine),
+    };
+    Engine::configure(
+        0,
+        Box::new(transport),
+        output,
+        Arc::default(),
+        &crate::test_utils::mock_engine(""),
+    )
+    .unwrap()
+}
+
+/// A running engine, shut down when dropped.
 pub struct Engine {
     pub id: usize,
-    process: Child,
+    transport: Box<dyn Transport>,
+    /// Lines sent by the engine, read by a separate thread so that reading them can time out.
+    responses: Receiver<std::io::Result<String>>,
+    /// Time after which an engine not answering a command is considered hung.
+    timeout: Duration,
+    /// Last lines the engine wrote to stderr.
+    stderr: Arc<Mutex<VecDeque<String>>>,
+    /// Number of rows and columns of the board, moves outside of it are refused.
+    board_size: u8,
+    /// Commands sent before `END` when shutting the engine down, see [`EngineProfile`].
+    shutdown: Vec<String>,
+    /// Marker identifying book moves in the engine's messages, see [`EngineProfile`].
+    book_marker: Option<String>,
+    /// Debug lines and messages sent by the engine while answering the last command.
+    messages: Vec<String>,
+    /// Errors sent by the engine before its answer to `ABOUT`, refusing its configuration.
+    configuration_errors: Vec<String>,
+    info: EngineInfo,
+    /// Whether lines of the answer to `ABOUT` may still arrive, when it spans several lines
+    /// or came too late. They are skipped when reading the answers to the next commands.
+    reading_about: bool,
+    /// Stones of the last board sent, see [`BoardLines`].
+    board_lines: BoardLines,
+    /// Text of the command being sent, kept to reuse its allocation.
+    output: String,
+    /// Text of every command sent, checked by tests.
+    #[cfg(test)]
+    pub(crate) sent: Vec<String>,
 }
 impl Engine {
-    /// Opens a new engine.
-    pub fn open_engine(id: usize, command: &str, move_time: u32) -> Result<Self, std::io::Error> {
-        let mut command_parts = command.split_whitespace();
-        let mut command = std::process::Command::new(command_parts.next().unwrap());
-        command.args(command_parts);
+    /// Opens a new engine, or connects to it if its command starts with [`TCP_PREFIX`].
+    pub fn open_engine(id: usize, config: &EngineConfig) -> Result<Self, EngineError> {
+        if let Some(address) = config.command.strip_prefix(TCP_PREFIX) {
+            return Self::connect_tcp(id, address, config);
+        }
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(script) = config.command.strip_prefix(MOCK_PREFIX) {
+            return Self::open_mock(id, script, config);
+        }
+        let invalid_command = |error| {
+            EngineError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidInput, error))
+        };
+        let command_parts = split_command(&config.command).map_err(invalid_command)?;
+        let Some((program, args)) = command_parts.split_first() else {
+            return Err(invalid_command("empty engine command"));
+        };
+        let mut command = std::process::Command::new(program);
+        command.args(args).envs(config.env.iter().cloned());
+        if let Some(workdir) = &config.workdir {
+            command.current_dir(workdir);
+        }
 
-        let process = command
+        let mut process = command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .spawn()?;
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                EngineError::IoError(std::io::Error::new(
+                    e.kind(),
+                    format!("could not start `{program}`: {e}"),
+                ))
+            })?;
 
-        let mut engine = Self { process, id };
+        let (Some(stdout), Some(stderr_pipe)) = (process.stdout.take(), process.stderr.take())
+        else {
+            unreachable!("the output of the engine is piped")
+        };
+        let stderr_pipe = BufReader::new(stderr_pipe);
+        let stderr = Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_LINES)));
+        {
+            let stderr = stderr.clone();
+            std::thread::spawn(move || {
+                for line in stderr_pipe.lines().map_while(Result::ok) {
+                    log::debug!("[{id}] stderr: {line}");
+                    let mut stderr = stderr.lock().unwrap();
+                    if stderr.len() == STDERR_LINES {
+                        stderr.pop_front();
+                    }
+                    stderr.push_back(line)
+                }
+            });
+        }
 
-        engine.send_command(Command::Start(15)).unwrap();
-        engine
-            .send_command(Command::Info {
-                key: "timeout_turn",
-                value: &move_time.to_string(),
-            })
-            .unwrap();
-        engine
-            .send_command(Command::Info {
-                key: "thread_num",
-                value: "1",
-            })
-            .unwrap();
-        engine
-            .send_command(Command::Info {
-                key: "rule",
-                value: "2",
+        Self::configure(id, Box::new(process), stdout, stderr, config)
+    }
+
+    /// Connects to an engine server speaking the protocol over TCP at this address.
+    pub fn connect_tcp<A: ToSocketAddrs>(
+        id: usize,
+        address: A,
+        config: &EngineConfig,
+    ) -> Result<Self, EngineError> {
+        let stream = TcpStream::connect(address).map_err(EngineError::IoError)?;
+        let output = stream.try_clone().map_err(EngineError::IoError)?;
+        Self::configure(
+            id,
+            Box::new(TcpTransport {
+                stream,
+                closed: false,
+            }),
+            output,
+            Arc::default(),
+            config,
+        )
+    }
+
+    /// Starts a [`MockEngine`] answering with the moves of `script`, separated by `;`.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn open_mock(id: usize, script: &str, config: &EngineConfig) -> Result<Self, EngineError> {
+        let script = script
+            .split(';')
+            .filter(|m| !m.trim().is_empty())
+            .map(|m| {
+                crate::selftest::parse_coordinates(m).ok_or_else(|| {
+                    EngineError::IoError(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("invalid mock engine move `{m}`"),
+                    ))
+                })
             })
-            .unwrap();
+            .collect::<Result<_, _>>()?;
+        let (transport, output) = MockEngine::start(script);
+        Self::configure(id, Box::new(transport), output, Arc::default(), config)
+    }
+
+    /// Starts reading the output of the engine, and configures it.
+    fn configure(
+        id: usize,
+        transport: Box<dyn Transport>,
+        output: impl Read + Send + 'static,
+        stderr: Arc<Mutex<VecDeque<String>>>,
+        config: &EngineConfig,
+    ) -> Result<Self, EngineError> {
+        let mut output = BufReader::new(output);
+        let (sender, responses) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let mut line = String::new();
+            match output.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) if sender.send(Ok(line)).is_ok() => {}
+                Ok(_) => break,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self {
+            responses,
+            timeout: config.start_timeout,
+            stderr,
+            transport,
+            id,
+            board_size: config.board_size,
+            book_marker: config.profile.book_marker.clone(),
+            shutdown: config.profile.shutdown.clone(),
+            messages: vec![],
+            configuration_errors: vec![],
+            reading_about: false,
+            info: EngineInfo::default(),
+            board_lines: BoardLines::default(),
+            output: String::new(),
+            #[cfg(test)]
+            sent: vec![],
+        };
+
+        // Engines may take a while to start reading commands, and an answer to `START` other
+        // than `OK` means that the board size is refused: `INFO` commands are only sent once it
+        // is acknowledged.
+        let start = engine.send_command(Command::Start(config.board_size));
+        engine.timeout = Duration::from_millis(config.move_time as u64) * 2 + RESPONSE_GRACE_PERIOD;
+        match start {
+            Ok(Response::Ok) => {}
+            Ok(r) => {
+                return Err(EngineError::NotStarted(Box::new(
+                    EngineError::UnexpectedResponse(r),
+                )))
+            }
+            Err(e) => return Err(EngineError::NotStarted(Box::new(e))),
+        }
+        engine.configure_info("timeout_turn", &config.move_time.to_string())?;
+        let threads = if config.deterministic {
+            1
+        } else {
+            config.threads
+        };
+        engine.configure_info("thread_num", &threads.to_string())?;
+        if let Some(max_memory) = config.max_memory {
+            engine.configure_info("max_memory", &max_memory.to_string())?;
+        }
+        engine.configure_info("rule", &config.profile.rule_value(config.rule).to_string())?;
+        if config.deterministic {
+            for (key, value) in &config.profile.deterministic_info {
+                engine.configure_info(key, value)?;
+            }
+        }
+        // `INFO` commands are not answered, so engines refusing one of them answer `ERROR`
+        // before answering `ABOUT`.
+        match engine.send_command(Command::About) {
+            Ok(Response::About(info)) => engine.info = info,
+            other => log::warn!("[{id}] Could not identify the engine: {other:?}"),
+        }
+        for error in &engine.configuration_errors {
+            let error_lowercase = error.to_lowercase();
+            if error_lowercase.contains("rule") {
+                log::error!(
+                    "[{id}] Engine refused rule {:?} ({}), its moves will not follow the rules of the games: {error}",
+                    config.rule,
+                    config.profile.rule_value(config.rule)
+                )
+            } else if let Some(max_memory) = config
+                .max_memory
+                .filter(|_| error_lowercase.contains("memory"))
+            {
+                log::warn!("[{id}] Engine refused to use {max_memory} bytes of memory: {error}")
+            } else {
+                log::warn!("[{id}] Engine refused its configuration: {error}")
+            }
+        }
         Ok(engine)
     }
 
-    pub fn close_engine(mut self) {
-        self.send_command(Command::End).unwrap();
-        self.process.kill().unwrap();
+    /// Sends an `INFO` command while opening the engine, identifying it in errors.
+    fn configure_info(&mut self, key: &str, value: &str) -> Result<(), EngineError> {
+        match self.send_command(Command::Info { key, value }) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(EngineError::Configuration {
+                command: format!("INFO {key} {value}"),
+                error: Box::new(error),
+            }),
+        }
+    }
+
+    /// Points of the board where black is not allowed to play after these moves, as reported
+    /// by the engine.
+    pub fn forbidden_points(&mut self, moves: &[(u8, u8)]) -> Result<Vec<(u8, u8)>, EngineError> {
+        self.send_command(Command::YixinBoard(moves))?;
+        match self.send_command(Command::ShowForbidden)? {
+            Response::Forbidden(points) => Ok(points),
+            r => Err(EngineError::UnexpectedResponse(r)),
+        }
+    }
+
+    /// Resets the engine to an empty board with `RESTART`, clearing its hash with `yxhashclear`
+    /// instead if it does not support it.
+    pub fn restart(&mut self) -> Result<(), EngineError> {
+        match self.send_command(Command::Restart) {
+            Ok(Response::Ok) => Ok(()),
+            Ok(r) => Err(EngineError::UnexpectedResponse(r)),
+            Err(e) if !e.is_fatal() => {
+                log::debug!(
+                    "[{}] Could not restart the engine, clearing its hash: {e}",
+                    self.id
+                );
+                self.send_command(Command::HashClear).map(|_| ())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Number of rows and columns of the board the engine plays on.
+    pub fn board_size(&self) -> u8 {
+        self.board_size
+    }
+
+    /// Description of the engine it gave when opened.
+    pub fn info(&self) -> &EngineInfo {
+        &self.info
+    }
+
+    /// Debug lines and messages sent by the engine while answering the last command.
+    pub fn last_messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    /// Whether the engine reported its last move as coming from its opening book, or `None`
+    /// if its profile does not describe how to detect book moves.
+    pub fn played_book_move(&self) -> Option<bool> {
+        let marker = self.book_marker.as_ref()?;
+        Some(self.messages.iter().any(|m| m.contains(marker.as_str())))
+    }
+
+    /// Shuts the engine down, see [`Self::shut_down`]. Engines are also shut down when dropped,
+    /// logging errors instead.
+    pub fn close_engine(mut self) -> Result<(), EngineError> {
+        self.shut_down()
+    }
+
+    /// Sends the shutdown commands of the profile then `END`, and releases the engine. Failing
+    /// to send commands is only logged, since the engine is released anyway.
+    fn shut_down(&mut self) -> Result<(), EngineError> {
+        if self.transport.is_closed() {
+            return Ok(());
+        }
+        let input = self.transport.input();
+        for command in &self.shutdown {
+            if let Err(e) = write!(input, "{command}\r\n") {
+                log::warn!("[{}] Could not send `{command}`: {e}", self.id);
+            }
+            log::trace!("[{}] Sent: {command}", self.id);
+        }
+        if let Err(e) = self.send_command(Command::End) {
+            log::warn!("[{}] Could not send END: {e}", self.id);
+        }
+        self.transport.close(self.id).map_err(EngineError::IoError)
+    }
+
+    /// The `n` best moves of the engine on the board, best first, or none if it chose to swap
+    /// colors.
+    ///
+    /// Engines are expected to answer `yxnbest` as Yixin does: with a `MESSAGE` starting with
+    /// each candidate move, then the move it chose, which comes first. Engines answering with
+    /// one move per line instead are read until they gave `n` moves.
+    pub fn best_moves(&mut self, moves: &[(u8, u8)], n: u8) -> Result<Vec<(u8, u8)>, EngineError> {
+        self.send_command(Command::YixinBoard(moves))?;
+        self.send_command(Command::NBest(n))?;
+        let mut candidates = Vec::with_capacity(n as usize);
+        let mut best_moves = Vec::with_capacity(n as usize);
+        loop {
+            match self
+                .next_response()
+                .map_err(|error| self.with_stderr(error))?
+            {
+                Response::Message(message) => {
+                    let candidate = message.split_whitespace().next().unwrap_or_default();
+                    match Response::parse(candidate, self.board_size) {
+                        Ok(Response::Move(m)) => candidates.push(m),
+                        Err(e @ ResponseParseErr::CoordinateOutOfBounds(_)) => {
+                            return Err(EngineError::ResponseParseError(e))
+                        }
+                        _ => {}
+                    }
+                }
+                Response::Debug(_) => {}
+                Response::Move(m) | Response::Suggest(m) if !candidates.is_empty() => {
+                    candidates.retain(|c| *c != m);
+                    candidates.insert(0, m);
+                    candidates.truncate(n as usize);
+                    return Ok(candidates);
+                }
+                Response::Move(m) | Response::Suggest(m) => {
+                    best_moves.push(m);
+                    if best_moves.len() == n as usize {
+                        return Ok(best_moves);
+                    }
+                }
+                Response::Swap if best_moves.is_empty() => return Ok(vec![]),
+                r => return Err(EngineError::UnexpectedResponse(r)),
+            }
+        }
     }
 
+    /// Sends a command and waits for its response. Errors of the engine and communication
+    /// errors carry the last lines the engine wrote to stderr.
     pub fn send_command<'a>(&mut self, command: Command<'a>) -> Result<Response, EngineError> {
-        write!(self.process.stdin.as_mut().unwrap(), "{command}")
-            .map_err(|e| EngineError::IoError(e))?;
+        self.send_command_inner(command)
+            .map_err(|error| self.with_stderr(error))
+    }
+
+    /// Attaches the last lines the engine wrote to stderr to engine and communication errors.
+    fn with_stderr(&self, error: EngineError) -> EngineError {
+        let stderr = Vec::from(self.stderr.lock().unwrap().clone());
+        match error {
+            EngineError::Error(_) | EngineError::IoError(_) if !stderr.is_empty() => {
+                EngineError::WithStderr {
+                    error: Box::new(error),
+                    stderr,
+                }
+            }
+            error => error,
+        }
+    }
+
+    fn send_command_inner(&mut self, command: Command<'_>) -> Result<Response, EngineError> {
+        self.output.clear();
+        match command {
+            Command::Board(moves) | Command::YixinBoard(moves) => {
+                self.output
+                    .push_str(if matches!(command, Command::Board(_)) {
+                        "BOARD\r\n"
+                    } else {
+                        "yxboard\r\n"
+                    });
+                self.output.push_str(self.board_lines.update(moves));
+                self.output.push_str("DONE\r\n");
+            }
+            _ => {
+                use std::fmt::Write;
+                let _ = write!(self.output, "{command}");
+            }
+        }
+        let input = self.transport.input();
+        input
+            .write_all(self.output.as_bytes())
+            .and_then(|_| input.flush())
+            .map_err(EngineError::IoError)?;
+        #[cfg(test)]
+        self.sent.push(self.output.clone());
 
         log::trace!("[{}] Sent: {command}", self.id);
         if matches!(
@@ -78,44 +843,102 @@ impl Engine {
             return Ok(Response::None);
         }
 
-        let response = &mut String::new();
-        let mut reader = BufReader::new(self.process.stdout.as_mut().unwrap());
+        self.messages.clear();
+        match command {
+            Command::About => {
+                self.reading_about = true;
+                self.read_about()
+            }
+            // The answer spans several lines, read by `best_moves`
+            Command::NBest(_) => Ok(Response::None),
+            _ => self.receive(),
+        }
+    }
+
+    /// Reads lines sent by the engine until one answers the last command.
+    fn receive(&mut self) -> Result<Response, EngineError> {
         loop {
-            reader
-                .read_line(response)
-                .map_err(|e| EngineError::IoError(e))?;
-            match response
-                .parse::<Response>()
-                .map_err(EngineError::ResponseParseError)?
-            {
-                Response::Ok => {
-                    return Ok(Response::Ok);
-                }
-                Response::Move((x, y)) => {
-                    return Ok(Response::Move((x, y)));
-                }
-                Response::Suggest((x, y)) => {
-                    return Ok(Response::Move((x, y)));
+            match self.next_response()? {
+                Response::Debug(_) | Response::Message(_) => {}
+                response => return Ok(response),
+            }
+        }
+    }
+
+    /// Reads the next line sent by the engine, keeping debug lines and messages in `messages`
+    /// and turning errors of the engine into [`EngineError`]s.
+    fn next_response(&mut self) -> Result<Response, EngineError> {
+        loop {
+            let line = self.read_line()?;
+            let response = match Response::parse(&line, self.board_size) {
+                Err(_) if self.reading_about && line.contains('=') => {
+                    log::debug!("[{}] Rest of the answer to ABOUT: {}", self.id, line.trim());
+                    self.info.update(&line);
+                    continue;
                 }
+                response => response.map_err(EngineError::ResponseParseError)?,
+            };
+            if !matches!(response, Response::Debug(_) | Response::Message(_)) {
+                self.reading_about = false;
+            }
+            return match response {
                 Response::Debug(s) => {
-                    log::debug!("[{}] {s}", self.id)
+                    log::debug!("[{}] {s}", self.id);
+                    self.messages.push(s.clone());
+                    Ok(Response::Debug(s))
                 }
                 Response::Error(s) => {
                     log::error!("[{}] {s}", self.id);
-                    return Err(EngineError::Error(s));
+                    Err(EngineError::Error(s))
                 }
                 Response::Unknown(s) => {
                     log::warn!("[{}] {s}", self.id);
-                    return Err(EngineError::Unknown(s));
+                    Err(EngineError::Unknown(s))
                 }
                 Response::Message(s) => {
-                    log::trace!("[{}] {s}", self.id)
-                }
-                Response::None => {
-                    return Ok(Response::None);
+                    log::trace!("[{}] {s}", self.id);
+                    self.messages.push(s.clone());
+                    Ok(Response::Message(s))
                 }
+                other => Ok(other),
+            };
+        }
+    }
+
+    /// Waits for the next line sent by the engine, for at most its timeout.
+    fn read_line(&mut self) -> Result<String, EngineError> {
+        match self.responses.recv_timeout(self.timeout) {
+            Ok(line) => line.map_err(EngineError::IoError),
+            Err(RecvTimeoutError::Timeout) => {
+                log::error!("[{}] Engine did not answer in {:?}", self.id, self.timeout);
+                Err(EngineError::Timeout)
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(EngineError::IoError(
+                std::io::ErrorKind::UnexpectedEof.into(),
+            )),
+        }
+    }
+
+    /// Reads the answer to `ABOUT`, which is not a regular response.
+    fn read_about(&mut self) -> Result<Response, EngineError> {
+        loop {
+            let line = self.read_line()?;
+            if line.contains('=') {
+                return Ok(Response::About(EngineInfo::from_about(&line)));
+            }
+            match Response::parse(&line, self.board_size) {
+                Ok(Response::Debug(s) | Response::Message(s)) => self.messages.push(s),
+                Ok(Response::Error(s)) => self.configuration_errors.push(s),
+                _ => return Ok(Response::About(EngineInfo::default())),
             }
-            response.clear()
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if let Err(e) = self.shut_down() {
+            log::warn!("[{}] Could not close the engine: {e}", self.id)
         }
     }
 }
@@ -129,9 +952,16 @@ pub enum Command<'a> {
     ShowForbidden,
     HashClear,
     Turn((u8, u8)),
+    Takeback((u8, u8)),
     Board(&'a [(u8, u8)]),
     YixinBoard(&'a [(u8, u8)]),
-    Info { key: &'a str, value: &'a str },
+    Info {
+        key: &'a str,
+        value: &'a str,
+    },
+    /// Asks for the given number of best moves on the current board.
+    NBest(u8),
+    About,
     End,
     Restart,
 }
@@ -144,6 +974,7 @@ impl<'a> std::fmt::Display for Command<'a> {
             Self::ShowForbidden => write!(f, "yxshowforbid\r\n"),
             Self::HashClear => write!(f, "yxhashclear\r\n"),
             Self::Turn((x, y)) => write!(f, "TURN {x},{y}\r\n"),
+            Self::Takeback((x, y)) => write!(f, "TAKEBACK {x},{y}\r\n"),
             Self::Board(moves) => {
                 write!(f, "BOARD\r\n")?;
                 for (i, (x, y)) in moves.iter().enumerate() {
@@ -159,18 +990,57 @@ impl<'a> std::fmt::Display for Command<'a> {
                 write!(f, "DONE\r\n")
             }
             Self::Info { key, value } => write!(f, "INFO {key} {value}\r\n"),
+            Self::NBest(n) => write!(f, "yxnbest {n}\r\n"),
+            Self::About => write!(f, "ABOUT\r\n"),
             Self::End => write!(f, "END\r\n"),
             Self::Restart => write!(f, "RESTART\r\n"),
         }
     }
 }
 
+/// Stone lines of the last board sent to an engine.
+///
+/// Positions of a game are matched in order, so each board only adds a stone to the previous one:
+/// keeping its lines avoids formatting every stone again at each ply.
+#[derive(Default)]
+struct BoardLines {
+    moves: Vec<(u8, u8)>,
+    /// End of the line of each move in `lines`.
+    ends: Vec<usize>,
+    lines: String,
+}
+impl BoardLines {
+    /// Stone lines of `moves`, as sent between `BOARD` and `DONE`, formatting only the moves that
+    /// differ from the last board.
+    fn update(&mut self, moves: &[(u8, u8)]) -> &str {
+        use std::fmt::Write;
+
+        let common = self
+            .moves
+            .iter()
+            .zip(moves)
+            .take_while(|(cached, played)| cached == played)
+            .count();
+        self.moves.truncate(common);
+        self.ends.truncate(common);
+        self.lines.truncate(self.ends.last().copied().unwrap_or(0));
+        for (i, &(x, y)) in moves.iter().enumerate().skip(common) {
+            let _ = write!(self.lines, "{x},{y},{}\r\n", if i % 2 == 0 { 1 } else { 2 });
+            self.moves.push((x, y));
+            self.ends.push(self.lines.len());
+        }
+        &self.lines
+    }
+}
+
 #[derive(Debug)]
 pub enum ResponseParseErr {
     MissingCommand,
     MissingArgument,
     MissingCoordinate,
     InvalidCoordinate(String),
+    /// The coordinates do not fall within the board.
+    CoordinateOutOfBounds((u8, u8)),
 }
 
 /// Responses from the Renju engine to the manager.
@@ -178,13 +1048,38 @@ pub enum ResponseParseErr {
 pub enum Response {
     Ok,
     Move((u8, u8)),
+    /// A move the engine suggests without playing it, so it is not on its board.
     Suggest((u8, u8)),
+    /// The engine chooses to swap colors instead of playing, as swap opening rules allow.
+    Swap,
     Debug(String),
     Error(String),
     Unknown(String),
     Message(String),
+    /// Forbidden points, answered to `yxshowforbid`.
+    Forbidden(Vec<(u8, u8)>),
+    About(EngineInfo),
     None,
 }
+impl Response {
+    /// Parses a line sent by an engine playing on a board of this size, refusing points that
+    /// fall outside of it.
+    pub fn parse(line: &str, board_size: u8) -> Result<Self, ResponseParseErr> {
+        let response = line.parse::<Self>()?;
+        let points = match &response {
+            Self::Move(point) | Self::Suggest(point) => std::slice::from_ref(point),
+            Self::Forbidden(points) => points.as_slice(),
+            _ => &[],
+        };
+        match points
+            .iter()
+            .find(|(x, y)| *x >= board_size || *y >= board_size)
+        {
+            Some(&point) => Err(ResponseParseErr::CoordinateOutOfBounds(point)),
+            None => Ok(response),
+        }
+    }
+}
 impl std::str::FromStr for Response {
     type Err = ResponseParseErr;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -192,6 +1087,7 @@ impl std::str::FromStr for Response {
         let command = tokens.next().ok_or(ResponseParseErr::MissingCommand)?;
         Ok(match command.to_lowercase().as_str() {
             "ok" => Self::Ok,
+            "swap" => Self::Swap,
             "suggest" => {
                 let coords = tokens.next().ok_or(ResponseParseErr::MissingArgument)?;
                 let mut coords = coords.split(',');
@@ -209,6 +1105,31 @@ impl std::str::FromStr for Response {
             "error" => Self::Error(tokens.collect::<Vec<_>>().join(" ")),
             "unknown" => Self::Unknown(tokens.collect::<Vec<_>>().join(" ")),
             "message" => Self::Message(tokens.collect::<Vec<_>>().join(" ")),
+            "forbid" => {
+                // Points are written as 2 digits for x then 2 for y, ending with a dot
+                let points = tokens.collect::<String>();
+                let points = points.strip_suffix('.').unwrap_or(&points);
+                let coordinate = |digits: &[u8]| {
+                    std::str::from_utf8(digits)
+                        .ok()
+                        .and_then(|d| d.parse::<u8>().ok())
+                        .ok_or_else(|| {
+                            ResponseParseErr::InvalidCoordinate(
+                                String::from_utf8_lossy(digits).to_string(),
+                            )
+                        })
+                };
+                if points.len() % 4 != 0 {
+                    return Err(ResponseParseErr::InvalidCoordinate(points.to_string()));
+                }
+                Self::Forbidden(
+                    points
+                        .as_bytes()
+                        .chunks(4)
+                        .map(|point| Ok((coordinate(&point[..2])?, coordinate(&point[2..])?)))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
             "" => Self::None,
             coords => {
                 let mut coords = coords.split(',');
@@ -225,3 +1146,215 @@ impl std::str::FromStr for Response {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mock_engine;
+
+    /// Answers of an engine playing 7,7 on any board, describing itself with `about`.
+    fn answer(line: &str, output: &mut dyn Write, about: &str) {
+        let _ = match line {
+            "START 15" | "RESTART" => write!(output, "OK\r\n"),
+            "ABOUT" => write!(output, "{about}"),
+            "DONE" => write!(output, "7,7\r\n"),
+            _ => Ok(()),
+        };
+    }
+
+    #[test]
+    fn commands_are_split_outside_of_quotes() {
+        assert_eq!(
+            split_command("pbrain-yixin  -a  b").unwrap(),
+            ["pbrain-yixin", "-a", "b"]
+        );
+        assert_eq!(
+            split_command(r#""C:\Program Files\engine.exe" --name 'my engine' ''"#).unwrap(),
+            [r"C:\Program Files\engine.exe", "--name", "my engine", ""]
+        );
+        assert_eq!(
+            split_command(r#"engine --dir="a b"c"#).unwrap(),
+            ["engine", "--dir=a bc"]
+        );
+        assert!(split_command("engine 'my engine").is_err());
+    }
+
+    #[test]
+    fn answers_to_about_spanning_several_lines_are_skipped() {
+        let mut engine = fake_engine(|line, output| {
+            answer(line, output, "name=\"Yixin\"\r\nversion=\"2018\"\r\n")
+        });
+        assert!(matches!(
+            engine.send_command(Command::Board(&[(0, 0)])),
+            Ok(Response::Move((7, 7)))
+        ));
+        assert_eq!(engine.info().name, "Yixin");
+        assert_eq!(engine.info().version, "2018");
+    }
+
+    #[test]
+    fn late_answers_to_about_are_skipped() {
+        let mut abouts = 0;
+        let mut engine = fake_engine(move |line, output| {
+            if line == "ABOUT" {
+                abouts += 1;
+                if abouts > 1 {
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+            }
+            answer(line, output, "name=\"Yixin\"\r\n")
+        });
+        engine.timeout = Duration::from_millis(50);
+        assert!(matches!(
+            engine.send_command(Command::About),
+            Err(EngineError::Timeout)
+        ));
+        engine.timeout = Duration::from_secs(5);
+        assert!(matches!(
+            engine.send_command(Command::Board(&[(0, 0)])),
+            Ok(Response::Move((7, 7)))
+        ));
+    }
+
+    #[test]
+    fn points_outside_of_the_board_are_refused() {
+        for line in ["15,3", "SUGGEST 3,15", "FORBID 01011500.", "FORBID 0115."] {
+            assert!(
+                matches!(
+                    Response::parse(line, 15),
+                    Err(ResponseParseErr::CoordinateOutOfBounds(_))
+                ),
+                "{line}"
+            );
+        }
+        assert!(matches!(
+            Response::parse("14,14", 15),
+            Ok(Response::Move((14, 14)))
+        ));
+        assert!(matches!(
+            Response::parse("FORBID 01011400.", 15),
+            Ok(Response::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn best_moves_are_read_from_messages_until_the_chosen_move() {
+        let config = mock_engine("");
+        let mut engine = Engine::open_engine(0, &config).unwrap();
+        assert_eq!(
+            engine.best_moves(&[(0, 0)], 3).unwrap(),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        // The next answer is not mistaken for a candidate
+        assert!(matches!(
+            engine.send_command(Command::Board(&[(0, 0), (1, 0)])),
+            Ok(Response::Move((2, 0)))
+        ));
+
+        let mut engine = fake_engine(|line, output| match line {
+            "yxnbest 3" => {
+                let _ = write!(
+                    output,
+                    "MESSAGE 7,7 eval 30\r\nDEBUG depth 12\r\nMESSAGE 8,8 eval 20\r\n\
+                     MESSAGE 9,9 eval 10\r\nMESSAGE 6,6 eval 5\r\n8,8\r\n"
+                );
+            }
+            line => answer(line, output, "name=\"Yixin\"\r\n"),
+        });
+        assert_eq!(
+            engine.best_moves(&[(0, 0)], 3).unwrap(),
+            vec![(8, 8), (7, 7), (9, 9)]
+        );
+    }
+
+    #[test]
+    fn best_moves_are_read_one_per_line_without_messages() {
+        let mut engine = fake_engine(|line, output| match line {
+            "yxnbest 2" => {
+                let _ = write!(output, "7,7\r\n8,8\r\n");
+            }
+            line => answer(line, output, "name=\"Yixin\"\r\n"),
+        });
+        assert_eq!(engine.best_moves(&[], 2).unwrap(), vec![(7, 7), (8, 8)]);
+    }
+
+    #[test]
+    fn suggestions_are_parsed_apart_from_moves() {
+        assert!(matches!(
+            Response::parse("SUGGEST 7,7", 15),
+            Ok(Response::Suggest((7, 7)))
+        ));
+        assert!(matches!(
+            Response::parse("7,7", 15),
+            Ok(Response::Move((7, 7)))
+        ));
+
+        // Engines suggesting a move are not taken as playing it
+        let mut engine = fake_engine(|line, output| match line {
+            "DONE" => {
+                let _ = write!(output, "SUGGEST 8,8\r\n");
+            }
+            line => answer(line, output, "name=\"Yixin\"\r\n"),
+        });
+        assert!(matches!(
+            engine.send_command(Command::Board(&[(7, 7)])),
+            Ok(Response::Suggest((8, 8)))
+        ));
+    }
+
+    #[test]
+    fn mock_engines_play_their_script_then_the_first_empty_point() {
+        let mut engine = Engine::open_engine(0, &mock_engine("7,7;8,8")).unwrap();
+        assert_eq!(engine.info().name, "selftest");
+        let mut play = |command| match engine.send_command(command) {
+            Ok(Response::Move(m)) => m,
+            r => panic!("{r:?}"),
+        };
+        assert_eq!(play(Command::Board(&[(0, 0)])), (7, 7));
+        assert_eq!(play(Command::Turn((1, 0))), (8, 8));
+        assert_eq!(play(Command::Turn((2, 0))), (3, 0));
+        assert_eq!(play(Command::Board(&[])), (0, 0));
+        engine.close_engine().unwrap();
+
+        assert!(Engine::open_engine(0, &mock_engine("7;7")).is_err());
+    }
+
+    #[test]
+    fn boards_are_sent_as_if_formatted_from_scratch() {
+        let moves = crate::test_utils::moves(200);
+        let mut engine = Engine::open_engine(0, &mock_engine("")).unwrap();
+        engine.sent.clear();
+        // The last boards leave the game, as when matching the next one
+        let mut boards = (1..=moves.len())
+            .map(|ply| &moves[..ply])
+            .collect::<Vec<_>>();
+        boards.extend([&moves[..100], &moves[50..60], &moves[..0]]);
+        for board in &boards {
+            engine.send_command(Command::Board(board)).unwrap();
+        }
+        let expected = boards.iter().map(|board| Command::Board(board).to_string());
+        assert!(engine.sent.iter().cloned().eq(expected));
+
+        // Formatting only the new stone of each board is much faster than formatting them all
+        fn time(moves: &[(u8, u8)], mut format: impl FnMut(&[(u8, u8)])) -> Duration {
+            let start = std::time::Instant::now();
+            for _ in 0..10 {
+                for ply in 0..=moves.len() {
+                    format(&moves[..ply]);
+                }
+            }
+            start.elapsed()
+        }
+        let mut lines = BoardLines::default();
+        let incremental = time(&moves, |board| {
+            std::hint::black_box(lines.update(board));
+        });
+        let full = time(&moves, |board| {
+            std::hint::black_box(Command::Board(board).to_string());
+        });
+        assert!(
+            incremental * 2 < full,
+            "{incremental:?} instead of {full:?}"
+        );
+    }
+}
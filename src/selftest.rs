@@ -0,0 +1,138 @@
+//! # Self-test engine
+//! A trivial engine speaking the Gomocup protocol, always playing the first
+//! empty point of the board. It is exposed through the `selftest-engine`
+//! subcommand so that the whole pipeline can be exercised without a real engine.
+
+use std::io::{BufRead, Write};
+
+/// Runs the self-test engine, reading commands from `input` and writing
+/// responses to `output` until `END` is received or the input is closed.
+pub fn run_selftest_engine<R: BufRead, W: Write>(input: R, output: W) -> std::io::Result<()> {
+    run_scripted_engine(input, output, &[])
+}
+
+/// Runs the self-test engine, answering its first move requests with `script` in order
+/// before falling back to the first empty point of the board.
+pub fn run_scripted_engine<R: BufRead, W: Write>(
+    input: R,
+    mut output: W,
+    script: &[(u8, u8)],
+) -> std::io::Result<()> {
+    let mut script = script.iter().copied();
+    let mut size = 15u8;
+    let mut stones = vec![];
+    let mut lines = input.lines();
+    while let Some(line) = lines.next() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let Some(command) = tokens.next() else {
+            continue;
+        };
+        match command.to_uppercase().as_str() {
+            "START" => {
+                match tokens.next().and_then(|s| s.parse::<u8>().ok()) {
+                    Some(s) if s > 0 => {
+                        size = s;
+                        stones.clear();
+                        write!(output, "OK\r\n")?
+                    }
+                    _ => write!(output, "ERROR unsupported board size\r\n")?,
+                };
+            }
+            "RESTART" => {
+                stones.clear();
+                write!(output, "OK\r\n")?
+            }
+            "BOARD" | "YXBOARD" => {
+                stones.clear();
+                for line in lines.by_ref() {
+                    let line = line?;
+                    if line.trim().eq_ignore_ascii_case("done") {
+                        break;
+                    }
+                    if let Some(stone) = parse_coordinates(&line) {
+                        stones.push(stone)
+                    }
+                }
+                if command.eq_ignore_ascii_case("board") {
+                    play(size, &mut stones, &mut script, &mut output)?
+                }
+            }
+            "TURN" => {
+                if let Some(stone) = tokens.next().and_then(parse_coordinates) {
+                    stones.push(stone)
+                }
+                play(size, &mut stones, &mut script, &mut output)?
+            }
+            "TAKEBACK" => match tokens.next().and_then(parse_coordinates) {
+                Some(stone) if stones.contains(&stone) => {
+                    stones.retain(|s| *s != stone);
+                    write!(output, "OK\r\n")?
+                }
+                _ => write!(output, "ERROR no stone to take back\r\n")?,
+            },
+            "BEGIN" => play(size, &mut stones, &mut script, &mut output)?,
+            "ABOUT" => write!(
+                output,
+                "name=\"selftest\", version=\"{}\", author=\"renju_move_matching\"\r\n",
+                env!("CARGO_PKG_VERSION")
+            )?,
+            "YXSHOWFORBID" => write!(output, "FORBID .\r\n")?,
+            "YXNBEST" => {
+                let n = tokens
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .unwrap_or(1);
+                // Candidates are sent as messages as Yixin does, followed by the best one
+                let mut empty = (0..size)
+                    .flat_map(|y| (0..size).map(move |x| (x, y)))
+                    .filter(|p| !stones.contains(p))
+                    .take(n)
+                    .peekable();
+                match empty.peek().copied() {
+                    Some((best_x, best_y)) => {
+                        for (x, y) in empty {
+                            write!(output, "MESSAGE {x},{y}\r\n")?
+                        }
+                        write!(output, "{best_x},{best_y}\r\n")?
+                    }
+                    None => write!(output, "ERROR board is full\r\n")?,
+                }
+            }
+            "INFO" | "YXHASHCLEAR" | "YXSTOP" => {}
+            "END" => break,
+            _ => write!(output, "UNKNOWN {command}\r\n")?,
+        }
+        output.flush()?
+    }
+    Ok(())
+}
+
+/// Plays the next scripted move, or the first empty point of the board, and sends it to the
+/// manager.
+fn play<W: Write>(
+    size: u8,
+    stones: &mut Vec<(u8, u8)>,
+    script: &mut impl Iterator<Item = (u8, u8)>,
+    output: &mut W,
+) -> std::io::Result<()> {
+    let empty = script.next().or_else(|| {
+        (0..size)
+            .flat_map(|y| (0..size).map(move |x| (x, y)))
+            .find(|p| !stones.contains(p))
+    });
+    match empty {
+        Some((x, y)) => {
+            stones.push((x, y));
+            write!(output, "{x},{y}\r\n")
+        }
+        None => write!(output, "ERROR board is full\r\n"),
+    }
+}
+
+pub(crate) fn parse_coordinates(s: &str) -> Option<(u8, u8)> {
+    let mut coords = s.trim().split(',');
+    let x = coords.next()?.trim().parse().ok()?;
+    let y = coords.next()?.trim().parse().ok()?;
+    Some((x, y))
+}
@@ -0,0 +1,217 @@
+//! # Statistics
+//! Helpers to tell whether a difference of accuracy between two engines is significant, and
+//! to plan runs able to detect one.
+
+/// Quantile function of the standard normal distribution, using Acklam's approximation
+/// (relative error below 1.15e-9).
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.)
+    };
+    if p < P_LOW {
+        tail((-2. * p.ln()).sqrt())
+    } else if p <= 1. - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.)
+    } else {
+        -tail((-2. * (1. - p).ln()).sqrt())
+    }
+}
+
+/// Cumulative distribution function of the standard normal distribution, from the
+/// complementary error function of Numerical Recipes (relative error below 1.2e-7).
+pub fn normal_cdf(x: f64) -> f64 {
+    const C: [f64; 10] = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ];
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1. / (1. + z / 2.);
+    let polynomial = C.iter().rev().fold(0., |sum, c| sum * t + c);
+    let erfc = t * (-z * z + polynomial).exp();
+    if x >= 0. {
+        1. - erfc / 2.
+    } else {
+        erfc / 2.
+    }
+}
+
+/// Two-sided two-proportion z-test of the difference between the accuracies of `b` and `a`,
+/// given as `(matches, total)`, with a pooled variance. Returns the z statistic, positive when
+/// `b` is more accurate, and the p-value, or `None` when the difference cannot be tested
+/// because either has no positions or every position has the same outcome.
+pub fn two_proportion_z_test(a: (u32, u32), b: (u32, u32)) -> Option<(f64, f64)> {
+    let (matches_a, total_a) = (a.0 as f64, a.1 as f64);
+    let (matches_b, total_b) = (b.0 as f64, b.1 as f64);
+    let pooled = (matches_a + matches_b) / (total_a + total_b);
+    let variance = pooled * (1. - pooled) * (1. / total_a + 1. / total_b);
+    if a.1 == 0 || b.1 == 0 || variance <= 0. {
+        return None;
+    }
+    let z = (matches_b / total_b - matches_a / total_a) / variance.sqrt();
+    Some((z, 2. * (1. - normal_cdf(z.abs()))))
+}
+
+/// Number of positions each engine must be matched on to detect, with a two-sided
+/// two-proportion z-test, a difference of accuracy of `effect` from `baseline` (both as
+/// fractions) at significance level `alpha` with probability `power`.
+///
+/// Returns `None` when `alpha` or `power` is not strictly between 0 and 1, or when the effect
+/// does not change the baseline once clamped to [0, 1], since no number of positions detects
+/// it.
+pub fn required_positions(baseline: f64, effect: f64, alpha: f64, power: f64) -> Option<u64> {
+    let (p1, p2) = (baseline, (baseline + effect).clamp(0., 1.));
+    let is_probability = |x: f64| x > 0. && x < 1.;
+    if !is_probability(alpha) || !is_probability(power) || !(0. ..=1.).contains(&p1) || p1 == p2 {
+        return None;
+    }
+    let pooled = (p1 + p2) / 2.;
+    let z_alpha = normal_quantile(1. - alpha / 2.);
+    let z_beta = normal_quantile(power);
+    let n = (z_alpha * (2. * pooled * (1. - pooled)).sqrt()
+        + z_beta * (p1 * (1. - p1) + p2 * (1. - p2)).sqrt())
+    .powi(2)
+        / (p2 - p1).powi(2);
+    Some(n.ceil() as u64)
+}
+
+/// Wilson score interval of a proportion of `successes` out of `total`, at this confidence
+/// level. Unlike the normal approximation, it stays within [0, 1] and does not collapse for
+/// proportions of 0 or 1. Its width shrinks as `total` grows.
+pub fn wilson_interval(successes: u32, total: u32, confidence: f64) -> (f64, f64) {
+    if total == 0 {
+        return (0., 1.);
+    }
+    let n = total as f64;
+    let p = successes as f64 / n;
+    let z = normal_quantile(1. - (1. - confidence) / 2.);
+    let center = (p + z * z / (2. * n)) / (1. + z * z / n);
+    let half_width = z / (1. + z * z / n) * (p * (1. - p) / n + z * z / (4. * n * n)).sqrt();
+    ((center - half_width).max(0.), (center + half_width).min(1.))
+}
+
+/// Indices of a uniformly random subset of `count` elements out of `len`, in increasing
+/// order. The subset only depends on `seed`, so that runs can be repeated.
+pub fn sample_indices(len: usize, count: usize, seed: u64) -> Vec<usize> {
+    // SplitMix64, which is more than good enough to pick games.
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    // Partial Fisher-Yates shuffle.
+    let count = count.min(len);
+    let mut indices = (0..len).collect::<Vec<_>>();
+    for i in 0..count {
+        let j = i + (next() % (len - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+    indices.truncate(count);
+    indices.sort_unstable();
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn z_tests() {
+        let assert_test = |a, b, (z, p_value): (f64, f64)| {
+            let (actual_z, actual_p_value) = two_proportion_z_test(a, b).unwrap();
+            assert!((actual_z - z).abs() < 1e-4, "z = {actual_z} instead of {z}");
+            assert!(
+                (actual_p_value - p_value).abs() < 1e-5,
+                "p = {actual_p_value} instead of {p_value}"
+            );
+        };
+        assert_test((50, 100), (60, 100), (1.42134, 0.155218));
+        assert_test((65, 100), (45, 100), (-2.84268, 0.004474));
+        assert_test((300, 1000), (340, 1200), (-0.85703, 0.391426));
+        assert_test((50, 100), (50, 100), (0., 1.));
+        assert_eq!(two_proportion_z_test((0, 0), (5, 10)), None);
+        assert_eq!(two_proportion_z_test((10, 10), (20, 20)), None);
+
+        assert!((normal_cdf(1.959964) - 0.975).abs() < 1e-6);
+        assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+    }
+
+    #[test]
+    fn wilson_bounds() {
+        assert_close(wilson_interval(5, 10, 0.95), (0.2366, 0.7634));
+        assert_close(wilson_interval(0, 10, 0.95), (0., 0.2775));
+        assert_close(wilson_interval(10, 10, 0.95), (0.7225, 1.));
+        assert_close(wilson_interval(500, 1000, 0.95), (0.4691, 0.5309));
+        assert_eq!(wilson_interval(0, 0, 0.95), (0., 1.));
+    }
+
+    #[test]
+    fn required_positions_of_detectable_effects() {
+        assert_eq!(required_positions(0.5, 0.05, 0.05, 0.8), Some(1565));
+        assert_eq!(required_positions(0.5, -0.05, 0.05, 0.8), Some(1565));
+        assert_eq!(required_positions(0.5, 0., 0.05, 0.8), None);
+        // The accuracy cannot go above 100%
+        assert_eq!(required_positions(1., 0.05, 0.05, 0.8), None);
+        for (alpha, power) in [
+            (0., 0.8),
+            (1., 0.8),
+            (0.05, 0.),
+            (0.05, 1.),
+            (f64::NAN, 0.8),
+        ] {
+            assert_eq!(required_positions(0.5, 0.05, alpha, power), None);
+        }
+    }
+}
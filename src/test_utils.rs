@@ -0,0 +1,136 @@
+//! Helpers shared by the unit tests of the crate.
+
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    db::{export_database, Color, DbFilter, Game, PsqConfig, Sampling, WhrConfig},
+    interface::CheckpointConfig,
+    move_matching::{MatchingOptions, DEFAULT_MOVE_BANDS},
+    plot::ResultsFormat,
+    profile::EngineProfile,
+    protocol::{EngineConfig, Rule, MOCK_PREFIX},
+    MatchConfig,
+};
+
+/// A small RenjuNet XML database: player 1 beats player 2, who beats player 3, over two
+/// tournaments, with a draw and an unrated game.
+pub const XML_DATABASE: &str = r#"<?xml version="1.0"?>
+<database version="1.0" date="2024-08-21">
+<players>
+<player id="1" name="Ando" surname="Meritee" />
+<player id="2" name="Vladimir" surname="Sushkov" />
+<player id="3" name="Ana" surname="" />
+</players>
+<tournaments>
+<tournament id="1" start="2006-04-29" end="2006-05-05" />
+<tournament id="2" start="2007-04-29" end="2007-05-05" />
+</tournaments>
+<games>
+<game id="1" tournament="1" rule="1" black="1" white="2" bresult="1">
+<move>h8 h9 h7 h6 i9 g7 f8 i8 f10 j7 k6 j8</move>
+</game>
+<game id="2" tournament="1" rule="1" black="2" white="1" bresult="0">
+<move>h8 i9 j10 g7 f8 i8 f10 j7 k6 j8 i7 j6</move>
+</game>
+<game id="3" tournament="1" rule="1" black="2" white="3" bresult="1">
+<move>h8 h9 h7 h6 i9 g7 f8 i8 f10 j7</move>
+</game>
+<game id="4" tournament="2" rule="1" black="3" white="2" bresult="0">
+<move>h8 i9 j10 g7 f8 i8 f10 j7 k6 j8</move>
+</game>
+<game id="5" tournament="2" rule="1" black="1" white="3" bresult="0.5">
+<move>h8 h9 h7 h6 i9 g7 f8 i8 f10 j7 k6 j8 i7 j6 j5</move>
+</game>
+<game id="6" tournament="2" rule="1" black="1" white="3" bresult="1" rated="0">
+<move>h8 h9 h7 h6 i9 g7 f8 i8</move>
+</game>
+</games>
+</database>
+"#;
+
+/// Writes [`XML_DATABASE`] to a temporary file.
+pub fn xml_database(name: &str) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, XML_DATABASE).unwrap();
+    path
+}
+
+/// A game between players of these ratings, won by black.
+pub fn game(black_elo: u64, white_elo: u64, moves: Vec<(u8, u8)>) -> Game {
+    Game {
+        black_elo,
+        white_elo,
+        moves,
+        winner: Some(Color::Black),
+        black_id: 1,
+        white_id: 2,
+        black_name: None,
+        white_name: None,
+    }
+}
+
+/// The first `n` points of a 15x15 board, row by row, as moves of a game without duplicates.
+pub fn moves(n: usize) -> Vec<(u8, u8)> {
+    (0..n).map(|i| ((i % 15) as u8, (i / 15) as u8)).collect()
+}
+
+/// A path in a temporary directory unique to this test process, removed first if it exists.
+pub fn temp_path(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("renju_move_matching-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(name);
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_dir_all(&path);
+    path
+}
+
+/// Settings of a mock engine answering with the moves of `script`, then the first empty point.
+pub fn mock_engine(script: &str) -> EngineConfig {
+    EngineConfig {
+        command: format!("{MOCK_PREFIX}{script}"),
+        move_time: 100,
+        rule: Rule::Renju,
+        board_size: 15,
+        profile: EngineProfile::yixin(),
+        threads: 1,
+        max_memory: None,
+        deterministic: false,
+        start_timeout: Duration::from_secs(5),
+        workdir: None,
+        env: vec![],
+    }
+}
+
+/// Settings of a run named after a temporary path, matching `games` with a mock engine on a
+/// single worker without the TUI.
+pub fn match_config(name: &str, games: &[Game]) -> MatchConfig {
+    let database_path = temp_path(&format!("{name}.jsonl"));
+    export_database(games, &database_path).unwrap();
+    MatchConfig {
+        name: temp_path(name).to_str().unwrap().to_string(),
+        engine: mock_engine(""),
+        database_path,
+        threads: 1,
+        max_consecutive_errors: 10,
+        games_count: None,
+        sample: Sampling::default(),
+        seed: 0,
+        auto_tune: false,
+        game_log: None,
+        event_log: None,
+        matching: MatchingOptions::default(),
+        move_bands: DEFAULT_MOVE_BANDS.to_vec(),
+        whr: WhrConfig::default(),
+        filter: DbFilter::default(),
+        rating_cache: None,
+        psq: PsqConfig::default(),
+        deterministic: false,
+        segment_plots: None,
+        rating_distribution_csv: false,
+        results_format: ResultsFormat::Csv,
+        checkpoints: CheckpointConfig::default(),
+        dry_run: false,
+        no_tui: true,
+    }
+}